@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+// how long a client waits for the monitoring loop to pick up its request
+pub const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// one json object per line, the reply is one json object per line as well
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlRequest {
+    Stats,
+    Switch,
+    Rules,
+    // the latest drops of every component, oldest first
+    RecentDrops,
+    // reloads the policy the ship was started with unless another file is given
+    Reload { policy: Option<String> },
+    Enable { component: String },
+    Disable { component: String },
+    LogLevel { level: String },
+}
+
+// sends one request and waits for its reply, for operator tools
+pub fn control_request(path: &Path, request: &ControlRequest) -> io::Result<Value> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT * 2))?;
+    writeln!(stream, "{}", serde_json::to_string(request)?)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(serde_json::from_str(&reply)?)
+}
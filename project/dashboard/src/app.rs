@@ -0,0 +1,164 @@
+use control_protocol::{control_request, ControlRequest};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+// the parts of the control socket replies the dashboard shows
+
+#[derive(Debug, Deserialize)]
+pub struct ComponentStats {
+    pub name: String,
+    pub iface: String,
+    pub enabled: bool,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub throughput_sent_mbps: f64,
+    pub throughput_received_mbps: f64,
+    pub packet_rate_sent: f64,
+    pub packet_rate_received: f64,
+    pub drops: BTreeMap<String, u64>,
+    pub sentences: BTreeMap<String, u64>,
+    pub denied: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DropEvent {
+    pub at: f64,
+    pub component: String,
+    pub prefix: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SwitchEntry {
+    pub mac: String,
+    pub port: usize,
+    pub component: Option<String>,
+    pub kind: String,
+}
+
+#[derive(Deserialize)]
+struct StatsReply {
+    components: Vec<ComponentStats>,
+}
+
+#[derive(Deserialize)]
+struct DropsReply {
+    drops: Vec<DropEvent>,
+}
+
+#[derive(Deserialize)]
+struct SwitchReply {
+    entries: Vec<SwitchEntry>,
+}
+
+pub struct App {
+    pub socket: PathBuf,
+    pub components: Vec<ComponentStats>,
+    pub recent_drops: Vec<DropEvent>,
+    pub switch: Vec<SwitchEntry>,
+    // component whose sentence mix is shown
+    pub selected: usize,
+    // last error talking to the ship, shown instead of stale data going unnoticed
+    pub error: Option<String>,
+    // component waiting for the operator to confirm it is to be disabled
+    pub confirm_disable: Option<String>,
+    pub should_quit: bool,
+}
+
+impl App {
+    pub fn new(socket: PathBuf) -> Self {
+        App {
+            socket,
+            components: Vec::new(),
+            recent_drops: Vec::new(),
+            switch: Vec::new(),
+            selected: 0,
+            error: None,
+            confirm_disable: None,
+            should_quit: false,
+        }
+    }
+
+    pub fn refresh(&mut self) {
+        let refreshed = self
+            .request::<StatsReply>(&ControlRequest::Stats)
+            .and_then(|stats| {
+                let drops = self.request::<DropsReply>(&ControlRequest::RecentDrops)?;
+                let switch = self.request::<SwitchReply>(&ControlRequest::Switch)?;
+                Ok((stats, drops, switch))
+            });
+
+        match refreshed {
+            Ok((stats, drops, switch)) => {
+                self.components = stats.components;
+                self.recent_drops = drops.drops;
+                self.switch = switch.entries;
+                self.selected = self.selected.min(self.components.len().saturating_sub(1));
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.components.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    // enables the selected component if it is disabled, a disable waits for confirm_pending
+    pub fn toggle_selected(&mut self) {
+        match self.components.get(self.selected) {
+            Some(component) if component.enabled => {
+                self.confirm_disable = Some(component.name.clone());
+            }
+            Some(component) => {
+                let request = ControlRequest::Enable {
+                    component: component.name.clone(),
+                };
+                self.send(&request);
+            }
+            None => {}
+        }
+    }
+
+    // disables the component the operator was asked about, or forgets it when not confirmed
+    pub fn confirm_pending(&mut self, confirmed: bool) {
+        let Some(component) = self.confirm_disable.take() else {
+            return;
+        };
+        if confirmed {
+            self.send(&ControlRequest::Disable { component });
+        }
+    }
+
+    // the refresh clears the error, so a failed request is reported once it is done
+    fn send(&mut self, request: &ControlRequest) {
+        let sent = self.request::<Value>(request);
+        self.refresh();
+        if let Err(e) = sent {
+            self.error = Some(e);
+        }
+    }
+
+    fn request<T: DeserializeOwned>(&self, request: &ControlRequest) -> Result<T, String> {
+        let reply = control_request(&self.socket, request)
+            .map_err(|e| format!("{}: {}", self.socket.display(), e))?;
+
+        if reply["ok"].as_bool() != Some(true) {
+            return Err(reply["error"]
+                .as_str()
+                .unwrap_or("unknown error")
+                .to_string());
+        }
+
+        serde_json::from_value(reply["result"].clone()).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,74 @@
+use app::App;
+use clap::Parser;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+mod app;
+mod ui;
+
+#[derive(Parser)]
+#[command(about = "Live view of a running ship through its control socket")]
+struct Cli {
+    /// Control socket of the ship, see `simulation run --control-socket`
+    #[arg(long, default_value = "/tmp/l7-control.sock")]
+    socket: PathBuf,
+
+    /// Milliseconds between two refreshes
+    #[arg(long, default_value_t = 1000)]
+    interval_ms: u64,
+}
+
+fn main() -> io::Result<()> {
+    let cli = Cli::parse();
+    let mut app = App::new(cli.socket);
+
+    let mut terminal = ratatui::init();
+    let result = run(
+        &mut terminal,
+        &mut app,
+        Duration::from_millis(cli.interval_ms),
+    );
+    ratatui::restore();
+    result
+}
+
+fn run(
+    terminal: &mut ratatui::DefaultTerminal,
+    app: &mut App,
+    interval: Duration,
+) -> io::Result<()> {
+    let mut last_refresh: Option<Instant> = None;
+
+    while !app.should_quit {
+        if last_refresh.is_none_or(|last_refresh| last_refresh.elapsed() >= interval) {
+            app.refresh();
+            last_refresh = Some(Instant::now());
+        }
+
+        terminal.draw(|frame| ui::draw(frame, app))?;
+
+        // keys are handled as soon as they arrive, the data only on the next refresh
+        if event::poll(Duration::from_millis(100))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                // while a disable waits for confirmation every other key cancels it
+                if app.confirm_disable.is_some() {
+                    app.confirm_pending(key.code == KeyCode::Char('y'));
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => app.should_quit = true,
+                    KeyCode::Down | KeyCode::Char('j') => app.select_next(),
+                    KeyCode::Up | KeyCode::Char('k') => app.select_previous(),
+                    KeyCode::Char(' ') => app.toggle_selected(),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use crate::app::App;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{
+    BarChart, Block, Borders, List, ListItem, Paragraph, Row, Table, TableState,
+};
+use ratatui::Frame;
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [header, components, details, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(app.components.len() as u16 + 3),
+        Constraint::Min(8),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let status = match &app.error {
+        Some(e) => Line::styled(format!(" {} - {}", app.socket.display(), e), Color::Red),
+        None => Line::styled(
+            format!(" {} - connected", app.socket.display()),
+            Color::Green,
+        ),
+    };
+    frame.render_widget(Paragraph::new(status), header);
+
+    draw_components(frame, app, components);
+
+    let [sentences, drops, switch] = Layout::horizontal([
+        Constraint::Percentage(35),
+        Constraint::Percentage(40),
+        Constraint::Percentage(25),
+    ])
+    .areas(details);
+    draw_sentences(frame, app, sentences);
+    draw_drops(frame, app, drops);
+    draw_switch(frame, app, switch);
+
+    let help = match &app.confirm_disable {
+        Some(component) => Paragraph::new(format!(
+            " disable {}? it will neither send nor receive  y confirm  any other key cancels",
+            component
+        ))
+        .style(Style::default().fg(Color::Yellow)),
+        None => Paragraph::new(" q quit  ↑/↓ select component  space enable/disable")
+            .style(Style::default().fg(Color::DarkGray)),
+    };
+    frame.render_widget(help, footer);
+}
+
+fn draw_components(frame: &mut Frame, app: &App, area: Rect) {
+    let header = Row::new(vec![
+        "COMPONENT",
+        "IFACE",
+        "STATE",
+        "SEND Mbit/s",
+        "RECV Mbit/s",
+        "SEND pkt/s",
+        "RECV pkt/s",
+        "SENT",
+        "RECEIVED",
+        "DROPS",
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let rows = app.components.iter().map(|component| {
+        let state = if component.enabled {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        Row::new(vec![
+            component.name.clone(),
+            component.iface.clone(),
+            state.to_string(),
+            format!("{:.2}", component.throughput_sent_mbps),
+            format!("{:.2}", component.throughput_received_mbps),
+            format!("{:.0}", component.packet_rate_sent),
+            format!("{:.0}", component.packet_rate_received),
+            component.packets_sent.to_string(),
+            component.packets_received.to_string(),
+            component.drops.values().sum::<u64>().to_string(),
+        ])
+    });
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(16),
+            Constraint::Length(8),
+            Constraint::Length(9),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(11),
+            Constraint::Length(11),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(8),
+        ],
+    )
+    .header(header)
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    .block(Block::default().borders(Borders::ALL).title(" Components "));
+
+    let mut state = TableState::default().with_selected(Some(app.selected));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_sentences(frame: &mut Frame, app: &App, area: Rect) {
+    let (title, bars): (String, Vec<(&str, u64)>) = match app.components.get(app.selected) {
+        Some(component) => (
+            format!(" Sentences sent by {} ", component.name),
+            component
+                .sentences
+                .iter()
+                .map(|(prefix, count)| (prefix.as_str(), *count))
+                .collect(),
+        ),
+        None => (String::from(" Sentences "), Vec::new()),
+    };
+
+    let denied: u64 = app
+        .components
+        .get(app.selected)
+        .map(|component| component.denied.values().sum())
+        .unwrap_or(0);
+
+    let chart = BarChart::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .title_bottom(format!(" denied by policy: {} ", denied)),
+        )
+        .data(bars.as_slice())
+        .bar_width(7)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(Color::Cyan))
+        .value_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+    frame.render_widget(chart, area);
+}
+
+fn draw_drops(frame: &mut Frame, app: &App, area: Rect) {
+    // newest first, as many as fit
+    let items: Vec<ListItem> = app
+        .recent_drops
+        .iter()
+        .rev()
+        .take(area.height.saturating_sub(2) as usize)
+        .map(|drop_event| {
+            ListItem::new(format!(
+                "{:>8.3}s {:<14} {:<7} {}",
+                drop_event.at, drop_event.component, drop_event.prefix, drop_event.reason
+            ))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .style(Style::default().fg(Color::Yellow))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Recent drops "),
+        );
+    frame.render_widget(list, area);
+}
+
+fn draw_switch(frame: &mut Frame, app: &App, area: Rect) {
+    let rows = app.switch.iter().map(|entry| {
+        Row::new(vec![
+            entry.mac.clone(),
+            entry.port.to_string(),
+            entry.component.clone().unwrap_or_default(),
+            entry.kind.clone(),
+        ])
+    });
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(17),
+            Constraint::Length(4),
+            Constraint::Min(8),
+            Constraint::Length(7),
+        ],
+    )
+    .header(
+        Row::new(vec!["MAC", "PORT", "COMPONENT", "KIND"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Switch table "),
+    );
+    frame.render_widget(table, area);
+}
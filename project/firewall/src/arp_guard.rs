@@ -0,0 +1,295 @@
+use crate::{format_mac, DropReason, SourceBinding};
+use packet_parser::PacketParser;
+use pnet::packet::arp::{ArpHardwareTypes, ArpOperations, ArpPacket, MutableArpPacket};
+use pnet::packet::ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket};
+use pnet::util::MacAddr;
+use std::net::{IpAddr, Ipv4Addr};
+
+const ARP_PACKET_LEN: usize = 28;
+// shortest ethernet frame without its frame check sequence, shorter replies are padded
+const MIN_FRAME_LEN: usize = 60;
+
+// what the ship does with an arp frame
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArpVerdict {
+    // the ship answers on behalf of the component owning the address, the request goes no further
+    Reply(Vec<u8>),
+    Drop(DropReason),
+    Forward,
+}
+
+// the addresses an ip of the policy may be announced with
+#[derive(Debug, Clone)]
+struct ArpBinding {
+    port: usize,
+    macs: Vec<[u8; 6]>,
+}
+
+// answers arp requests for the components' addresses and drops the frames contradicting them
+pub struct ArpGuard {
+    bindings: hashbrown::HashMap<Ipv4Addr, ArpBinding>,
+    // answer the requests for the addresses of the policy instead of flooding them
+    pub proxy: bool,
+    // drop the arp frames that contradict the policy
+    pub inspection: bool,
+    pub proxy_replies: u64,
+    pub anomalies: u64,
+}
+
+impl Default for ArpGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArpGuard {
+    pub fn new() -> Self {
+        ArpGuard {
+            bindings: hashbrown::HashMap::new(),
+            proxy: true,
+            inspection: true,
+            proxy_replies: 0,
+            anomalies: 0,
+        }
+    }
+
+    // every ipv4 address of the component resolves to its first mac, the others may announce it as well
+    pub fn bind(&mut self, port: usize, binding: &SourceBinding) {
+        binding.ips.iter().for_each(|ip| {
+            let IpAddr::V4(ip) = ip else {
+                return;
+            };
+            self.bindings.insert(
+                *ip,
+                ArpBinding {
+                    port,
+                    macs: binding.macs.clone(),
+                },
+            );
+        });
+    }
+
+    // drops the addresses of a port, used when the policy of its component changes
+    pub fn unbind(&mut self, port: usize) {
+        self.bindings.retain(|_, binding| binding.port != port);
+    }
+
+    // the address the ship answers with for an ip of the policy
+    pub fn resolve(&self, ip: &Ipv4Addr) -> Option<[u8; 6]> {
+        self.bindings
+            .get(ip)
+            .and_then(|binding| binding.macs.first().copied())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Ipv4Addr, usize, [u8; 6])> {
+        self.bindings
+            .iter()
+            .filter_map(|(ip, binding)| Some((ip, binding.port, *binding.macs.first()?)))
+    }
+
+    // none when the frame is not arp at all, tagged or not, port is where the frame came in
+    pub fn inspect(&mut self, frame: &[u8], port: usize) -> Option<ArpVerdict> {
+        let layout = PacketParser::new(frame).layout()?;
+        if layout.ethertype != EtherTypes::Arp {
+            return None;
+        }
+        let ethernet_packet = EthernetPacket::new(frame)?;
+
+        let arp_packet = match ArpPacket::new(&frame[layout.network_offset..]) {
+            Some(arp_packet)
+                if arp_packet.get_hardware_type() == ArpHardwareTypes::Ethernet
+                    && arp_packet.get_protocol_type() == EtherTypes::Ipv4 =>
+            {
+                arp_packet
+            }
+            _ if !self.inspection => return Some(ArpVerdict::Forward),
+            _ => return Some(self.anomaly(String::from("malformed arp frame"))),
+        };
+
+        let operation = arp_packet.get_operation();
+        let sender_mac = arp_packet.get_sender_hw_addr();
+        let sender_ip = arp_packet.get_sender_proto_addr();
+        let target_ip = arp_packet.get_target_proto_addr();
+
+        if self.inspection {
+            // the arp payload must agree with the ethernet header it travels in
+            if sender_mac != ethernet_packet.get_source() {
+                return Some(self.anomaly(format!(
+                    "sender {} sent from {}",
+                    sender_mac,
+                    ethernet_packet.get_source()
+                )));
+            }
+
+            // probes announce nothing, every other sender claims its ip
+            if !sender_ip.is_unspecified() {
+                if let Some(binding) = self.bindings.get(&sender_ip) {
+                    let gratuitous = if sender_ip == target_ip {
+                        "gratuitous "
+                    } else {
+                        ""
+                    };
+                    if !binding.macs.contains(&sender_mac.octets()) {
+                        return Some(self.anomaly(format!(
+                            "{}{} claimed by {}",
+                            gratuitous, sender_ip, sender_mac
+                        )));
+                    }
+                    // the right addresses, but from the port of another component
+                    if binding.port != port {
+                        let owner = binding.port;
+                        return Some(self.anomaly(format!(
+                            "{}{} claimed on port {} instead of {}",
+                            gratuitous, sender_ip, port, owner
+                        )));
+                    }
+                }
+            }
+        }
+
+        if self.proxy && operation == ArpOperations::Request && sender_ip != target_ip {
+            if let Some(target_mac) = self.resolve(&target_ip) {
+                if let Some(reply) =
+                    arp_reply(&frame[..layout.network_offset], &arp_packet, target_mac)
+                {
+                    self.proxy_replies += 1;
+                    log::debug!(
+                        "| PROXY ARP {} IS AT {} FOR {}",
+                        target_ip,
+                        format_mac(&target_mac),
+                        sender_ip
+                    );
+                    return Some(ArpVerdict::Reply(reply));
+                }
+            }
+        }
+
+        Some(ArpVerdict::Forward)
+    }
+
+    fn anomaly(&mut self, detail: String) -> ArpVerdict {
+        self.anomalies += 1;
+        log::warn!("| ARP ANOMALY: {}", detail.to_uppercase());
+        ArpVerdict::Drop(DropReason::ArpSpoofing(detail))
+    }
+}
+
+// the reply the owner of the target address would have sent, in the same vlan as the request
+fn arp_reply(request_header: &[u8], request: &ArpPacket, target_mac: [u8; 6]) -> Option<Vec<u8>> {
+    let target_mac = MacAddr::from(target_mac);
+    let mut buffer = request_header.to_vec();
+    buffer.resize(
+        (request_header.len() + ARP_PACKET_LEN).max(MIN_FRAME_LEN),
+        0,
+    );
+
+    let mut ethernet_packet = MutableEthernetPacket::new(&mut buffer)?;
+    ethernet_packet.set_destination(request.get_sender_hw_addr());
+    ethernet_packet.set_source(target_mac);
+
+    let mut arp_packet = MutableArpPacket::new(&mut buffer[request_header.len()..])?;
+    arp_packet.set_hardware_type(ArpHardwareTypes::Ethernet);
+    arp_packet.set_protocol_type(EtherTypes::Ipv4);
+    arp_packet.set_hw_addr_len(6);
+    arp_packet.set_proto_addr_len(4);
+    arp_packet.set_operation(ArpOperations::Reply);
+    arp_packet.set_sender_hw_addr(target_mac);
+    arp_packet.set_sender_proto_addr(request.get_target_proto_addr());
+    arp_packet.set_target_hw_addr(request.get_sender_hw_addr());
+    arp_packet.set_target_proto_addr(request.get_sender_proto_addr());
+
+    Some(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GPS_MAC: &str = "02:00:00:00:00:01";
+    const RADAR_MAC: &str = "02:00:00:00:00:02";
+
+    fn guard() -> ArpGuard {
+        let mut guard = ArpGuard::new();
+        guard.bind(
+            0,
+            &SourceBinding::new(GPS_MAC, "10.42.0.1", &[], &[]).unwrap(),
+        );
+        guard.bind(
+            1,
+            &SourceBinding::new(RADAR_MAC, "10.42.0.2", &[], &[]).unwrap(),
+        );
+        guard
+    }
+
+    fn arp_frame(
+        operation: pnet::packet::arp::ArpOperation,
+        sender_mac: &str,
+        sender_ip: &str,
+        target_ip: &str,
+    ) -> Vec<u8> {
+        let sender_mac: MacAddr = sender_mac.parse().unwrap();
+        let mut frame = vec![0u8; 14 + ARP_PACKET_LEN];
+        let mut ethernet_packet = MutableEthernetPacket::new(&mut frame).unwrap();
+        ethernet_packet.set_destination(MacAddr::broadcast());
+        ethernet_packet.set_source(sender_mac);
+        ethernet_packet.set_ethertype(EtherTypes::Arp);
+
+        let mut arp_packet = MutableArpPacket::new(&mut frame[14..]).unwrap();
+        arp_packet.set_hardware_type(ArpHardwareTypes::Ethernet);
+        arp_packet.set_protocol_type(EtherTypes::Ipv4);
+        arp_packet.set_hw_addr_len(6);
+        arp_packet.set_proto_addr_len(4);
+        arp_packet.set_operation(operation);
+        arp_packet.set_sender_hw_addr(sender_mac);
+        arp_packet.set_sender_proto_addr(sender_ip.parse().unwrap());
+        arp_packet.set_target_proto_addr(target_ip.parse().unwrap());
+        frame
+    }
+
+    #[test]
+    fn proxied_replies_are_padded_to_the_shortest_frame() {
+        let request = arp_frame(ArpOperations::Request, GPS_MAC, "10.42.0.1", "10.42.0.2");
+        let Some(ArpVerdict::Reply(reply)) = guard().inspect(&request, 0) else {
+            panic!("no proxied reply");
+        };
+
+        assert_eq!(reply.len(), MIN_FRAME_LEN);
+        let arp_packet = ArpPacket::new(&reply[14..]).unwrap();
+        assert_eq!(arp_packet.get_operation(), ArpOperations::Reply);
+        assert_eq!(
+            arp_packet.get_sender_hw_addr(),
+            RADAR_MAC.parse::<MacAddr>().unwrap()
+        );
+        assert!(reply[14 + ARP_PACKET_LEN..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn a_binding_is_only_announced_from_its_port() {
+        let mut guard = guard();
+        let announcement = arp_frame(ArpOperations::Reply, GPS_MAC, "10.42.0.1", "10.42.0.2");
+        assert_eq!(guard.inspect(&announcement, 0), Some(ArpVerdict::Forward));
+        assert!(matches!(
+            guard.inspect(&announcement, 1),
+            Some(ArpVerdict::Drop(DropReason::ArpSpoofing(_)))
+        ));
+        assert_eq!(guard.anomalies, 1);
+    }
+
+    #[test]
+    fn a_binding_is_only_announced_with_its_macs() {
+        let mut guard = guard();
+        let announcement = arp_frame(ArpOperations::Reply, RADAR_MAC, "10.42.0.1", "10.42.0.1");
+        assert!(matches!(
+            guard.inspect(&announcement, 0),
+            Some(ArpVerdict::Drop(DropReason::ArpSpoofing(_)))
+        ));
+    }
+
+    #[test]
+    fn without_inspection_any_port_may_announce() {
+        let mut guard = guard();
+        guard.inspection = false;
+        let announcement = arp_frame(ArpOperations::Reply, GPS_MAC, "10.42.0.1", "10.42.0.2");
+        assert_eq!(guard.inspect(&announcement, 1), Some(ArpVerdict::Forward));
+    }
+}
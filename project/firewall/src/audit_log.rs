@@ -0,0 +1,390 @@
+use crate::{first_tag_block, DropReason};
+use chrono::{SecondsFormat, Utc};
+use packet_parser::PacketParser;
+use pnet::util::MacAddr;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AuditLevel {
+    Off,
+    Drops,
+    All,
+}
+
+impl AuditLevel {
+    pub fn parse(level: &str) -> Option<Self> {
+        match level.to_lowercase().as_str() {
+            "off" => Some(AuditLevel::Off),
+            "drops" => Some(AuditLevel::Drops),
+            "all" => Some(AuditLevel::All),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AuditEvent<'a> {
+    timestamp: String,
+    ingress: &'a str,
+    interface: &'a str,
+    source_mac: Option<String>,
+    source_ip: Option<String>,
+    // from the IEC 61162-450 tag block, when the sentences carry one
+    source_id: Option<String>,
+    destination_id: Option<String>,
+    line_count: Option<u32>,
+    sentence: &'a str,
+    verdict: &'a str,
+    reason: Option<String>,
+    matched_rule: Option<String>,
+}
+
+impl<'a> AuditEvent<'a> {
+    fn new(ingress: &'a str, interface: &'a str, frame: &[u8], sentence: &'a str) -> Self {
+        let source_mac = (frame.len() >= 12).then(|| {
+            MacAddr::new(frame[6], frame[7], frame[8], frame[9], frame[10], frame[11]).to_string()
+        });
+
+        let tag_block = first_tag_block(frame).unwrap_or_default();
+
+        AuditEvent {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            ingress,
+            interface,
+            source_mac,
+            source_ip: PacketParser::new(frame)
+                .source_ip()
+                .map(|ip| ip.to_string()),
+            source_id: tag_block.source,
+            destination_id: tag_block.destination,
+            line_count: tag_block.line_count,
+            sentence,
+            verdict: "allowed",
+            reason: None,
+            matched_rule: None,
+        }
+    }
+}
+
+// written in place of the events that did not fit in the per second budget
+#[derive(Serialize)]
+struct SuppressedEvents {
+    timestamp: String,
+    verdict: &'static str,
+    suppressed: u64,
+}
+
+// json lines log of the firewall decisions, sampled and rate limited so that a flood of
+// traffic cannot turn the logging itself into a denial of service
+pub struct AuditLog {
+    pub level: AuditLevel,
+    // at most this many events are written each second, 0 means unlimited
+    pub max_events_per_second: u64,
+    // only one out of this many allowed decisions is written
+    pub sample_allowed: u64,
+    pub suppressed_total: u64,
+    writer: BufWriter<File>,
+    window_start: Instant,
+    events_in_window: u64,
+    suppressed_in_window: u64,
+    allowed_seen: u64,
+}
+
+impl AuditLog {
+    pub fn new(
+        path: &str,
+        level: AuditLevel,
+        max_events_per_second: u64,
+        sample_allowed: u64,
+    ) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(AuditLog {
+            level,
+            max_events_per_second,
+            sample_allowed: sample_allowed.max(1),
+            suppressed_total: 0,
+            writer: BufWriter::new(file),
+            window_start: Instant::now(),
+            events_in_window: 0,
+            suppressed_in_window: 0,
+            allowed_seen: 0,
+        })
+    }
+
+    pub fn log_allowed(
+        &mut self,
+        ingress: &str,
+        interface: &str,
+        frame: &[u8],
+        sentence: &str,
+        matched_rule: Option<String>,
+    ) {
+        self.record_allowed(
+            ingress,
+            interface,
+            frame,
+            sentence,
+            matched_rule,
+            Instant::now(),
+        );
+    }
+
+    pub fn log_dropped(
+        &mut self,
+        ingress: &str,
+        interface: &str,
+        frame: &[u8],
+        sentence: &str,
+        reason: &DropReason,
+        matched_rule: Option<String>,
+    ) {
+        self.record_dropped(
+            ingress,
+            interface,
+            frame,
+            sentence,
+            reason,
+            matched_rule,
+            Instant::now(),
+        );
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.roll_window(Instant::now(), true);
+        self.writer.flush()
+    }
+
+    fn record_allowed(
+        &mut self,
+        ingress: &str,
+        interface: &str,
+        frame: &[u8],
+        sentence: &str,
+        matched_rule: Option<String>,
+        now: Instant,
+    ) {
+        if self.level < AuditLevel::All {
+            return;
+        }
+
+        self.allowed_seen += 1;
+        if !self.allowed_seen.is_multiple_of(self.sample_allowed) {
+            return;
+        }
+
+        if !self.within_budget(now) {
+            return;
+        }
+        let mut event = AuditEvent::new(ingress, interface, frame, sentence);
+        event.matched_rule = matched_rule;
+        self.write_line(&event);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn record_dropped(
+        &mut self,
+        ingress: &str,
+        interface: &str,
+        frame: &[u8],
+        sentence: &str,
+        reason: &DropReason,
+        matched_rule: Option<String>,
+        now: Instant,
+    ) {
+        if self.level < AuditLevel::Drops {
+            return;
+        }
+
+        if !self.within_budget(now) {
+            return;
+        }
+        let mut event = AuditEvent::new(ingress, interface, frame, sentence);
+        event.verdict = "dropped";
+        event.reason = Some(reason.to_string());
+        event.matched_rule = matched_rule;
+        self.write_line(&event);
+    }
+
+    // counts the event against the budget of the window, before anything is built for it
+    // so that a flood of suppressed events costs no formatting
+    fn within_budget(&mut self, now: Instant) -> bool {
+        self.roll_window(now, false);
+
+        if self.max_events_per_second > 0 && self.events_in_window >= self.max_events_per_second {
+            self.suppressed_in_window += 1;
+            self.suppressed_total += 1;
+            return false;
+        }
+
+        self.events_in_window += 1;
+        true
+    }
+
+    // starts a new one second window, reporting how many events the previous one dropped
+    fn roll_window(&mut self, now: Instant, force: bool) {
+        if !force && now.saturating_duration_since(self.window_start) < Duration::from_secs(1) {
+            return;
+        }
+
+        if self.suppressed_in_window > 0 {
+            let suppressed = SuppressedEvents {
+                timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+                verdict: "suppressed",
+                suppressed: self.suppressed_in_window,
+            };
+            self.write_line(&suppressed);
+        }
+
+        if let Err(e) = self.writer.flush() {
+            log::error!("| AUDIT LOG FLUSH FAILED: {}", e);
+        }
+
+        self.window_start = now;
+        self.events_in_window = 0;
+        self.suppressed_in_window = 0;
+    }
+
+    fn write_line<T: Serialize>(&mut self, line: &T) {
+        let result = serde_json::to_writer(&mut self.writer, line)
+            .map_err(io::Error::from)
+            .and_then(|_| self.writer.write_all(b"\n"));
+
+        if let Err(e) = result {
+            log::error!("| AUDIT LOG WRITE FAILED: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::udp_frame;
+    use serde_json::Value;
+
+    const GGA: &[u8] = b"$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n";
+
+    fn audit_log(name: &str, level: AuditLevel, max_events_per_second: u64) -> (AuditLog, String) {
+        let path = std::env::temp_dir()
+            .join(format!("audit-{}-{}.jsonl", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned();
+        let _ = std::fs::remove_file(&path);
+        let audit_log = AuditLog::new(&path, level, max_events_per_second, 1).unwrap();
+        (audit_log, path)
+    }
+
+    // the lines written so far, the file is removed
+    fn lines(mut audit_log: AuditLog, path: &str) -> Vec<Value> {
+        audit_log.writer.flush().unwrap();
+        let lines = std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        std::fs::remove_file(path).unwrap();
+        lines
+    }
+
+    fn log_drop(audit_log: &mut AuditLog, now: Instant) {
+        audit_log.record_dropped(
+            "gps",
+            "test3",
+            &udp_frame(GGA),
+            "$GPGGA",
+            &DropReason::SentenceNotAllowed(String::from("$GPGGA")),
+            Some(String::from("gps.sends")),
+            now,
+        );
+    }
+
+    #[test]
+    fn drops_name_the_rule_that_matched() {
+        let (mut audit_log, path) = audit_log("matched-rule", AuditLevel::Drops, 0);
+        let start = audit_log.window_start;
+        log_drop(&mut audit_log, start);
+
+        let lines = lines(audit_log, &path);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["verdict"], "dropped");
+        assert_eq!(lines[0]["reason"], "sentence $GPGGA not allowed");
+        assert_eq!(lines[0]["matched_rule"], "gps.sends");
+    }
+
+    #[test]
+    fn events_over_the_budget_are_summed_up_when_the_window_ends() {
+        let (mut audit_log, path) = audit_log("window", AuditLevel::Drops, 2);
+        let start = audit_log.window_start;
+        (0..5).for_each(|i| log_drop(&mut audit_log, start + Duration::from_millis(100 * i)));
+        assert_eq!(audit_log.suppressed_total, 3);
+
+        // the next window has a budget of its own
+        log_drop(&mut audit_log, start + Duration::from_millis(1000));
+        log_drop(&mut audit_log, start + Duration::from_millis(1100));
+
+        let verdicts: Vec<String> = lines(audit_log, &path)
+            .iter()
+            .map(|line| line["verdict"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            verdicts,
+            ["dropped", "dropped", "suppressed", "dropped", "dropped"]
+        );
+    }
+
+    #[test]
+    fn the_suppressed_line_counts_the_events_of_its_window() {
+        let (mut audit_log, path) = audit_log("suppressed", AuditLevel::Drops, 1);
+        let start = audit_log.window_start;
+        (0..4).for_each(|_| log_drop(&mut audit_log, start));
+        audit_log.roll_window(start + Duration::from_secs(1), false);
+        // nothing was suppressed in the second window, it adds no line
+        audit_log.roll_window(start + Duration::from_secs(2), false);
+
+        let lines = lines(audit_log, &path);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["verdict"], "suppressed");
+        assert_eq!(lines[1]["suppressed"], 3);
+    }
+
+    #[test]
+    fn one_allowed_decision_out_of_sample_allowed_is_written() {
+        let (mut audit_log, path) = audit_log("sampled", AuditLevel::All, 0);
+        audit_log.sample_allowed = 3;
+        let start = audit_log.window_start;
+        (0..7).for_each(|_| {
+            audit_log.record_allowed(
+                "gps",
+                "test3",
+                &udp_frame(GGA),
+                "$GPGGA",
+                Some(String::from("gps.sends $GPGGA")),
+                start,
+            )
+        });
+
+        let lines = lines(audit_log, &path);
+        assert_eq!(lines.len(), 2);
+        assert!(
+            lines
+                .iter()
+                .all(|line| line["verdict"] == "allowed"
+                    && line["matched_rule"] == "gps.sends $GPGGA")
+        );
+    }
+
+    #[test]
+    fn the_level_decides_what_is_written() {
+        let (mut audit_log, path) = audit_log("level", AuditLevel::Drops, 0);
+        let start = audit_log.window_start;
+        audit_log.record_allowed("gps", "test3", &udp_frame(GGA), "$GPGGA", None, start);
+        log_drop(&mut audit_log, start);
+
+        let lines = lines(audit_log, &path);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["verdict"], "dropped");
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{FragmentError, TcpError, Violation};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DropReason {
+    // the component is not allowed to send this sentence
+    SentenceNotAllowed(String),
+    // no umem chunk was available on the destination socket
+    TxAllocationFailed,
+    // the kernel refused to wake the destination socket for transmission
+    TxWakeupFailed,
+    // the component was disabled from the control socket
+    ComponentDisabled,
+    // the frame carries a source address the component is not bound to
+    SpoofedSourceMac(String),
+    SpoofedSourceIp(String),
+    SpoofedSourceId(String),
+    // an arp frame contradicts the addresses of the policy
+    ArpSpoofing(String),
+    // the frame is an ipv4 fragment that cannot be part of a sane datagram
+    Fragment(FragmentError),
+    // the tcp stream the segment belongs to cannot be inspected as it is
+    TcpStream(TcpError),
+    // a sentence breaks the syntax of IEC 61162-1
+    InvalidSentence(Violation),
+    // the ship denies this proprietary sentence or its manufacturer
+    ProprietaryDenied(String),
+}
+
+impl DropReason {
+    // stable name of the reason, used as a metric label
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DropReason::SentenceNotAllowed(_) => "sentence_not_allowed",
+            DropReason::TxAllocationFailed => "tx_allocation_failed",
+            DropReason::TxWakeupFailed => "tx_wakeup_failed",
+            DropReason::ComponentDisabled => "component_disabled",
+            DropReason::SpoofedSourceMac(_) => "spoofed_source_mac",
+            DropReason::SpoofedSourceIp(_) => "spoofed_source_ip",
+            DropReason::SpoofedSourceId(_) => "spoofed_source_id",
+            DropReason::ArpSpoofing(_) => "arp_spoofing",
+            DropReason::Fragment(error) => error.kind(),
+            DropReason::TcpStream(error) => error.kind(),
+            DropReason::InvalidSentence(violation) => violation.kind(),
+            DropReason::ProprietaryDenied(_) => "proprietary_denied",
+        }
+    }
+}
+
+impl fmt::Display for DropReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DropReason::SentenceNotAllowed(prefix) => write!(f, "sentence {} not allowed", prefix),
+            DropReason::TxAllocationFailed => write!(f, "tx memory allocation failed"),
+            DropReason::TxWakeupFailed => write!(f, "tx wakeup failed"),
+            DropReason::ComponentDisabled => write!(f, "component disabled"),
+            DropReason::SpoofedSourceMac(mac) => write!(f, "spoofed source mac {}", mac),
+            DropReason::SpoofedSourceIp(ip) => write!(f, "spoofed source ip {}", ip),
+            DropReason::SpoofedSourceId(id) if id.is_empty() => {
+                write!(f, "sentence without source id")
+            }
+            DropReason::SpoofedSourceId(id) => write!(f, "spoofed source id {}", id),
+            DropReason::ArpSpoofing(detail) => write!(f, "arp spoofing, {}", detail),
+            DropReason::Fragment(error) => write!(f, "{}", error),
+            DropReason::TcpStream(error) => write!(f, "{}", error),
+            DropReason::InvalidSentence(violation) => write!(f, "{}", violation),
+            DropReason::ProprietaryDenied(prefix) => {
+                write!(f, "proprietary sentence {} denied", prefix)
+            }
+        }
+    }
+}
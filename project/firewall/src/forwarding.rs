@@ -0,0 +1,272 @@
+use crate::{
+    lists_sentence, multicast_destination, retain_sentences, sentence_deliveries,
+    sentence_prefixes, MultiSentence, Receiver, SentenceSchemas, ShipSwitch, TrafficEntry,
+    TransmissionGroups,
+};
+use packet_parser::{AddressFamily, PacketParser};
+use pnet::packet::ethernet::MutableEthernetPacket;
+use pnet::packet::ipv4::MutableIpv4Packet;
+use pnet::packet::ipv6::MutableIpv6Packet;
+use pnet::packet::udp::MutableUdpPacket;
+use pnet::util::MacAddr;
+use std::borrow::Cow;
+use std::net::{IpAddr, Ipv4Addr};
+
+// where a frame of the ship traffic goes
+pub enum Forwarding<'e> {
+    // the frame handed to each of these ports, none when nobody gets it
+    Deliver(Vec<(usize, Cow<'e, [u8]>)>),
+    // a tcp segment with a sentence its destination does not receive, its connection is reset
+    Reset(String),
+}
+
+// the view of the ship the forwarding decisions need,
+// the ship and the replay of a capture deliver the same frames through it
+pub struct Forwarder<'a> {
+    // one per port
+    pub receivers: &'a [Receiver<'a>],
+    pub groups: &'a TransmissionGroups,
+    pub ship_switch: &'a ShipSwitch,
+    pub schemas: &'a SentenceSchemas,
+}
+
+impl Forwarder<'_> {
+    // multi_sentence is the mode of the component the frame came from
+    pub fn forward<'e>(
+        &self,
+        entry: &'e TrafficEntry,
+        multi_sentence: MultiSentence,
+    ) -> Forwarding<'e> {
+        if entry.is_nmea {
+            // the nmea sentence should be multicasted to all ship's components that can receive it
+            return Forwarding::Deliver(self.multicast(entry, multi_sentence));
+        }
+
+        // the first sentence of a tcp segment its destination does not receive
+        let receives = self.receivers[entry.destination].receives;
+        match entry
+            .sentences
+            .iter()
+            .find(|sentence| !lists_sentence(receives, sentence))
+        {
+            Some(denied) => Forwarding::Reset(denied.clone()),
+            None => Forwarding::Deliver(vec![(entry.destination, Cow::Borrowed(&entry.data))]),
+        }
+    }
+
+    fn multicast<'e>(
+        &self,
+        entry: &'e TrafficEntry,
+        multi_sentence: MultiSentence,
+    ) -> Vec<(usize, Cow<'e, [u8]>)> {
+        if let Some(address) = multicast_destination(&entry.data) {
+            return self.group(entry, &address, multi_sentence);
+        }
+        if entry.data[0] & 1 == 1 {
+            log::info!(
+                "| {} SENT TO A MULTICAST MAC WITHOUT A MULTICAST GROUP, NOT DELIVERED",
+                entry.prefix
+            );
+            return Vec::new();
+        }
+
+        // every component receiving one of the sentences of the datagram gets a copy
+        let prefixes = self.prefixes(entry);
+        let deliveries = match sentence_deliveries(self.receivers, &prefixes, self.ship_switch) {
+            Ok(deliveries) => deliveries,
+            Err(e) => {
+                log::error!("{}", e);
+                return Vec::new();
+            }
+        };
+
+        let family = PacketParser::new(&entry.data)
+            .layout()
+            .and_then(|layout| layout.family);
+
+        let mut frames = Vec::new();
+        for delivery in deliveries {
+            // the copy keeps the address family of the original frame
+            let destination_ip = match family {
+                Some(AddressFamily::Ipv6) => match delivery.ipv6 {
+                    Some(ipv6) => IpAddr::V6(ipv6),
+                    None => {
+                        log::warn!(
+                            "| {} HAS NO IPV6 ADDRESS, {} NOT DELIVERED",
+                            self.receivers[delivery.port].name,
+                            entry.prefix
+                        );
+                        continue;
+                    }
+                },
+                _ => IpAddr::V4(delivery.ip),
+            };
+
+            let receives = self.receivers[delivery.component_index].receives;
+            let Some(data) = received_data(
+                self.schemas,
+                &entry.data,
+                &prefixes,
+                receives,
+                multi_sentence,
+            ) else {
+                log::info!(
+                    "| {} DOES NOT RECEIVE EVERY SENTENCE OF {}, NOT DELIVERED",
+                    self.receivers[delivery.component_index].name,
+                    entry.prefix
+                );
+                continue;
+            };
+
+            match forge_packet(&data, delivery.mac, destination_ip) {
+                Some(packet) if !packet.is_empty() => {
+                    frames.push((delivery.port, Cow::Owned(packet)))
+                }
+                _ => {
+                    log::error!("ERROR FORGING PACKET FOR MULTICAST");
+                    break;
+                }
+            }
+        }
+        frames
+    }
+
+    // the frame goes as it is to every other component subscribed to its group or member of it
+    fn group<'e>(
+        &self,
+        entry: &'e TrafficEntry,
+        address: &Ipv4Addr,
+        multi_sentence: MultiSentence,
+    ) -> Vec<(usize, Cow<'e, [u8]>)> {
+        let prefixes = self.prefixes(entry);
+        let group = self
+            .groups
+            .by_address(address)
+            .map_or_else(|| address.to_string(), |group| group.name.clone());
+
+        let subscriptions: Vec<&[String]> = self
+            .receivers
+            .iter()
+            .map(|receiver| receiver.groups)
+            .collect();
+        let subscribers = self.groups.members(
+            address,
+            &subscriptions,
+            &self.ship_switch.igmp,
+            entry.ingress,
+            entry.received_at,
+        );
+
+        if subscribers.is_empty() {
+            log::info!("| NO COMPONENT SUBSCRIBED TO {}", group);
+        }
+
+        subscribers
+            .into_iter()
+            .filter_map(|port| {
+                let receives = self.receivers[port].receives;
+                match received_data(
+                    self.schemas,
+                    &entry.data,
+                    &prefixes,
+                    receives,
+                    multi_sentence,
+                ) {
+                    Some(data) => Some((port, Cow::Owned(data))),
+                    None => {
+                        log::info!(
+                            "| {} DOES NOT RECEIVE EVERY SENTENCE OF {} ON {}, NOT DELIVERED",
+                            self.receivers[port].name,
+                            entry.prefix,
+                            group
+                        );
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    // the sentences of the frame, or the prefix it was judged by when none is recognised
+    fn prefixes(&self, entry: &TrafficEntry) -> Vec<String> {
+        let mut prefixes = sentence_prefixes(self.schemas, &entry.data);
+        if prefixes.is_empty() {
+            prefixes.push(entry.prefix.clone());
+        }
+        prefixes
+    }
+}
+
+// a component receiving only some of the sentences gets those in filter mode, nothing otherwise
+pub fn received_data(
+    schemas: &SentenceSchemas,
+    data: &[u8],
+    prefixes: &[String],
+    receives: &[String],
+    multi_sentence: MultiSentence,
+) -> Option<Vec<u8>> {
+    match prefixes
+        .iter()
+        .all(|prefix| lists_sentence(receives, prefix))
+    {
+        true => Some(data.to_vec()),
+        false if multi_sentence == MultiSentence::Filter => {
+            retain_sentences(schemas, data, |prefix| lists_sentence(receives, prefix))
+        }
+        false => None,
+    }
+}
+
+// rewrites the destination of a copy of the frame, vlan tags and ip extension headers stay as they are
+pub fn forge_packet(
+    data: &[u8],
+    destination_mac: MacAddr,
+    destination_ip: IpAddr,
+) -> Option<Vec<u8>> {
+    let layout = PacketParser::new(data).layout()?;
+    let udp = layout.udp?;
+    let mut new_packet_buffer = data.to_vec();
+
+    let mut new_ethernet_packet = MutableEthernetPacket::new(&mut new_packet_buffer)?;
+    new_ethernet_packet.set_destination(destination_mac);
+
+    let (network, transport) = new_packet_buffer.split_at_mut(udp.start);
+    let network = &mut network[layout.network_offset..];
+    let mut new_udp_packet = MutableUdpPacket::new(&mut transport[..udp.len()])?;
+    new_udp_packet.set_checksum(0);
+
+    match (layout.family?, destination_ip) {
+        (AddressFamily::Ipv4, IpAddr::V4(destination_ip)) => {
+            let mut new_ipv4_packet = MutableIpv4Packet::new(network)?;
+            new_ipv4_packet.set_destination(destination_ip);
+
+            // Recalculate the IPv4 checksum
+            new_ipv4_packet.set_checksum(0); // Reset checksum before calculation
+            let checksum = pnet::packet::ipv4::checksum(&new_ipv4_packet.to_immutable());
+            new_ipv4_packet.set_checksum(checksum);
+
+            // Calculate the UDP checksum
+            let udp_checksum = pnet::packet::udp::ipv4_checksum(
+                &new_udp_packet.to_immutable(),
+                &new_ipv4_packet.get_source(),
+                &destination_ip,
+            );
+            new_udp_packet.set_checksum(udp_checksum);
+        }
+        (AddressFamily::Ipv6, IpAddr::V6(destination_ip)) => {
+            // ipv6 has no header checksum, the udp one is mandatory
+            let mut new_ipv6_packet = MutableIpv6Packet::new(network)?;
+            new_ipv6_packet.set_destination(destination_ip);
+
+            let udp_checksum = pnet::packet::udp::ipv6_checksum(
+                &new_udp_packet.to_immutable(),
+                &new_ipv6_packet.get_source(),
+                &destination_ip,
+            );
+            new_udp_packet.set_checksum(udp_checksum);
+        }
+        _ => return None,
+    }
+
+    Some(new_packet_buffer)
+}
@@ -0,0 +1,402 @@
+use packet_parser::{AddressFamily, PacketParser};
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::Packet;
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+// robustness variable times query interval plus the query response interval, as in RFC 2236
+const DEFAULT_MEMBERSHIP_INTERVAL: Duration = Duration::from_secs(260);
+
+const MEMBERSHIP_QUERY: u8 = 0x11;
+const V1_MEMBERSHIP_REPORT: u8 = 0x12;
+const V2_MEMBERSHIP_REPORT: u8 = 0x16;
+const LEAVE_GROUP: u8 = 0x17;
+const V3_MEMBERSHIP_REPORT: u8 = 0x22;
+
+// IGMPv3 group record types, RFC 3376
+const MODE_IS_INCLUDE: u8 = 1;
+const MODE_IS_EXCLUDE: u8 = 2;
+const CHANGE_TO_INCLUDE_MODE: u8 = 3;
+const CHANGE_TO_EXCLUDE_MODE: u8 = 4;
+const ALLOW_NEW_SOURCES: u8 = 5;
+
+// what an igmp frame means for the membership table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IgmpMessage {
+    // a multicast router asks who is listening, to one group or to all of them
+    Query(Option<Ipv4Addr>),
+    // the groups joined and left by the sender of a report
+    Report {
+        joined: Vec<Ipv4Addr>,
+        left: Vec<Ipv4Addr>,
+    },
+    // truncated or with a wrong checksum
+    Malformed,
+}
+
+// learns which ports joined which ipv4 multicast groups from their igmp messages
+pub struct IgmpSnooping {
+    memberships: hashbrown::HashMap<Ipv4Addr, hashbrown::HashMap<usize, Instant>>,
+    // ports multicast routers sent queries from, reports go to them only
+    routers: hashbrown::HashMap<usize, Instant>,
+    // multicast is only restricted to the members when on, it is flooded otherwise
+    pub enabled: bool,
+    // memberships not reported again for this long are forgotten
+    pub membership_interval: Duration,
+    pub joins: u64,
+    pub leaves: u64,
+    pub queries: u64,
+    pub malformed: u64,
+}
+
+impl Default for IgmpSnooping {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IgmpSnooping {
+    pub fn new() -> Self {
+        IgmpSnooping {
+            memberships: hashbrown::HashMap::new(),
+            routers: hashbrown::HashMap::new(),
+            enabled: true,
+            membership_interval: DEFAULT_MEMBERSHIP_INTERVAL,
+            joins: 0,
+            leaves: 0,
+            queries: 0,
+            malformed: 0,
+        }
+    }
+
+    // none when the frame is not igmp, the table is updated otherwise
+    pub fn snoop(&mut self, frame: &[u8], port: usize, now: Instant) -> Option<IgmpMessage> {
+        let message = igmp_message(frame)?;
+        if !self.enabled {
+            return Some(message);
+        }
+
+        match &message {
+            IgmpMessage::Query(_) => {
+                self.queries += 1;
+                self.routers.insert(port, now);
+            }
+            IgmpMessage::Report { joined, left } => {
+                joined
+                    .iter()
+                    .filter(|group| is_snooped(group))
+                    .for_each(|group| {
+                        let members = self.memberships.entry(*group).or_default();
+                        if members.insert(port, now).is_none() {
+                            self.joins += 1;
+                            log::info!("| PORT {} JOINED {}", port, group);
+                        }
+                    });
+                // every port is a single component, it can be removed without asking the others
+                left.iter().for_each(|group| {
+                    let Some(members) = self.memberships.get_mut(group) else {
+                        return;
+                    };
+                    if members.remove(&port).is_some() {
+                        self.leaves += 1;
+                        log::info!("| PORT {} LEFT {}", port, group);
+                    }
+                    if members.is_empty() {
+                        self.memberships.remove(group);
+                    }
+                });
+            }
+            IgmpMessage::Malformed => self.malformed += 1,
+        }
+        self.expire(now);
+        Some(message)
+    }
+
+    // the ports an igmp message goes to, reports only reach the multicast routers
+    pub fn forward_ports(
+        &self,
+        message: &IgmpMessage,
+        ingress_port: usize,
+        ports: usize,
+    ) -> Vec<usize> {
+        match message {
+            IgmpMessage::Report { .. } if self.enabled => {
+                let mut routers: Vec<usize> = self
+                    .routers
+                    .keys()
+                    .copied()
+                    .filter(|port| *port != ingress_port)
+                    .collect();
+                routers.sort();
+                routers
+            }
+            IgmpMessage::Malformed if self.enabled => Vec::new(),
+            _ => (0..ports).filter(|port| *port != ingress_port).collect(),
+        }
+    }
+
+    // the ports that joined a group, none when nobody did or it is not snooped
+    pub fn members(&self, group: &Ipv4Addr, now: Instant) -> Option<Vec<usize>> {
+        if !self.enabled || !is_snooped(group) {
+            return None;
+        }
+        let mut members: Vec<usize> = self
+            .memberships
+            .get(group)?
+            .iter()
+            .filter(|(_, last_report)| now.duration_since(**last_report) < self.membership_interval)
+            .map(|(port, _)| *port)
+            .collect();
+        members.sort();
+        (!members.is_empty()).then_some(members)
+    }
+
+    // the groups and their member ports with the time of their last report
+    pub fn iter(&self) -> impl Iterator<Item = (&Ipv4Addr, Vec<(usize, Instant)>)> {
+        self.memberships.iter().map(|(group, members)| {
+            let mut members: Vec<(usize, Instant)> =
+                members.iter().map(|(port, seen)| (*port, *seen)).collect();
+            members.sort();
+            (group, members)
+        })
+    }
+
+    pub fn routers(&self) -> Vec<usize> {
+        let mut routers: Vec<usize> = self.routers.keys().copied().collect();
+        routers.sort();
+        routers
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let interval = self.membership_interval;
+        self.memberships.retain(|_, members| {
+            members.retain(|_, last_report| now.duration_since(*last_report) < interval);
+            !members.is_empty()
+        });
+        self.routers
+            .retain(|_, last_query| now.duration_since(*last_query) < interval);
+    }
+}
+
+// link local groups such as 224.0.0.1 are always flooded, hosts never report them
+fn is_snooped(group: &Ipv4Addr) -> bool {
+    group.is_multicast() && group.octets()[..3] != [224, 0, 0]
+}
+
+// the igmp message an ipv4 frame carries, tagged or not
+fn igmp_message(frame: &[u8]) -> Option<IgmpMessage> {
+    let layout = PacketParser::new(frame).layout()?;
+    if layout.family? != AddressFamily::Ipv4 {
+        return None;
+    }
+    let ipv4_packet = Ipv4Packet::new(&frame[layout.network_offset..])?;
+    if ipv4_packet.get_next_level_protocol() != IpNextHeaderProtocols::Igmp {
+        return None;
+    }
+
+    let igmp = ipv4_packet.payload();
+    if igmp.len() < 8 || pnet::util::checksum(igmp, 1) != u16::from_be_bytes([igmp[2], igmp[3]]) {
+        return Some(IgmpMessage::Malformed);
+    }
+    let group = Ipv4Addr::new(igmp[4], igmp[5], igmp[6], igmp[7]);
+
+    let message = match igmp[0] {
+        MEMBERSHIP_QUERY => IgmpMessage::Query((!group.is_unspecified()).then_some(group)),
+        V1_MEMBERSHIP_REPORT | V2_MEMBERSHIP_REPORT => IgmpMessage::Report {
+            joined: vec![group],
+            left: Vec::new(),
+        },
+        LEAVE_GROUP => IgmpMessage::Report {
+            joined: Vec::new(),
+            left: vec![group],
+        },
+        V3_MEMBERSHIP_REPORT => v3_report(igmp).unwrap_or(IgmpMessage::Malformed),
+        _ => return None,
+    };
+    Some(message)
+}
+
+// the group records of an IGMPv3 report, an include mode without sources is a leave
+fn v3_report(igmp: &[u8]) -> Option<IgmpMessage> {
+    let records = u16::from_be_bytes([igmp[6], igmp[7]]);
+    let mut joined = Vec::new();
+    let mut left = Vec::new();
+
+    let mut offset = 8;
+    for _ in 0..records {
+        let record = igmp.get(offset..offset + 8)?;
+        let aux_length = record[1] as usize * 4;
+        let sources = u16::from_be_bytes([record[2], record[3]]) as usize;
+        let group = Ipv4Addr::new(record[4], record[5], record[6], record[7]);
+
+        match record[0] {
+            MODE_IS_INCLUDE | CHANGE_TO_INCLUDE_MODE if sources == 0 => left.push(group),
+            MODE_IS_INCLUDE | CHANGE_TO_INCLUDE_MODE | ALLOW_NEW_SOURCES => joined.push(group),
+            MODE_IS_EXCLUDE | CHANGE_TO_EXCLUDE_MODE => joined.push(group),
+            // blocking some sources keeps the membership
+            _ => {}
+        }
+        offset += 8 + sources * 4 + aux_length;
+    }
+    if offset > igmp.len() {
+        return None;
+    }
+
+    Some(IgmpMessage::Report { joined, left })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::ethernet::{EtherTypes, MutableEthernetPacket};
+    use pnet::packet::ipv4::MutableIpv4Packet;
+
+    const GROUP: Ipv4Addr = Ipv4Addr::new(239, 192, 0, 1);
+    const OTHER_GROUP: Ipv4Addr = Ipv4Addr::new(239, 192, 0, 2);
+
+    // an igmp message in an ipv4 frame, with its checksum set
+    fn igmp_frame(mut igmp: Vec<u8>) -> Vec<u8> {
+        let checksum = pnet::util::checksum(&igmp, 1);
+        igmp[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+        let mut frame = vec![0u8; 14 + 20 + igmp.len()];
+        MutableEthernetPacket::new(&mut frame)
+            .unwrap()
+            .set_ethertype(EtherTypes::Ipv4);
+        let mut ipv4_packet = MutableIpv4Packet::new(&mut frame[14..]).unwrap();
+        ipv4_packet.set_version(4);
+        ipv4_packet.set_header_length(5);
+        ipv4_packet.set_total_length((20 + igmp.len()) as u16);
+        ipv4_packet.set_ttl(1);
+        ipv4_packet.set_next_level_protocol(IpNextHeaderProtocols::Igmp);
+        ipv4_packet.set_source(Ipv4Addr::new(10, 42, 0, 30));
+        ipv4_packet.set_destination(Ipv4Addr::new(224, 0, 0, 22));
+        ipv4_packet.set_payload(&igmp);
+        frame
+    }
+
+    fn v2_message(kind: u8, group: Ipv4Addr) -> Vec<u8> {
+        let mut igmp = vec![kind, 0, 0, 0];
+        igmp.extend(group.octets());
+        igmp
+    }
+
+    // an IGMPv3 report announcing records groups, of which only the given ones are written
+    fn v3_report(records: u16, written: &[(u8, Ipv4Addr)]) -> Vec<u8> {
+        let mut igmp = vec![V3_MEMBERSHIP_REPORT, 0, 0, 0, 0, 0];
+        igmp.extend(records.to_be_bytes());
+        for (record_type, group) in written {
+            igmp.extend([*record_type, 0, 0, 0]);
+            igmp.extend(group.octets());
+        }
+        igmp
+    }
+
+    #[test]
+    fn v2_reports_join_and_leaves_leave() {
+        let mut snooping = IgmpSnooping::new();
+        let now = Instant::now();
+
+        let report = igmp_frame(v2_message(V2_MEMBERSHIP_REPORT, GROUP));
+        snooping.snoop(&report, 3, now);
+        assert_eq!(snooping.members(&GROUP, now), Some(vec![3]));
+
+        let leave = igmp_frame(v2_message(LEAVE_GROUP, GROUP));
+        snooping.snoop(&leave, 3, now);
+        assert_eq!(snooping.members(&GROUP, now), None);
+        assert_eq!((snooping.joins, snooping.leaves), (1, 1));
+    }
+
+    #[test]
+    fn v3_reports_join_and_leave_every_record() {
+        let mut snooping = IgmpSnooping::new();
+        let now = Instant::now();
+
+        let report = igmp_frame(v3_report(
+            2,
+            &[
+                (CHANGE_TO_EXCLUDE_MODE, GROUP),
+                (MODE_IS_EXCLUDE, OTHER_GROUP),
+            ],
+        ));
+        assert_eq!(
+            snooping.snoop(&report, 1, now),
+            Some(IgmpMessage::Report {
+                joined: vec![GROUP, OTHER_GROUP],
+                left: Vec::new(),
+            })
+        );
+
+        let leave = igmp_frame(v3_report(1, &[(CHANGE_TO_INCLUDE_MODE, GROUP)]));
+        snooping.snoop(&leave, 1, now);
+        assert_eq!(snooping.members(&GROUP, now), None);
+        assert_eq!(snooping.members(&OTHER_GROUP, now), Some(vec![1]));
+    }
+
+    #[test]
+    fn truncated_v3_reports_are_malformed() {
+        let mut snooping = IgmpSnooping::new();
+        let now = Instant::now();
+
+        // two records announced, one written
+        let report = igmp_frame(v3_report(2, &[(CHANGE_TO_EXCLUDE_MODE, GROUP)]));
+        assert_eq!(
+            snooping.snoop(&report, 1, now),
+            Some(IgmpMessage::Malformed)
+        );
+
+        // a record announcing a source that is not there
+        let mut igmp = v3_report(1, &[(ALLOW_NEW_SOURCES, GROUP)]);
+        igmp[11] = 1;
+        assert_eq!(
+            snooping.snoop(&igmp_frame(igmp), 1, now),
+            Some(IgmpMessage::Malformed)
+        );
+
+        assert_eq!(snooping.members(&GROUP, now), None);
+        assert_eq!(snooping.malformed, 2);
+        assert!(snooping
+            .forward_ports(&IgmpMessage::Malformed, 1, 4)
+            .is_empty());
+    }
+
+    #[test]
+    fn bad_checksums_are_malformed() {
+        let mut frame = igmp_frame(v2_message(V2_MEMBERSHIP_REPORT, GROUP));
+        let last = frame.len() - 1;
+        frame[last] ^= 0xff;
+        assert_eq!(igmp_message(&frame), Some(IgmpMessage::Malformed));
+    }
+
+    #[test]
+    fn memberships_age_with_the_time_they_are_given() {
+        let mut snooping = IgmpSnooping::new();
+        let reported = Instant::now();
+
+        let report = igmp_frame(v2_message(V2_MEMBERSHIP_REPORT, GROUP));
+        snooping.snoop(&report, 2, reported);
+        let later = reported + snooping.membership_interval;
+        assert_eq!(
+            snooping.members(&GROUP, later - Duration::from_secs(1)),
+            Some(vec![2])
+        );
+        assert_eq!(snooping.members(&GROUP, later), None);
+    }
+
+    #[test]
+    fn reports_only_reach_the_routers() {
+        let mut snooping = IgmpSnooping::new();
+        let now = Instant::now();
+
+        let query = igmp_frame(v2_message(MEMBERSHIP_QUERY, Ipv4Addr::UNSPECIFIED));
+        assert_eq!(
+            snooping.snoop(&query, 0, now),
+            Some(IgmpMessage::Query(None))
+        );
+
+        let report = igmp_frame(v2_message(V2_MEMBERSHIP_REPORT, GROUP));
+        let message = snooping.snoop(&report, 2, now).unwrap();
+        assert_eq!(snooping.forward_ports(&message, 2, 4), [0]);
+    }
+}
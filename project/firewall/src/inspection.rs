@@ -0,0 +1,299 @@
+use crate::{
+    filter_sentences, inspect_frame, switch_frame, tcp_resets, ArpVerdict, DropReason,
+    MultiSentence, ProprietaryRules, Reassembler, Reassembly, SentenceSchemas, SentenceValidator,
+    ShipSwitch, SourceBinding, TcpInspector, TcpVerdict,
+};
+use std::borrow::Cow;
+use std::time::Instant;
+
+// what the inspection made of a frame sent by a component
+pub struct Inspection<'f> {
+    // the frame as the policy saw it: reassembled, filtered or as it arrived
+    pub frame: Cow<'f, [u8]>,
+    pub is_nmea: bool,
+    pub prefix: String,
+    // sentences completed by a tcp segment
+    pub sentences: Vec<String>,
+    // sentences cut out of the datagram in filter mode
+    pub filtered: Vec<String>,
+    // answered by the ship on behalf of the owner of the address
+    pub arp_reply: Option<Vec<u8>>,
+    pub drop_reason: Option<DropReason>,
+    // the tcp connection of the frame is torn down along with it
+    pub reset: bool,
+}
+
+// a frame let in for inspection, or the reason it goes no further
+type Admission<'f> = (Cow<'f, [u8]>, Result<(), DropReason>);
+
+// a frame waiting to be sent out of one of the ship's components
+#[derive(Clone)]
+pub struct TrafficEntry {
+    pub destination: usize,
+    pub data: Vec<u8>,
+    pub is_nmea: bool,
+    pub prefix: String,
+    // component the frame came from and when it was taken off its rx ring
+    pub ingress: usize,
+    pub received_at: Instant,
+    // sentences completed by a tcp segment, each checked against the receives of the destination
+    pub sentences: Vec<String>,
+}
+
+// the policy and the state the frames of one component are judged with,
+// the ship and the replay of a capture take the same decisions through it
+pub struct Inspector<'a> {
+    // a disabled component neither sends nor receives anything
+    pub enabled: bool,
+    pub sends: &'a [String],
+    pub schemas: &'a SentenceSchemas,
+    pub multi_sentence: MultiSentence,
+    pub binding: Option<&'a SourceBinding>,
+    pub reassembler: Option<&'a mut Reassembler>,
+    pub tcp: Option<&'a mut TcpInspector>,
+    pub validator: Option<&'a mut SentenceValidator>,
+    pub proprietary: Option<&'a mut ProprietaryRules>,
+}
+
+impl Inspector<'_> {
+    // the verdict on a frame of the component, none while it is a fragment of an incomplete datagram
+    pub fn inspect<'f>(
+        &mut self,
+        rx_slice: &'f [u8],
+        port: usize,
+        ship_switch: &mut ShipSwitch,
+        received_at: Instant,
+    ) -> Option<Inspection<'f>> {
+        let (frame, admitted) = self.admit(rx_slice, received_at)?;
+
+        //Parse the incoming message
+        let (message_ok, is_nmea, prefix) = inspect_frame(self.sends, self.schemas, &frame);
+        let mut inspection = Inspection {
+            frame,
+            is_nmea,
+            prefix,
+            sentences: Vec::new(),
+            filtered: Vec::new(),
+            arp_reply: None,
+            drop_reason: None,
+            reset: false,
+        };
+
+        let verdict = admitted
+            .and_then(|()| self.judge(&mut inspection, message_ok, port, ship_switch, received_at));
+        if let Err(reason) = verdict {
+            inspection.drop_reason = Some(reason);
+        }
+
+        inspection.prefix = match &inspection.drop_reason {
+            Some(DropReason::SentenceNotAllowed(denied))
+            | Some(DropReason::ProprietaryDenied(denied)) => denied.clone(),
+            _ => inspection
+                .sentences
+                .first()
+                .cloned()
+                .unwrap_or(inspection.prefix),
+        };
+        Some(inspection)
+    }
+
+    // the frame as a whole datagram of an enabled component with the addresses it was given,
+    // none while its datagram is incomplete
+    fn admit<'f>(&mut self, rx_slice: &'f [u8], received_at: Instant) -> Option<Admission<'f>> {
+        if !self.enabled {
+            return Some((Cow::Borrowed(rx_slice), Err(DropReason::ComponentDisabled)));
+        }
+
+        // every fragment must come from the component, the datagram is only inspected once whole
+        if let Some(reason) = self.binding.and_then(|binding| binding.check(rx_slice)) {
+            return Some((Cow::Borrowed(rx_slice), Err(reason)));
+        }
+
+        let reassembly = match self.reassembler.as_mut() {
+            Some(reassembler) => reassembler.push(rx_slice, received_at),
+            None => Reassembly::unreassembled(rx_slice),
+        };
+        let frame = match reassembly {
+            Reassembly::Whole => Cow::Borrowed(rx_slice),
+            Reassembly::Complete(datagram) => Cow::Owned(datagram),
+            Reassembly::Pending => return None,
+            Reassembly::Rejected(reason) => return Some((Cow::Borrowed(rx_slice), Err(reason))),
+        };
+
+        // the IEC 61162-450 source identifiers are only known once the datagram is whole
+        match self
+            .binding
+            .and_then(|binding| binding.check_source_id(&frame))
+        {
+            Some(reason) => Some((frame, Err(reason))),
+            None => Some((frame, Ok(()))),
+        }
+    }
+
+    // the checks of an admitted frame, in the order their drop reasons take precedence
+    fn judge(
+        &mut self,
+        inspection: &mut Inspection,
+        message_ok: bool,
+        port: usize,
+        ship_switch: &mut ShipSwitch,
+        received_at: Instant,
+    ) -> Result<(), DropReason> {
+        let message_ok = message_ok || self.filter(inspection);
+
+        match ship_switch.arp.inspect(&inspection.frame, port) {
+            Some(ArpVerdict::Drop(reason)) => return Err(reason),
+            Some(ArpVerdict::Reply(reply)) => inspection.arp_reply = Some(reply),
+            _ => {}
+        }
+
+        if let Some(reason) = self
+            .validator
+            .as_mut()
+            .and_then(|validator| validator.inspect(&inspection.frame))
+        {
+            return Err(reason);
+        }
+        if let Some(reason) = self
+            .proprietary
+            .as_mut()
+            .and_then(|proprietary| proprietary.inspect(&inspection.frame))
+        {
+            return Err(reason);
+        }
+
+        if !message_ok {
+            return Err(DropReason::SentenceNotAllowed(inspection.prefix.clone()));
+        }
+
+        self.inspect_tcp(inspection, received_at)
+    }
+
+    // in filter mode the forbidden sentences are cut out and the datagram goes on with the others,
+    // true when something is left of it
+    fn filter(&mut self, inspection: &mut Inspection) -> bool {
+        if self.multi_sentence != MultiSentence::Filter {
+            return false;
+        }
+        let Some((datagram, denied)) =
+            filter_sentences(self.sends, self.schemas, &inspection.frame)
+        else {
+            return false;
+        };
+
+        let (_, _, prefix) = inspect_frame(self.sends, self.schemas, &datagram);
+        inspection.prefix = prefix;
+        inspection.frame = Cow::Owned(datagram);
+        inspection.filtered = denied;
+        true
+    }
+
+    // the sentences of a tcp stream are checked as the segments completing them go by
+    fn inspect_tcp(
+        &mut self,
+        inspection: &mut Inspection,
+        received_at: Instant,
+    ) -> Result<(), DropReason> {
+        let verdict = match self.tcp.as_mut() {
+            Some(tcp) => tcp.inspect(&inspection.frame, self.sends, self.schemas, received_at),
+            None => TcpVerdict::Uninspected,
+        };
+        let sentences = match verdict {
+            TcpVerdict::Forward(sentences) => sentences,
+            TcpVerdict::Drop(reason) => return Err(reason),
+            TcpVerdict::Reset(reason) => {
+                inspection.reset = true;
+                return Err(reason);
+            }
+            TcpVerdict::Uninspected => return Ok(()),
+        };
+
+        // a denied proprietary sentence resets the connection as any other forbidden one
+        if let Some(proprietary) = self.proprietary.as_mut() {
+            if let Some(denied) = sentences
+                .iter()
+                .find(|sentence| proprietary.denies(sentence))
+            {
+                proprietary.denied += 1;
+                inspection.reset = true;
+                return Err(DropReason::ProprietaryDenied(denied.clone()));
+            }
+        }
+
+        inspection.sentences = sentences;
+        Ok(())
+    }
+
+    // the ship traffic an inspected frame turns into: the frame, the arp reply or the tcp resets
+    pub fn traffic(
+        &mut self,
+        inspection: &Inspection,
+        port: usize,
+        ports: usize,
+        ship_switch: &mut ShipSwitch,
+        received_at: Instant,
+    ) -> Vec<TrafficEntry> {
+        let entry = |data: Vec<u8>, is_nmea: bool, sentences: Vec<String>| TrafficEntry {
+            destination: port,
+            data,
+            is_nmea,
+            prefix: inspection.prefix.clone(),
+            ingress: port,
+            received_at,
+            sentences,
+        };
+
+        if let Some(reply) = inspection.arp_reply.clone() {
+            // answered on behalf of the owner of the address, the reply goes back where the request came from
+            vec![entry(reply, false, inspection.sentences.clone())]
+        } else if inspection.drop_reason.is_none() {
+            let entry = entry(
+                inspection.frame.to_vec(),
+                inspection.is_nmea,
+                inspection.sentences.clone(),
+            );
+            switched(entry, ship_switch, ports)
+        } else if let Some((to_sender, to_receiver)) = inspection
+            .reset
+            .then(|| self.reset_connection(&inspection.frame))
+            .flatten()
+        {
+            // both ends learn the connection is gone, the receiver wherever the switch knows it
+            let mut traffic = vec![entry(to_sender, false, Vec::new())];
+            traffic.extend(switched(
+                entry(to_receiver, false, Vec::new()),
+                ship_switch,
+                ports,
+            ));
+            traffic
+        } else {
+            Vec::new()
+        }
+    }
+
+    // forgets the connection of a tcp segment going no further, with the resets for both of its ends
+    pub fn reset_connection(&mut self, segment: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+        if let Some(tcp) = self.tcp.as_mut() {
+            tcp.forget(segment);
+        }
+        tcp_resets(segment)
+    }
+}
+
+// a copy of the entry for every port the ship switch sends its frame to
+fn switched(entry: TrafficEntry, ship_switch: &mut ShipSwitch, ports: usize) -> Vec<TrafficEntry> {
+    switch_frame(
+        ship_switch,
+        &entry.data,
+        entry.ingress,
+        ports,
+        entry.is_nmea,
+        entry.received_at,
+    )
+    .into_iter()
+    .map(|destination| TrafficEntry {
+        destination,
+        ..entry.clone()
+    })
+    .collect()
+}
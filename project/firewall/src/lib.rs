@@ -0,0 +1,262 @@
+use nmea::Nmea;
+use packet_parser::PacketParser;
+use pnet::util::MacAddr;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::time::Instant;
+mod arp_guard;
+mod audit_log;
+mod drop_reason;
+mod forwarding;
+mod igmp;
+mod inspection;
+mod proprietary;
+mod reassembly;
+mod sentences;
+mod ship_switch;
+mod source_binding;
+mod tcp_stream;
+#[cfg(test)]
+mod test_support;
+mod transmission_group;
+mod validation;
+pub use arp_guard::{ArpGuard, ArpVerdict};
+pub use audit_log::{AuditLevel, AuditLog};
+pub use drop_reason::DropReason;
+pub use forwarding::{forge_packet, received_data, Forwarder, Forwarding};
+pub use igmp::{IgmpMessage, IgmpSnooping};
+pub use inspection::{Inspection, Inspector, TrafficEntry};
+pub use nmea::{parse_schemas, FieldFormat, FieldSchema, SentenceSchema, SentenceSchemas};
+pub use proprietary::{lists_sentence, proprietary_manufacturer, ProprietaryRules};
+pub use reassembly::{fragment_ipv4, FragmentError, Reassembler, Reassembly};
+pub use sentences::{
+    filter_sentences, first_tag_block, retain_sentences, sentence_prefixes, split_sentences,
+    MultiSentence,
+};
+pub use ship_switch::{format_mac, EntryKind, LearnEvent, ShipSwitch, SwitchEntry};
+pub use source_binding::SourceBinding;
+pub use tcp_stream::{tcp_resets, TcpError, TcpInspector, TcpVerdict};
+pub use transmission_group::{TransmissionGroup, TransmissionGroups};
+pub use validation::{SentenceValidator, Violation, ViolationAction};
+
+// a copy of an nmea sentence addressed to one of the components allowed to receive it
+pub struct Delivery {
+    pub component_index: usize,
+    pub port: usize,
+    pub mac: MacAddr,
+    pub ip: Ipv4Addr,
+    pub ipv6: Option<Ipv6Addr>,
+}
+
+// the view of a component the forwarding decisions need
+pub struct Receiver<'a> {
+    pub name: &'a str,
+    pub mac: &'a str,
+    pub ip: &'a str,
+    pub ipv6: Option<&'a str>,
+    pub receives: &'a [String],
+    // IEC 61162-450 transmission groups the component is subscribed to
+    pub groups: &'a [String],
+}
+
+// parses the frame and checks it against the sends list of the component it came from
+pub fn inspect_frame(
+    sends: &[String],
+    schemas: &SentenceSchemas,
+    frame: &[u8],
+) -> (bool, bool, String) {
+    let packet_parser = PacketParser::new(frame);
+    match packet_parser.parse_traffic() {
+        Ok(message) => {
+            // a datagram may pack several sentences, hiding one behind another must not work
+            let verdicts: Vec<(bool, bool, String)> = split_sentences(&message)
+                .into_iter()
+                .map(|sentence| apply_policy(sends, schemas, sentence.to_string()))
+                .collect();
+            let is_nmea = verdicts.iter().any(|(_, is_nmea, _)| *is_nmea);
+
+            // the first forbidden sentence names the datagram, otherwise the first nmea one does
+            verdicts
+                .iter()
+                .find(|(is_allowed, _, _)| !is_allowed)
+                .or_else(|| verdicts.iter().find(|(_, is_nmea, _)| *is_nmea))
+                .map(|(is_allowed, _, prefix)| (*is_allowed, is_nmea, prefix.clone()))
+                .unwrap_or((true, false, String::from("NONMEA")))
+        }
+        Err(_) => (true, false, String::from("NONMEA")),
+    }
+}
+
+pub fn apply_policy(
+    sends: &[String],
+    schemas: &SentenceSchemas,
+    message: String,
+) -> (bool, bool, String) {
+    // here we know that the message is udp and it should be parsed to check for nmea compliance
+    // an IEC 61162-450 tag block in front of the sentence must not hide it from the parser
+    let (_, sentence) = nmea::split_tag_block(&message);
+    let mut nmea = Nmea::new();
+    let message_ok = nmea.parse_with(sentence.to_string(), schemas);
+
+    match message_ok {
+        Ok(()) => {
+            // message is valid nmea
+            // now gotta check if the message can be sent by the component
+            // AIS comes as !AIVDM, the prefix keeps the start delimiter of the sentence
+            let delimiter = sentence.chars().next().unwrap_or('$');
+            let prefix = format!(
+                "{}{}{}",
+                delimiter,
+                nmea.str_talker_id(),
+                nmea.str_sentence_type()
+            );
+            let is_allowed = lists_sentence(sends, &prefix);
+            (is_allowed, true, prefix)
+        }
+
+        Err(_) => (true, false, String::from("NONMEA")),
+    }
+}
+
+// learns the source address of the frame and returns the ports it should be sent out of,
+// now is when the frame was received, the capture time of a replayed one
+pub fn switch_frame(
+    ship_switch: &mut ShipSwitch,
+    frame: &[u8],
+    ingress_port: usize,
+    ports: usize,
+    is_nmea: bool,
+    now: Instant,
+) -> Vec<usize> {
+    let eth_dst_addr: [u8; 6] = frame[0..6].try_into().unwrap();
+    let eth_src_addr: [u8; 6] = frame[6..12].try_into().unwrap();
+
+    // Add mac src address to the ship switch
+    ship_switch.learn(eth_src_addr, ingress_port, now);
+
+    if let Some(message) = ship_switch.igmp.snoop(frame, ingress_port, now) {
+        ship_switch
+            .igmp
+            .forward_ports(&message, ingress_port, ports)
+    } else if let Some(destination_port) = ship_switch.get_at(&eth_dst_addr, now) {
+        vec![destination_port]
+    } else if is_nmea && eth_dst_addr[0] & 1 == 1 {
+        // sentences sent to a group are handed over once, the receivers are chosen from the policy
+        vec![ingress_port]
+    } else if let Some(members) =
+        multicast_destination(frame).and_then(|group| ship_switch.igmp.members(&group, now))
+    {
+        // multicast other than nmea reaches the ports that joined its group only
+        members
+            .into_iter()
+            .filter(|port| *port != ingress_port)
+            .collect()
+    } else if !is_nmea {
+        // nmea sentences should not be flooded since we want that only the correct recipients get what they expect
+        (0..ports).filter(|port| *port != ingress_port).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+// the ipv4 multicast group a frame is sent to
+pub fn multicast_destination(frame: &[u8]) -> Option<Ipv4Addr> {
+    let layout = PacketParser::new(frame).layout()?;
+    if layout.family? != packet_parser::AddressFamily::Ipv4 {
+        return None;
+    }
+    let ipv4_packet = pnet::packet::ipv4::Ipv4Packet::new(&frame[layout.network_offset..])?;
+    Some(ipv4_packet.get_destination()).filter(Ipv4Addr::is_multicast)
+}
+
+// the nmea sentence should be multicasted to all ship's components that can receive it
+pub fn multicast_deliveries(
+    receivers: &[Receiver],
+    prefix: &String,
+    ship_switch: &ShipSwitch,
+) -> Result<Vec<Delivery>, String> {
+    sentence_deliveries(receivers, std::slice::from_ref(prefix), ship_switch)
+}
+
+// the components receiving at least one of the sentences of a datagram
+pub fn sentence_deliveries(
+    receivers: &[Receiver],
+    prefixes: &[String],
+    ship_switch: &ShipSwitch,
+) -> Result<Vec<Delivery>, String> {
+    let mut deliveries = Vec::new();
+    for (component_index, receiver) in receivers.iter().enumerate() {
+        if !prefixes
+            .iter()
+            .any(|prefix| lists_sentence(receiver.receives, prefix))
+        {
+            continue;
+        }
+
+        let ipv6 = receiver.ipv6.map(Ipv6Addr::from_str).transpose();
+        let (mac, ip, ipv6) = match (
+            MacAddr::from_str(receiver.mac),
+            Ipv4Addr::from_str(receiver.ip),
+            ipv6,
+        ) {
+            (Ok(mac), Ok(ip), Ok(ipv6)) => (mac, ip, ipv6),
+            _ => {
+                return Err(String::from(
+                    "ERROR PARSING MAC OR IP ADDRESS FOR MULTICAST",
+                ))
+            }
+        };
+
+        // Skip if MAC address is not found in the map
+        if let Some(port) = ship_switch.get(&mac.octets()) {
+            deliveries.push(Delivery {
+                component_index,
+                port,
+                mac,
+                ip,
+                ipv6,
+            });
+        }
+    }
+
+    Ok(deliveries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::udp_frame;
+
+    fn sends(prefixes: &[&str]) -> Vec<String> {
+        prefixes.iter().map(|prefix| prefix.to_string()).collect()
+    }
+
+    #[test]
+    fn invalid_utf8_does_not_hide_a_forbidden_sentence() {
+        let mut payload = b"$HEHDT,274.5,T*2B\r\n".to_vec();
+        payload.extend_from_slice(&[0xff, 0xfe]);
+        payload.extend_from_slice(
+            b"$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n",
+        );
+        let frame = udp_frame(&payload);
+        let schemas = SentenceSchemas::builtin();
+
+        assert_eq!(
+            inspect_frame(&sends(&["$HEHDT"]), schemas, &frame),
+            (false, true, String::from("$GPGGA"))
+        );
+        assert_eq!(
+            inspect_frame(&sends(&["$HEHDT", "$GPGGA"]), schemas, &frame),
+            (true, true, String::from("$HEHDT"))
+        );
+    }
+
+    #[test]
+    fn binary_payloads_are_not_nmea() {
+        let frame = udp_frame(&[0xff, 0x00, 0x24, 0x80]);
+        assert_eq!(
+            inspect_frame(&[], SentenceSchemas::builtin(), &frame),
+            (true, false, String::from("NONMEA"))
+        );
+    }
+}
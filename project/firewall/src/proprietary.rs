@@ -0,0 +1,128 @@
+use crate::{split_sentences, DropReason};
+use nmea::{Nmea, SentenceSchemas, TalkerId};
+use packet_parser::PacketParser;
+
+// the manufacturer code of a proprietary sentence, like GRM for $PGRMZ
+pub fn proprietary_manufacturer(prefix: &str) -> Option<String> {
+    let (_, sentence) = nmea::split_tag_block(prefix);
+    let mut nmea = Nmea::new();
+    nmea.parse(sentence.to_string()).ok()?;
+    match nmea.talker_id {
+        TalkerId::Proprietary(manufacturer) => manufacturer.strip_prefix('P').map(String::from),
+        _ => None,
+    }
+}
+
+// whether a sends or receives list holds a sentence,
+// a proprietary one is also held through its manufacturer, $PGRM holds $PGRMZ
+pub fn lists_sentence(list: &[String], prefix: &str) -> bool {
+    list.iter().any(|entry| entry == prefix)
+        || proprietary_manufacturer(prefix).is_some_and(|manufacturer| {
+            list.iter()
+                .any(|entry| entry.strip_prefix("$P") == Some(manufacturer.as_str()))
+        })
+}
+
+// ship wide rules for proprietary sentences, on top of what the components send and receive
+#[derive(Debug, Clone, Default)]
+pub struct ProprietaryRules {
+    // manufacturers like $PSRF and sentences like $PGRMM no component may send
+    pub deny: Vec<String>,
+    // drop the sentences of manufacturers missing from the registry
+    pub deny_unregistered: bool,
+    pub denied: u64,
+}
+
+impl ProprietaryRules {
+    pub fn new(deny: &[String], deny_unregistered: bool) -> Self {
+        ProprietaryRules {
+            deny: deny.to_vec(),
+            deny_unregistered,
+            denied: 0,
+        }
+    }
+
+    pub fn denies(&self, prefix: &str) -> bool {
+        let Some(manufacturer) = proprietary_manufacturer(prefix) else {
+            return false;
+        };
+        lists_sentence(&self.deny, prefix)
+            || (self.deny_unregistered && nmea::manufacturer_name(&manufacturer).is_none())
+    }
+
+    // the first sentence of the frame the rules deny
+    pub fn inspect(&mut self, frame: &[u8]) -> Option<DropReason> {
+        let payload = PacketParser::new(frame).parse_traffic().ok()?;
+        let denied = split_sentences(&payload).into_iter().find_map(|sentence| {
+            // proprietary sentences are recognised whatever the schemas
+            let (_, _, prefix) =
+                crate::apply_policy(&[], SentenceSchemas::builtin(), sentence.to_string());
+            self.denies(&prefix).then_some(prefix)
+        })?;
+
+        self.denied += 1;
+        log::warn!("| PROPRIETARY SENTENCE {} DENIED", denied);
+        Some(DropReason::ProprietaryDenied(denied))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::udp_frame;
+
+    fn list(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    #[test]
+    fn the_manufacturer_comes_from_the_prefix() {
+        assert_eq!(
+            proprietary_manufacturer("$PGRMZ"),
+            Some(String::from("GRM"))
+        );
+        assert_eq!(
+            proprietary_manufacturer("\\s:GP0001*hh\\$PGRMZ"),
+            Some(String::from("GRM"))
+        );
+        assert_eq!(proprietary_manufacturer("$GPGGA"), None);
+    }
+
+    #[test]
+    fn a_manufacturer_lists_all_of_its_sentences() {
+        assert!(lists_sentence(&list(&["$PGRM"]), "$PGRMZ"));
+        assert!(lists_sentence(&list(&["$PGRMZ"]), "$PGRMZ"));
+        assert!(!lists_sentence(&list(&["$PGRMZ"]), "$PGRMM"));
+        assert!(!lists_sentence(&list(&["$PGRM"]), "$PSRF100"));
+        assert!(lists_sentence(&list(&["$GPGGA"]), "$GPGGA"));
+    }
+
+    #[test]
+    fn rules_deny_by_manufacturer_or_sentence() {
+        let rules = ProprietaryRules::new(&list(&["$PSRF", "$PGRMM"]), false);
+        assert!(rules.denies("$PSRF100"));
+        assert!(rules.denies("$PGRMM"));
+        assert!(!rules.denies("$PGRMZ"));
+        assert!(!rules.denies("$PXYZA"));
+        // only proprietary sentences are subject to the rules
+        assert!(!ProprietaryRules::new(&list(&["$GPGGA"]), true).denies("$GPGGA"));
+    }
+
+    #[test]
+    fn unregistered_manufacturers_can_be_denied() {
+        let rules = ProprietaryRules::new(&[], true);
+        assert!(rules.denies("$PXYZA"));
+        assert!(!rules.denies("$PGRMZ"));
+    }
+
+    #[test]
+    fn a_denied_sentence_drops_the_frame() {
+        let mut rules = ProprietaryRules::new(&list(&["$PGRMM"]), false);
+        assert_eq!(rules.inspect(&udp_frame(b"$PGRMZ,246,f,3*1B\r\n")), None);
+        assert_eq!(
+            rules.inspect(&udp_frame(b"$PGRMZ,246,f,3*1B\r\n$PGRMM,WGS 84*06\r\n")),
+            Some(DropReason::ProprietaryDenied(String::from("$PGRMM")))
+        );
+        assert_eq!(rules.denied, 1);
+    }
+}
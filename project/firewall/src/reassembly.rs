@@ -0,0 +1,599 @@
+use crate::DropReason;
+use packet_parser::{AddressFamily, PacketParser};
+use pnet::packet::ipv4::{Ipv4Flags, Ipv4Packet, MutableIpv4Packet};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+// the largest ip datagram, header included
+const MAX_IPV4_LENGTH: usize = 65535;
+// an ipv4 header without options, the least an ihl may describe
+const MIN_IPV4_HEADER: usize = 20;
+// fragment offsets are counted in blocks of this many bytes
+const FRAGMENT_BLOCK: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FragmentError {
+    // two fragments cover the same bytes with different data
+    Overlap,
+    // a fragment too small to be anything but an evasion attempt
+    TinyFragment,
+    // the fragment does not describe a valid piece of a datagram
+    Malformed,
+    // the datagram would grow past the configured size
+    TooLarge,
+    TooManyFragments,
+    // as many datagrams as allowed are already being reassembled
+    TableFull,
+    // the datagram was not completed in time
+    Timeout,
+    // ipv6 datagrams are never reassembled, their fragments would go by uninspected
+    Ipv6Fragment,
+}
+
+impl FragmentError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FragmentError::Overlap => "fragment_overlap",
+            FragmentError::TinyFragment => "tiny_fragment",
+            FragmentError::Malformed => "malformed_fragment",
+            FragmentError::TooLarge => "fragmented_datagram_too_large",
+            FragmentError::TooManyFragments => "too_many_fragments",
+            FragmentError::TableFull => "reassembly_table_full",
+            FragmentError::Timeout => "reassembly_timeout",
+            FragmentError::Ipv6Fragment => "ipv6_fragment",
+        }
+    }
+}
+
+impl fmt::Display for FragmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FragmentError::Overlap => write!(f, "overlapping fragments"),
+            FragmentError::TinyFragment => write!(f, "tiny fragment"),
+            FragmentError::Malformed => write!(f, "malformed fragment"),
+            FragmentError::TooLarge => write!(f, "fragmented datagram too large"),
+            FragmentError::TooManyFragments => write!(f, "too many fragments"),
+            FragmentError::TableFull => write!(f, "reassembly table full"),
+            FragmentError::Timeout => write!(f, "reassembly timed out"),
+            FragmentError::Ipv6Fragment => write!(f, "ipv6 fragment"),
+        }
+    }
+}
+
+// what became of a frame handed to the reassembler
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reassembly {
+    // not a fragment, the frame is inspected as it is
+    Whole,
+    // held until the rest of its datagram arrives
+    Pending,
+    // the last missing fragment, the frame carries the whole datagram
+    Complete(Vec<u8>),
+    Rejected(DropReason),
+}
+
+impl Reassembly {
+    // what becomes of a frame when reassembly is off, ipv4 fragments go on as they are
+    pub fn unreassembled(frame: &[u8]) -> Self {
+        match PacketParser::new(frame).layout() {
+            Some(layout) if layout.fragment && layout.family == Some(AddressFamily::Ipv6) => {
+                Reassembly::Rejected(DropReason::Fragment(FragmentError::Ipv6Fragment))
+            }
+            _ => Reassembly::Whole,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct DatagramKey {
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    protocol: u8,
+    identification: u16,
+}
+
+struct PartialDatagram {
+    // link layer and ip header of the first fragment once it arrived, with the offset of the ip header
+    header: Option<(Vec<u8>, usize)>,
+    // payload of every fragment by offset, they never overlap
+    fragments: BTreeMap<usize, Vec<u8>>,
+    // payload length, known once the last fragment arrived
+    length: Option<usize>,
+    received: usize,
+    first_seen: Instant,
+}
+
+// bounded ipv4 reassembly, so that the policy is applied to whole datagrams
+pub struct Reassembler {
+    datagrams: hashbrown::HashMap<DatagramKey, PartialDatagram>,
+    pub timeout: Duration,
+    pub max_datagrams: usize,
+    // payload bytes of one datagram
+    pub max_datagram_size: usize,
+    pub max_fragments: usize,
+    // payload bytes of every fragment but the last one
+    pub min_fragment_size: usize,
+    pub reassembled: u64,
+    pub timeouts: u64,
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Reassembler {
+            datagrams: hashbrown::HashMap::new(),
+            timeout: Duration::from_secs(5),
+            max_datagrams: 64,
+            max_datagram_size: MAX_IPV4_LENGTH - 20,
+            max_fragments: 64,
+            min_fragment_size: 64,
+            reassembled: 0,
+            timeouts: 0,
+        }
+    }
+
+    pub fn pending(&self) -> usize {
+        self.datagrams.len()
+    }
+
+    pub fn push(&mut self, frame: &[u8], now: Instant) -> Reassembly {
+        let layout = match PacketParser::new(frame).layout() {
+            Some(layout) if layout.family == Some(AddressFamily::Ipv4) => layout,
+            _ => return Reassembly::unreassembled(frame),
+        };
+        let network_offset = layout.network_offset;
+        let ipv4_packet = match Ipv4Packet::new(&frame[network_offset..]) {
+            Some(ipv4_packet) => ipv4_packet,
+            None => return Reassembly::Whole,
+        };
+
+        let more_fragments = ipv4_packet.get_flags() & Ipv4Flags::MoreFragments != 0;
+        let offset = ipv4_packet.get_fragment_offset() as usize * FRAGMENT_BLOCK;
+        if !more_fragments && offset == 0 {
+            return Reassembly::Whole;
+        }
+
+        let key = DatagramKey {
+            source: ipv4_packet.get_source(),
+            destination: ipv4_packet.get_destination(),
+            protocol: ipv4_packet.get_next_level_protocol().0,
+            identification: ipv4_packet.get_identification(),
+        };
+
+        let header_length = ipv4_packet.get_header_length() as usize * 4;
+        let total_length = ipv4_packet.get_total_length() as usize;
+        let payload = match frame.get(network_offset + header_length..network_offset + total_length)
+        {
+            Some(payload) if MIN_IPV4_HEADER <= header_length && header_length < total_length => {
+                payload
+            }
+            _ => return self.reject(&key, FragmentError::Malformed),
+        };
+        let end = offset + payload.len();
+
+        // every fragment but the last one ends on a block boundary
+        if more_fragments && payload.len() % FRAGMENT_BLOCK != 0 {
+            return self.reject(&key, FragmentError::Malformed);
+        }
+        if more_fragments && payload.len() < self.min_fragment_size {
+            return self.reject(&key, FragmentError::TinyFragment);
+        }
+        if end > self.max_datagram_size || header_length + end > MAX_IPV4_LENGTH {
+            return self.reject(&key, FragmentError::TooLarge);
+        }
+
+        if !self.datagrams.contains_key(&key) && self.datagrams.len() >= self.max_datagrams {
+            return Reassembly::Rejected(DropReason::Fragment(FragmentError::TableFull));
+        }
+        let datagram = self
+            .datagrams
+            .entry(key)
+            .or_insert_with(|| PartialDatagram {
+                header: None,
+                fragments: BTreeMap::new(),
+                length: None,
+                received: 0,
+                first_seen: now,
+            });
+
+        // a retransmitted fragment is harmless, any other overlap could hide data from the inspection
+        for (fragment_offset, fragment) in datagram.fragments.iter() {
+            let fragment_end = fragment_offset + fragment.len();
+            if *fragment_offset < end && offset < fragment_end {
+                if *fragment_offset == offset && fragment.as_slice() == payload {
+                    return Reassembly::Pending;
+                }
+                return self.reject(&key, FragmentError::Overlap);
+            }
+        }
+
+        // the last fragment fixes the length, nothing may lie past it
+        let length = match (more_fragments, datagram.length) {
+            (false, Some(length)) if length != end => {
+                return self.reject(&key, FragmentError::Overlap)
+            }
+            (false, _) => Some(end),
+            (true, length) => length,
+        };
+        let beyond_end = length.is_some_and(|length| {
+            end > length
+                || datagram
+                    .fragments
+                    .iter()
+                    .any(|(fragment_offset, fragment)| fragment_offset + fragment.len() > length)
+        });
+        if beyond_end {
+            return self.reject(&key, FragmentError::Overlap);
+        }
+        if datagram.fragments.len() >= self.max_fragments {
+            return self.reject(&key, FragmentError::TooManyFragments);
+        }
+
+        datagram.length = length;
+        if offset == 0 {
+            datagram.header = Some((
+                frame[..network_offset + header_length].to_vec(),
+                network_offset,
+            ));
+        }
+        datagram.fragments.insert(offset, payload.to_vec());
+        datagram.received += payload.len();
+
+        // fragments never overlap, so as many bytes as the length means no hole is left
+        if datagram.header.is_none() || datagram.length != Some(datagram.received) {
+            return Reassembly::Pending;
+        }
+
+        match self.datagrams.remove(&key).and_then(rebuild) {
+            Some(frame) => {
+                self.reassembled += 1;
+                Reassembly::Complete(frame)
+            }
+            None => Reassembly::Rejected(DropReason::Fragment(FragmentError::Malformed)),
+        }
+    }
+
+    // forgets the datagrams not completed in time, returns how many were
+    pub fn expire(&mut self, now: Instant) -> usize {
+        if self.datagrams.is_empty() {
+            return 0;
+        }
+
+        let before = self.datagrams.len();
+        let timeout = self.timeout;
+        self.datagrams.retain(|key, datagram| {
+            let expired = now.duration_since(datagram.first_seen) > timeout;
+            if expired {
+                log::debug!(
+                    "| REASSEMBLY OF {} -> {} ID {} TIMED OUT",
+                    key.source,
+                    key.destination,
+                    key.identification
+                );
+            }
+            !expired
+        });
+
+        let expired = before - self.datagrams.len();
+        self.timeouts += expired as u64;
+        expired
+    }
+
+    // the whole datagram is dropped, so that the fragments already held cannot be completed differently
+    fn reject(&mut self, key: &DatagramKey, error: FragmentError) -> Reassembly {
+        self.datagrams.remove(key);
+        log::warn!(
+            "| {} FROM {} (ID {}), DATAGRAM DROPPED",
+            error.to_string().to_uppercase(),
+            key.source,
+            key.identification
+        );
+        Reassembly::Rejected(DropReason::Fragment(error))
+    }
+}
+
+// the first fragment with every payload after it, as if the datagram was never fragmented
+fn rebuild(datagram: PartialDatagram) -> Option<Vec<u8>> {
+    let (mut frame, network_offset) = datagram.header?;
+    let header_length = frame.len() - network_offset;
+    datagram
+        .fragments
+        .values()
+        .for_each(|fragment| frame.extend_from_slice(fragment));
+
+    let mut ipv4_packet = MutableIpv4Packet::new(&mut frame[network_offset..])?;
+    ipv4_packet.set_flags(ipv4_packet.get_flags() & !Ipv4Flags::MoreFragments);
+    ipv4_packet.set_fragment_offset(0);
+    ipv4_packet.set_total_length((header_length + datagram.length?) as u16);
+    ipv4_packet.set_checksum(0);
+    let checksum = pnet::packet::ipv4::checksum(&ipv4_packet.to_immutable());
+    ipv4_packet.set_checksum(checksum);
+
+    Some(frame)
+}
+
+// splits a datagram larger than the mtu back into fragments, the ip options go in every one of them,
+// none when the frame fits as it is
+pub fn fragment_ipv4(frame: &[u8], mtu: usize) -> Option<Vec<Vec<u8>>> {
+    let layout = PacketParser::new(frame).layout()?;
+    if layout.family != Some(AddressFamily::Ipv4) {
+        return None;
+    }
+    let network_offset = layout.network_offset;
+    let ipv4_packet = Ipv4Packet::new(&frame[network_offset..])?;
+
+    let header_length = ipv4_packet.get_header_length() as usize * 4;
+    let total_length = (ipv4_packet.get_total_length() as usize).min(frame.len() - network_offset);
+    let block = mtu.saturating_sub(header_length) / FRAGMENT_BLOCK * FRAGMENT_BLOCK;
+    if total_length <= mtu
+        || block == 0
+        || header_length < MIN_IPV4_HEADER
+        || header_length >= total_length
+    {
+        return None;
+    }
+
+    let header = &frame[..network_offset + header_length];
+    let payload = &frame[network_offset + header_length..network_offset + total_length];
+    let first_offset = ipv4_packet.get_fragment_offset() as usize * FRAGMENT_BLOCK;
+    let more_fragments = ipv4_packet.get_flags() & Ipv4Flags::MoreFragments != 0;
+
+    let fragments = payload
+        .chunks(block)
+        .enumerate()
+        .filter_map(|(index, chunk)| {
+            let last = (index + 1) * block >= payload.len();
+            let mut fragment = header.to_vec();
+            fragment.extend_from_slice(chunk);
+
+            let mut ipv4_packet = MutableIpv4Packet::new(&mut fragment[network_offset..])?;
+            let flags = ipv4_packet.get_flags() & !Ipv4Flags::MoreFragments;
+            ipv4_packet.set_flags(match last && !more_fragments {
+                true => flags,
+                false => flags | Ipv4Flags::MoreFragments,
+            });
+            ipv4_packet
+                .set_fragment_offset(((first_offset + index * block) / FRAGMENT_BLOCK) as u16);
+            ipv4_packet.set_total_length((header_length + chunk.len()) as u16);
+            ipv4_packet.set_checksum(0);
+            let checksum = pnet::packet::ipv4::checksum(&ipv4_packet.to_immutable());
+            ipv4_packet.set_checksum(checksum);
+            Some(fragment)
+        })
+        .collect();
+    Some(fragments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::ethernet::{EtherTypes, MutableEthernetPacket};
+    use pnet::packet::ip::IpNextHeaderProtocols;
+
+    // an ipv4 fragment of datagram 7 carrying payload at offset bytes into it
+    fn fragment(offset: usize, more_fragments: bool, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0u8; 14 + 20 + payload.len()];
+        MutableEthernetPacket::new(&mut frame)
+            .unwrap()
+            .set_ethertype(EtherTypes::Ipv4);
+
+        let mut ipv4_packet = MutableIpv4Packet::new(&mut frame[14..]).unwrap();
+        ipv4_packet.set_version(4);
+        ipv4_packet.set_header_length(5);
+        ipv4_packet.set_total_length((20 + payload.len()) as u16);
+        ipv4_packet.set_identification(7);
+        ipv4_packet.set_flags(match more_fragments {
+            true => Ipv4Flags::MoreFragments,
+            false => 0,
+        });
+        ipv4_packet.set_fragment_offset((offset / FRAGMENT_BLOCK) as u16);
+        ipv4_packet.set_ttl(64);
+        ipv4_packet.set_next_level_protocol(IpNextHeaderProtocols::Udp);
+        ipv4_packet.set_source(Ipv4Addr::new(10, 42, 0, 10));
+        ipv4_packet.set_destination(Ipv4Addr::new(10, 42, 0, 20));
+        ipv4_packet.set_payload(payload);
+        let checksum = pnet::packet::ipv4::checksum(&ipv4_packet.to_immutable());
+        ipv4_packet.set_checksum(checksum);
+        frame
+    }
+
+    fn rejected(error: FragmentError) -> Reassembly {
+        Reassembly::Rejected(DropReason::Fragment(error))
+    }
+
+    #[test]
+    fn fragments_are_rebuilt_into_the_datagram() {
+        let mut reassembler = Reassembler::new();
+        let now = Instant::now();
+
+        assert_eq!(
+            reassembler.push(&fragment(64, false, &[2u8; 10]), now),
+            Reassembly::Pending
+        );
+        let frame = match reassembler.push(&fragment(0, true, &[1u8; 64]), now) {
+            Reassembly::Complete(frame) => frame,
+            other => panic!("datagram not complete: {:?}", other),
+        };
+
+        let ipv4_packet = Ipv4Packet::new(&frame[14..]).unwrap();
+        assert_eq!(ipv4_packet.get_total_length(), 20 + 74);
+        assert_eq!(ipv4_packet.get_flags() & Ipv4Flags::MoreFragments, 0);
+        assert_eq!(ipv4_packet.get_fragment_offset(), 0);
+        assert_eq!(&frame[34..98], &[1u8; 64]);
+        assert_eq!(&frame[98..], &[2u8; 10]);
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn a_retransmitted_fragment_is_harmless() {
+        let mut reassembler = Reassembler::new();
+        let now = Instant::now();
+
+        let first = fragment(0, true, &[1u8; 64]);
+        assert_eq!(reassembler.push(&first, now), Reassembly::Pending);
+        assert_eq!(reassembler.push(&first, now), Reassembly::Pending);
+        assert!(matches!(
+            reassembler.push(&fragment(64, false, &[2u8; 10]), now),
+            Reassembly::Complete(_)
+        ));
+    }
+
+    #[test]
+    fn overlapping_fragments_drop_the_datagram() {
+        let mut reassembler = Reassembler::new();
+        let now = Instant::now();
+
+        assert_eq!(
+            reassembler.push(&fragment(0, true, &[1u8; 64]), now),
+            Reassembly::Pending
+        );
+        // same offset, different bytes
+        assert_eq!(
+            reassembler.push(&fragment(0, true, &[3u8; 64]), now),
+            rejected(FragmentError::Overlap)
+        );
+        assert_eq!(reassembler.pending(), 0);
+
+        assert_eq!(
+            reassembler.push(&fragment(0, true, &[1u8; 64]), now),
+            Reassembly::Pending
+        );
+        // starts inside the first fragment
+        assert_eq!(
+            reassembler.push(&fragment(56, false, &[2u8; 16]), now),
+            rejected(FragmentError::Overlap)
+        );
+    }
+
+    #[test]
+    fn a_second_last_fragment_with_another_length_is_an_overlap() {
+        let mut reassembler = Reassembler::new();
+        let now = Instant::now();
+
+        assert_eq!(
+            reassembler.push(&fragment(64, false, &[2u8; 10]), now),
+            Reassembly::Pending
+        );
+        assert_eq!(
+            reassembler.push(&fragment(128, false, &[2u8; 10]), now),
+            rejected(FragmentError::Overlap)
+        );
+    }
+
+    #[test]
+    fn oversized_datagrams_are_rejected() {
+        let mut reassembler = Reassembler::new();
+        reassembler.max_datagram_size = 100;
+        let now = Instant::now();
+
+        assert_eq!(
+            reassembler.push(&fragment(96, false, &[2u8; 10]), now),
+            rejected(FragmentError::TooLarge)
+        );
+
+        // past the largest ip datagram whatever the configuration
+        let mut reassembler = Reassembler::new();
+        reassembler.max_datagram_size = usize::MAX;
+        assert_eq!(
+            reassembler.push(&fragment(65528, false, &[2u8; 10]), now),
+            rejected(FragmentError::TooLarge)
+        );
+    }
+
+    #[test]
+    fn tiny_and_unaligned_fragments_are_rejected() {
+        let mut reassembler = Reassembler::new();
+        let now = Instant::now();
+
+        assert_eq!(
+            reassembler.push(&fragment(0, true, &[1u8; 8]), now),
+            rejected(FragmentError::TinyFragment)
+        );
+        assert_eq!(
+            reassembler.push(&fragment(0, true, &[1u8; 70]), now),
+            rejected(FragmentError::Malformed)
+        );
+    }
+
+    #[test]
+    fn a_header_length_below_five_words_is_malformed() {
+        let mut reassembler = Reassembler::new();
+        let mut frame = fragment(0, true, &[1u8; 64]);
+        MutableIpv4Packet::new(&mut frame[14..])
+            .unwrap()
+            .set_header_length(4);
+
+        assert_eq!(
+            reassembler.push(&frame, Instant::now()),
+            rejected(FragmentError::Malformed)
+        );
+        assert_eq!(fragment_ipv4(&frame, 40), None);
+    }
+
+    #[test]
+    fn incomplete_datagrams_expire() {
+        let mut reassembler = Reassembler::new();
+        let now = Instant::now();
+
+        reassembler.push(&fragment(0, true, &[1u8; 64]), now);
+        assert_eq!(reassembler.expire(now + Duration::from_secs(1)), 0);
+        assert_eq!(
+            reassembler.expire(now + reassembler.timeout + Duration::from_millis(1)),
+            1
+        );
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn large_datagrams_are_fragmented_again_on_block_boundaries() {
+        let frame = fragment(0, false, &[5u8; 100]);
+        assert_eq!(fragment_ipv4(&frame, 1500), None);
+
+        let fragments = fragment_ipv4(&frame, 60).unwrap();
+        assert_eq!(fragments.len(), 3);
+        let mut reassembler = Reassembler::new();
+        reassembler.min_fragment_size = 0;
+        let now = Instant::now();
+        assert_eq!(reassembler.push(&fragments[0], now), Reassembly::Pending);
+        assert_eq!(reassembler.push(&fragments[1], now), Reassembly::Pending);
+        assert_eq!(
+            reassembler.push(&fragments[2], now),
+            Reassembly::Complete(frame)
+        );
+    }
+
+    #[test]
+    fn ipv6_fragments_are_never_let_through() {
+        // ipv6 header followed by a fragment header announcing more fragments
+        let mut frame = vec![0u8; 14 + 40 + 8 + 16];
+        MutableEthernetPacket::new(&mut frame)
+            .unwrap()
+            .set_ethertype(EtherTypes::Ipv6);
+        frame[14] = 0x60;
+        frame[18..20].copy_from_slice(&24u16.to_be_bytes());
+        frame[20] = IpNextHeaderProtocols::Ipv6Frag.0;
+        frame[21] = 64;
+        frame[54] = IpNextHeaderProtocols::Udp.0;
+        frame[57] = 1;
+
+        assert_eq!(
+            Reassembly::unreassembled(&frame),
+            rejected(FragmentError::Ipv6Fragment)
+        );
+        assert_eq!(
+            Reassembler::new().push(&frame, Instant::now()),
+            rejected(FragmentError::Ipv6Fragment)
+        );
+        assert_eq!(
+            Reassembly::unreassembled(&fragment(0, false, &[1u8; 10])),
+            Reassembly::Whole
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use crate::apply_policy;
+use nmea::SentenceSchemas;
+use packet_parser::{AddressFamily, PacketParser};
+use pnet::packet::ipv4::MutableIpv4Packet;
+use pnet::packet::ipv6::MutableIpv6Packet;
+use pnet::packet::udp::MutableUdpPacket;
+
+const UDP_HEADER_LEN: usize = 8;
+const IPV6_HEADER_LEN: usize = 40;
+
+// how a datagram packing several sentences is judged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MultiSentence {
+    // one forbidden sentence drops the whole datagram
+    #[default]
+    Drop,
+    // the forbidden sentences are cut out and the datagram goes on with the others
+    Filter,
+}
+
+impl MultiSentence {
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.to_lowercase().as_str() {
+            "drop" => Some(MultiSentence::Drop),
+            "filter" => Some(MultiSentence::Filter),
+            _ => None,
+        }
+    }
+}
+
+// the sentences of a payload, each with its line terminator and tag block, the IEC 61162-450 header
+// is not one of them, a start delimiter or a tag block opens a new sentence even without a line
+// terminator before it, so that no sentence is judged by the prefix of another
+pub fn split_sentences(payload: &str) -> Vec<&str> {
+    let (_, payload) = nmea::strip_udpbc_header(payload);
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut in_tag_block = false;
+    let mut after_tag_block = false;
+
+    for (index, character) in payload.char_indices() {
+        let opens = match character {
+            '\\' => !in_tag_block,
+            // the sentence a tag block stands in front of belongs to it,
+            // a tag block left open does not hide the sentences after it
+            '$' | '!' => !after_tag_block,
+            _ => false,
+        };
+        if opens && index > start {
+            sentences.push(&payload[start..index]);
+            start = index;
+        }
+        if opens && character != '\\' {
+            in_tag_block = false;
+        }
+
+        after_tag_block = in_tag_block && character == '\\';
+        match character {
+            '\\' => in_tag_block = !in_tag_block,
+            '\n' => {
+                sentences.push(&payload[start..=index]);
+                start = index + 1;
+                in_tag_block = false;
+            }
+            _ => {}
+        }
+    }
+    sentences.push(&payload[start..]);
+
+    sentences
+        .into_iter()
+        .filter(|sentence| !sentence.trim().is_empty())
+        .collect()
+}
+
+// the distinct nmea sentences a frame carries, in order
+pub fn sentence_prefixes(schemas: &SentenceSchemas, frame: &[u8]) -> Vec<String> {
+    let Ok(payload) = PacketParser::new(frame).parse_traffic() else {
+        return Vec::new();
+    };
+
+    let mut prefixes: Vec<String> = Vec::new();
+    split_sentences(&payload).into_iter().for_each(|sentence| {
+        let (_, is_nmea, prefix) = apply_policy(&[], schemas, sentence.to_string());
+        if is_nmea && !prefixes.contains(&prefix) {
+            prefixes.push(prefix);
+        }
+    });
+    prefixes
+}
+
+// the IEC 61162-450 tag block of the first sentence carrying one
+pub fn first_tag_block(frame: &[u8]) -> Option<nmea::TagBlock> {
+    let payload = PacketParser::new(frame).parse_traffic().ok()?;
+    split_sentences(&payload)
+        .into_iter()
+        .find_map(|sentence| nmea::split_tag_block(sentence).0)
+}
+
+// the frame with the sentences the component may send only, and the prefixes cut out of it,
+// none when nothing allowed is left
+pub fn filter_sentences(
+    sends: &[String],
+    schemas: &SentenceSchemas,
+    frame: &[u8],
+) -> Option<(Vec<u8>, Vec<String>)> {
+    let payload = PacketParser::new(frame).parse_traffic().ok()?;
+
+    let mut kept = String::new();
+    let mut denied = Vec::new();
+    let (udpbc, _) = nmea::strip_udpbc_header(&payload);
+    split_sentences(&payload).into_iter().for_each(|sentence| {
+        match apply_policy(sends, schemas, sentence.to_string()) {
+            (true, _, _) => kept.push_str(sentence),
+            (false, _, prefix) => denied.push(prefix),
+        }
+    });
+
+    if kept.is_empty() {
+        return None;
+    }
+    if udpbc {
+        kept.insert_str(0, nmea::UDPBC_HEADER);
+    }
+    Some((with_udp_payload(frame, kept.as_bytes())?, denied))
+}
+
+// the frame with the sentences keep holds for only, non nmea text is named NONMEA,
+// none when nothing is left
+pub fn retain_sentences(
+    schemas: &SentenceSchemas,
+    frame: &[u8],
+    keep: impl Fn(&str) -> bool,
+) -> Option<Vec<u8>> {
+    let payload = PacketParser::new(frame).parse_traffic().ok()?;
+
+    let (udpbc, _) = nmea::strip_udpbc_header(&payload);
+    let mut kept: String = split_sentences(&payload)
+        .into_iter()
+        .filter(|sentence| {
+            let (_, _, prefix) = apply_policy(&[], schemas, sentence.to_string());
+            keep(&prefix)
+        })
+        .collect();
+
+    if kept.is_empty() {
+        return None;
+    }
+    if udpbc {
+        kept.insert_str(0, nmea::UDPBC_HEADER);
+    }
+    with_udp_payload(frame, kept.as_bytes())
+}
+
+// the same frame carrying another udp payload, lengths and checksums updated
+fn with_udp_payload(frame: &[u8], payload: &[u8]) -> Option<Vec<u8>> {
+    let layout = PacketParser::new(frame).layout()?;
+    let udp = layout.udp?;
+    let udp_length = UDP_HEADER_LEN + payload.len();
+
+    let mut new_frame = frame[..udp.start + UDP_HEADER_LEN].to_vec();
+    new_frame.extend_from_slice(payload);
+
+    let (network, transport) = new_frame.split_at_mut(udp.start);
+    let network = &mut network[layout.network_offset..];
+    let mut udp_packet = MutableUdpPacket::new(transport)?;
+    udp_packet.set_length(udp_length as u16);
+    udp_packet.set_checksum(0);
+
+    match layout.family? {
+        AddressFamily::Ipv4 => {
+            let header_length = network.len();
+            let mut ipv4_packet = MutableIpv4Packet::new(network)?;
+            ipv4_packet.set_total_length((header_length + udp_length) as u16);
+            ipv4_packet.set_checksum(0);
+            let checksum = pnet::packet::ipv4::checksum(&ipv4_packet.to_immutable());
+            ipv4_packet.set_checksum(checksum);
+
+            let checksum = pnet::packet::udp::ipv4_checksum(
+                &udp_packet.to_immutable(),
+                &ipv4_packet.get_source(),
+                &ipv4_packet.get_destination(),
+            );
+            udp_packet.set_checksum(checksum);
+        }
+        AddressFamily::Ipv6 => {
+            let extension_length = network.len() - IPV6_HEADER_LEN;
+            let mut ipv6_packet = MutableIpv6Packet::new(network)?;
+            ipv6_packet.set_payload_length((extension_length + udp_length) as u16);
+
+            let checksum = pnet::packet::udp::ipv6_checksum(
+                &udp_packet.to_immutable(),
+                &ipv6_packet.get_source(),
+                &ipv6_packet.get_destination(),
+            );
+            udp_packet.set_checksum(checksum);
+        }
+    }
+
+    Some(new_frame)
+}
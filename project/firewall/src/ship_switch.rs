@@ -0,0 +1,247 @@
+use crate::{ArpGuard, IgmpSnooping};
+use pnet::util::MacAddr;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+const DEFAULT_AGING_TIME: Duration = Duration::from_secs(300);
+// expired entries are swept at most this often
+const AGING_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    // seeded from the policy, never aged out nor moved
+    Static,
+    Dynamic,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SwitchEntry {
+    pub port: usize,
+    pub kind: EntryKind,
+    pub last_seen: Instant,
+}
+
+// what learning a source address did to the table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LearnEvent {
+    Learned,
+    Refreshed,
+    Moved { from: usize },
+    // a static address showed up on another port, the table is left untouched
+    StaticConflict { port: usize },
+    // the port already learned as many addresses as it may
+    PortLimitReached,
+}
+
+// forwarding database of the ship, maps a mac address to the index of the component port it lives on
+pub struct ShipSwitch {
+    entries: hashbrown::HashMap<[u8; 6], SwitchEntry>,
+    // dynamic entries not seen for this long are forgotten, zero disables aging
+    pub aging_time: Duration,
+    // dynamic entries a single port may learn, zero means no limit
+    pub max_macs_per_port: usize,
+    pub moves: u64,
+    pub static_conflicts: u64,
+    pub port_limit_rejections: u64,
+    pub aged_out: u64,
+    last_sweep: Option<Instant>,
+    // ip to mac bindings of the policy, for proxy arp and arp inspection
+    pub arp: ArpGuard,
+    // ipv4 multicast groups the ports joined
+    pub igmp: IgmpSnooping,
+}
+
+impl Default for ShipSwitch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShipSwitch {
+    pub fn new() -> Self {
+        ShipSwitch {
+            entries: hashbrown::HashMap::new(),
+            aging_time: DEFAULT_AGING_TIME,
+            max_macs_per_port: 0,
+            moves: 0,
+            static_conflicts: 0,
+            port_limit_rejections: 0,
+            aged_out: 0,
+            last_sweep: None,
+            arp: ArpGuard::new(),
+            igmp: IgmpSnooping::new(),
+        }
+    }
+
+    pub fn add_static(&mut self, mac: [u8; 6], port: usize) {
+        self.entries.insert(
+            mac,
+            SwitchEntry {
+                port,
+                kind: EntryKind::Static,
+                last_seen: Instant::now(),
+            },
+        );
+    }
+
+    // static entry for a mac written as in the policy, like 54:00:00:00:00:10
+    pub fn add_static_from_str(&mut self, mac: &str, port: usize) -> Result<(), String> {
+        let mac = MacAddr::from_str(mac).map_err(|_| format!("invalid mac address {}", mac))?;
+        self.add_static(mac.octets(), port);
+        Ok(())
+    }
+
+    // drops the static entries of a port, used when the policy of its component changes
+    pub fn remove_static(&mut self, port: usize) {
+        self.entries
+            .retain(|_, entry| !(entry.kind == EntryKind::Static && entry.port == port));
+    }
+
+    pub fn learn(&mut self, mac: [u8; 6], port: usize, now: Instant) -> LearnEvent {
+        // an expired entry is as good as none, whatever port it was on
+        let existing = self
+            .entries
+            .get(&mac)
+            .copied()
+            .filter(|entry| entry.kind == EntryKind::Static || !self.is_expired(entry, now));
+
+        match existing {
+            Some(entry) if entry.port == port => {
+                self.entries.insert(
+                    mac,
+                    SwitchEntry {
+                        last_seen: now,
+                        ..entry
+                    },
+                );
+                LearnEvent::Refreshed
+            }
+            Some(entry) if entry.kind == EntryKind::Static => {
+                self.static_conflicts += 1;
+                log::warn!(
+                    "| STATIC MAC {} SEEN ON PORT {} INSTEAD OF {}",
+                    format_mac(&mac),
+                    port,
+                    entry.port
+                );
+                LearnEvent::StaticConflict { port: entry.port }
+            }
+            _ => {
+                // counting only happens for addresses new to the port, not for every frame
+                if self.max_macs_per_port > 0
+                    && self.dynamic_entries_on(port) >= self.max_macs_per_port
+                {
+                    self.port_limit_rejections += 1;
+                    log::warn!(
+                        "| PORT {} REACHED ITS LIMIT OF {} MAC ADDRESSES, {} NOT LEARNED",
+                        port,
+                        self.max_macs_per_port,
+                        format_mac(&mac)
+                    );
+                    return LearnEvent::PortLimitReached;
+                }
+
+                self.entries.insert(
+                    mac,
+                    SwitchEntry {
+                        port,
+                        kind: EntryKind::Dynamic,
+                        last_seen: now,
+                    },
+                );
+
+                match existing {
+                    Some(entry) => {
+                        self.moves += 1;
+                        log::warn!(
+                            "| MAC {} MOVED FROM PORT {} TO PORT {}",
+                            format_mac(&mac),
+                            entry.port,
+                            port
+                        );
+                        LearnEvent::Moved { from: entry.port }
+                    }
+                    None => {
+                        log::debug!("| MAC {} LEARNED ON PORT {}", format_mac(&mac), port);
+                        LearnEvent::Learned
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, mac: &[u8; 6]) -> Option<usize> {
+        self.get_at(mac, Instant::now())
+    }
+
+    // the port of the mac as the switch knew it at that time
+    pub fn get_at(&self, mac: &[u8; 6], now: Instant) -> Option<usize> {
+        let entry = self.entries.get(mac)?;
+        match entry.kind {
+            EntryKind::Dynamic if self.is_expired(entry, now) => None,
+            _ => Some(entry.port),
+        }
+    }
+
+    pub fn entry(&self, mac: &[u8; 6]) -> Option<&SwitchEntry> {
+        self.entries.get(mac)
+    }
+
+    // forgets the dynamic entries older than the aging time, returns how many were removed
+    pub fn age_out(&mut self, now: Instant) -> usize {
+        if self.aging_time.is_zero() {
+            return 0;
+        }
+        if let Some(last_sweep) = self.last_sweep {
+            if now.duration_since(last_sweep) < AGING_SWEEP_INTERVAL {
+                return 0;
+            }
+        }
+        self.last_sweep = Some(now);
+
+        let before = self.entries.len();
+        let aging_time = self.aging_time;
+        self.entries.retain(|mac, entry| {
+            let expired = entry.kind == EntryKind::Dynamic
+                && now.duration_since(entry.last_seen) > aging_time;
+            if expired {
+                log::debug!("| MAC {} AGED OUT OF PORT {}", format_mac(mac), entry.port);
+            }
+            !expired
+        });
+
+        let removed = before - self.entries.len();
+        self.aged_out += removed as u64;
+        removed
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8; 6], &SwitchEntry)> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn dynamic_entries_on(&self, port: usize) -> usize {
+        self.entries
+            .values()
+            .filter(|entry| entry.kind == EntryKind::Dynamic && entry.port == port)
+            .count()
+    }
+
+    fn is_expired(&self, entry: &SwitchEntry, now: Instant) -> bool {
+        !self.aging_time.is_zero() && now.duration_since(entry.last_seen) > self.aging_time
+    }
+}
+
+pub fn format_mac(mac: &[u8; 6]) -> String {
+    mac.iter()
+        .map(|octet| format!("{:02x}", octet))
+        .collect::<Vec<String>>()
+        .join(":")
+}
@@ -0,0 +1,129 @@
+use crate::{format_mac, split_sentences, DropReason};
+use packet_parser::PacketParser;
+use pnet::util::MacAddr;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+// the source addresses a component may use on its own interface, anything else is spoofed
+#[derive(Debug, Clone)]
+pub struct SourceBinding {
+    pub macs: Vec<[u8; 6]>,
+    pub ips: Vec<IpAddr>,
+    // IEC 61162-450 source identifiers of the component, none leaves the tag blocks unchecked.
+    // a component declaring some has to name one in the tag block of every sentence it sends
+    pub source_ids: Vec<String>,
+}
+
+impl SourceBinding {
+    // the declared mac and ip of the component plus the extra ones it is allowed to use
+    pub fn new(
+        mac: &str,
+        ip: &str,
+        allowed_macs: &[String],
+        allowed_ips: &[String],
+    ) -> Result<Self, String> {
+        let macs = std::iter::once(mac)
+            .chain(allowed_macs.iter().map(String::as_str))
+            .map(|mac| {
+                MacAddr::from_str(mac)
+                    .map(|mac| mac.octets())
+                    .map_err(|_| format!("invalid mac address {}", mac))
+            })
+            .collect::<Result<Vec<[u8; 6]>, String>>()?;
+
+        let ips = std::iter::once(ip)
+            .chain(allowed_ips.iter().map(String::as_str))
+            .map(|ip| IpAddr::from_str(ip).map_err(|_| format!("invalid ip address {}", ip)))
+            .collect::<Result<Vec<IpAddr>, String>>()?;
+
+        Ok(SourceBinding {
+            macs,
+            ips,
+            source_ids: Vec::new(),
+        })
+    }
+
+    // frames without an ip header are only checked against the mac addresses
+    pub fn check(&self, frame: &[u8]) -> Option<DropReason> {
+        let source_mac: [u8; 6] = frame.get(6..12)?.try_into().ok()?;
+        if !self.macs.contains(&source_mac) {
+            return Some(DropReason::SpoofedSourceMac(format_mac(&source_mac)));
+        }
+
+        match PacketParser::new(frame).source_ip() {
+            Some(source_ip) if !self.ips.contains(&source_ip) => {
+                Some(DropReason::SpoofedSourceIp(source_ip.to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    // the source identifiers in the tag blocks of the sentences, checked once the datagram is whole.
+    // a sentence without one could come from anyone, so it is as spoofed as one naming another source
+    pub fn check_source_id(&self, frame: &[u8]) -> Option<DropReason> {
+        if self.source_ids.is_empty() {
+            return None;
+        }
+
+        let payload = PacketParser::new(frame).parse_traffic().ok()?;
+        split_sentences(&payload)
+            .into_iter()
+            .map(nmea::split_tag_block)
+            .filter(|(_, sentence)| sentence.starts_with(['$', '!']))
+            .find_map(
+                |(tag_block, _)| match tag_block.and_then(|tag_block| tag_block.source) {
+                    Some(source_id) if self.source_ids.contains(&source_id) => None,
+                    Some(source_id) => Some(DropReason::SpoofedSourceId(source_id)),
+                    None => Some(DropReason::SpoofedSourceId(String::new())),
+                },
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::udp_frame;
+
+    fn binding(source_ids: &[&str]) -> SourceBinding {
+        SourceBinding {
+            source_ids: source_ids.iter().map(|id| id.to_string()).collect(),
+            ..SourceBinding::new("54:00:00:00:00:30", "10.42.0.30", &[], &[]).unwrap()
+        }
+    }
+
+    const GGA: &str = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n";
+
+    #[test]
+    fn declared_source_ids_pass() {
+        let frame = udp_frame(format!("\\s:GP0001*hh\\{}", GGA).as_bytes());
+        assert_eq!(binding(&["GP0001"]).check_source_id(&frame), None);
+    }
+
+    #[test]
+    fn other_source_ids_are_spoofed() {
+        let frame = udp_frame(format!("\\s:GP0001*hh\\{}\\s:GP0002*hh\\{}", GGA, GGA).as_bytes());
+        assert_eq!(
+            binding(&["GP0001"]).check_source_id(&frame),
+            Some(DropReason::SpoofedSourceId(String::from("GP0002")))
+        );
+    }
+
+    #[test]
+    fn untagged_sentences_are_spoofed_once_source_ids_are_declared() {
+        let frame = udp_frame(GGA.as_bytes());
+        assert_eq!(
+            binding(&["GP0001"]).check_source_id(&frame),
+            Some(DropReason::SpoofedSourceId(String::new()))
+        );
+        let frame = udp_frame(format!("\\d:EC0001*hh\\{}", GGA).as_bytes());
+        assert!(binding(&["GP0001"]).check_source_id(&frame).is_some());
+
+        // without declared source ids the tag blocks are not looked at
+        assert_eq!(binding(&[]).check_source_id(&frame), None);
+        assert_eq!(
+            binding(&[]).check_source_id(&udp_frame(GGA.as_bytes())),
+            None
+        );
+    }
+}
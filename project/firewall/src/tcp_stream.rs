@@ -0,0 +1,762 @@
+use crate::{apply_policy, split_sentences, DropReason};
+use nmea::SentenceSchemas;
+use packet_parser::{AddressFamily, PacketParser};
+use pnet::packet::ethernet::MutableEthernetPacket;
+use pnet::packet::ipv4::MutableIpv4Packet;
+use pnet::packet::ipv6::MutableIpv6Packet;
+use pnet::packet::tcp::{MutableTcpPacket, TcpFlags, TcpPacket};
+use pnet::packet::Packet;
+use std::collections::VecDeque;
+use std::fmt;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+const NMEA_TCP_PORT: u16 = 10110;
+const TCP_HEADER_LEN: usize = 20;
+// bytes kept per direction to check retransmissions against
+const RETRANSMISSION_WINDOW: usize = 65536;
+// idle flows are swept at most this often
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TcpError {
+    // data past a hole in the stream, the sender retransmits what is missing
+    OutOfOrder,
+    // bytes already inspected sent again with a different content
+    RetransmissionMismatch,
+    // no line terminator within the configured length
+    LineTooLong,
+    // as many connections as allowed are already tracked
+    FlowLimit,
+    // data on a connection whose start was never seen, it cannot be inspected from its first byte
+    UntrackedFlow,
+}
+
+impl TcpError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TcpError::OutOfOrder => "tcp_out_of_order",
+            TcpError::RetransmissionMismatch => "tcp_retransmission_mismatch",
+            TcpError::LineTooLong => "tcp_line_too_long",
+            TcpError::FlowLimit => "tcp_flow_limit",
+            TcpError::UntrackedFlow => "tcp_untracked_flow",
+        }
+    }
+}
+
+impl fmt::Display for TcpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TcpError::OutOfOrder => write!(f, "tcp segment out of order"),
+            TcpError::RetransmissionMismatch => write!(f, "tcp retransmission differs"),
+            TcpError::LineTooLong => write!(f, "tcp line too long"),
+            TcpError::FlowLimit => write!(f, "tcp flow limit reached"),
+            TcpError::UntrackedFlow => write!(f, "tcp data on an untracked connection"),
+        }
+    }
+}
+
+// what the ship does with a tcp segment
+#[derive(Debug, Clone, PartialEq)]
+pub enum TcpVerdict {
+    // not a segment of an inspected port
+    Uninspected,
+    // forwarded as it is, with the sentences its payload completed
+    Forward(Vec<String>),
+    // dropped, the connection goes on
+    Drop(DropReason),
+    // the connection carried something forbidden and has to be torn down
+    Reset(DropReason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FlowKey {
+    source: IpAddr,
+    source_port: u16,
+    destination: IpAddr,
+    destination_port: u16,
+}
+
+// one direction of a connection
+struct TcpFlow {
+    next_seq: u32,
+    // the last bytes forwarded, ending right before next_seq
+    forwarded: VecDeque<u8>,
+    // the sentences not terminated yet
+    line: Vec<u8>,
+    // sentences of the line already judged, as soon as their address field was complete
+    checked: usize,
+    last_seen: Instant,
+}
+
+// follows the byte stream of the tcp connections on the nmea ports and checks every sentence in it,
+// segments are forwarded in order only so that what the receiver reassembles is what was inspected
+pub struct TcpInspector {
+    flows: hashbrown::HashMap<FlowKey, TcpFlow>,
+    // connections with one of these ports on either side are inspected
+    pub ports: Vec<u16>,
+    pub max_flows: usize,
+    pub max_line_length: usize,
+    pub idle_timeout: Duration,
+    pub resets: u64,
+    last_sweep: Option<Instant>,
+}
+
+impl Default for TcpInspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TcpInspector {
+    pub fn new() -> Self {
+        TcpInspector {
+            flows: hashbrown::HashMap::new(),
+            ports: vec![NMEA_TCP_PORT],
+            max_flows: 256,
+            max_line_length: 1024,
+            idle_timeout: Duration::from_secs(300),
+            resets: 0,
+            last_sweep: None,
+        }
+    }
+
+    pub fn flows(&self) -> usize {
+        self.flows.len()
+    }
+
+    pub fn inspect(
+        &mut self,
+        frame: &[u8],
+        sends: &[String],
+        schemas: &SentenceSchemas,
+        now: Instant,
+    ) -> TcpVerdict {
+        self.expire(now);
+
+        let (key, tcp_packet) = match segment(frame) {
+            Some((key, tcp_packet))
+                if self.ports.contains(&key.source_port)
+                    || self.ports.contains(&key.destination_port) =>
+            {
+                (key, tcp_packet)
+            }
+            _ => return TcpVerdict::Uninspected,
+        };
+
+        let flags = tcp_packet.get_flags();
+        if flags & TcpFlags::RST != 0 {
+            self.flows.remove(&key);
+            return TcpVerdict::Forward(Vec::new());
+        }
+
+        let payload = tcp_packet.payload();
+        let mut seq = tcp_packet.get_sequence();
+        if flags & TcpFlags::SYN != 0 {
+            // a new connection, or the same one again, starts over
+            self.flows.remove(&key);
+            seq = seq.wrapping_add(1);
+        }
+
+        // only a connection seen from its start can be inspected, a flow is never made up from
+        // a segment in the middle of one
+        if !self.flows.contains_key(&key) {
+            if flags & TcpFlags::SYN == 0 {
+                return match payload.is_empty() {
+                    true => TcpVerdict::Forward(Vec::new()),
+                    false => TcpVerdict::Reset(DropReason::TcpStream(TcpError::UntrackedFlow)),
+                };
+            }
+            if self.flows.len() >= self.max_flows {
+                return TcpVerdict::Drop(DropReason::TcpStream(TcpError::FlowLimit));
+            }
+            self.flows.insert(
+                key,
+                TcpFlow {
+                    next_seq: seq,
+                    forwarded: VecDeque::new(),
+                    line: Vec::new(),
+                    checked: 0,
+                    last_seen: now,
+                },
+            );
+        }
+
+        let fin = flags & TcpFlags::FIN != 0;
+        let verdict = match self.flows.get_mut(&key) {
+            Some(flow) => {
+                flow.last_seen = now;
+                flow.accept(seq, payload, fin, sends, schemas, self.max_line_length)
+            }
+            None => TcpVerdict::Forward(Vec::new()),
+        };
+
+        match &verdict {
+            TcpVerdict::Reset(reason) => {
+                self.flows.remove(&key);
+                self.resets += 1;
+                log::warn!(
+                    "| TCP {}:{} -> {}:{} RESET: {}",
+                    key.source,
+                    key.source_port,
+                    key.destination,
+                    key.destination_port,
+                    reason.to_string().to_uppercase()
+                );
+            }
+            TcpVerdict::Forward(_) if fin => {
+                self.flows.remove(&key);
+            }
+            _ => {}
+        }
+
+        verdict
+    }
+
+    // stops tracking the connection of the segment, once the ship reset it
+    pub fn forget(&mut self, frame: &[u8]) {
+        if let Some((key, _)) = segment(frame) {
+            if self.flows.remove(&key).is_some() {
+                self.resets += 1;
+            }
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        if let Some(last_sweep) = self.last_sweep {
+            if now.duration_since(last_sweep) < EXPIRY_SWEEP_INTERVAL {
+                return;
+            }
+        }
+        self.last_sweep = Some(now);
+
+        let idle_timeout = self.idle_timeout;
+        self.flows
+            .retain(|_, flow| now.duration_since(flow.last_seen) <= idle_timeout);
+    }
+}
+
+impl TcpFlow {
+    // the bytes of a sentence are only forwarded once its address field was allowed,
+    // the line left when the sender closes its side is judged as it is
+    fn accept(
+        &mut self,
+        seq: u32,
+        payload: &[u8],
+        fin: bool,
+        sends: &[String],
+        schemas: &SentenceSchemas,
+        max_line_length: usize,
+    ) -> TcpVerdict {
+        if payload.is_empty() && !fin {
+            return TcpVerdict::Forward(Vec::new());
+        }
+
+        // how far the segment starts before the next expected byte, with sequence wrap around
+        let behind = self.next_seq.wrapping_sub(seq) as i32;
+        if behind < 0 {
+            return TcpVerdict::Drop(DropReason::TcpStream(TcpError::OutOfOrder));
+        }
+
+        // the bytes sent again must be the ones already inspected
+        let behind = behind as usize;
+        let repeated = behind.min(payload.len());
+        if repeated > 0 {
+            if behind > self.forwarded.len() {
+                return TcpVerdict::Drop(DropReason::TcpStream(TcpError::RetransmissionMismatch));
+            }
+            let start = self.forwarded.len() - behind;
+            let matches = self
+                .forwarded
+                .range(start..start + repeated)
+                .eq(payload[..repeated].iter());
+            if !matches {
+                return TcpVerdict::Reset(DropReason::TcpStream(TcpError::RetransmissionMismatch));
+            }
+        }
+
+        let mut sentences = Vec::new();
+        let new_bytes = &payload[repeated..];
+        let mut line = self.line.clone();
+        let mut checked = self.checked;
+        for byte in new_bytes {
+            line.push(*byte);
+            let terminated = *byte == b'\n';
+            if !terminated && line.len() > max_line_length {
+                return TcpVerdict::Reset(DropReason::TcpStream(TcpError::LineTooLong));
+            }
+            if !matches!(*byte, b'\n' | b',' | b'*') {
+                continue;
+            }
+
+            let check = check_line(&line, &mut checked, terminated, sends, schemas);
+            match check {
+                Ok(checked) => sentences.extend(checked),
+                Err(reason) => return TcpVerdict::Reset(reason),
+            }
+            if terminated {
+                line.clear();
+                checked = 0;
+            }
+        }
+        if fin {
+            match check_line(&line, &mut checked, true, sends, schemas) {
+                Ok(checked) => sentences.extend(checked),
+                Err(reason) => return TcpVerdict::Reset(reason),
+            }
+        }
+
+        // only a forwarded segment moves the stream forward
+        self.line = line;
+        self.checked = checked;
+        self.next_seq = self.next_seq.wrapping_add(new_bytes.len() as u32);
+        self.forwarded.extend(new_bytes);
+        let excess = self.forwarded.len().saturating_sub(RETRANSMISSION_WINDOW);
+        self.forwarded.drain(..excess);
+
+        TcpVerdict::Forward(sentences)
+    }
+}
+
+// judges the sentences of a line not judged yet, those whose address field is complete or every one
+// of them when the line is over, and returns the nmea ones
+fn check_line(
+    line: &[u8],
+    checked: &mut usize,
+    terminated: bool,
+    sends: &[String],
+    schemas: &SentenceSchemas,
+) -> Result<Vec<String>, DropReason> {
+    let mut sentences = Vec::new();
+    let line = String::from_utf8_lossy(line);
+    let pieces = split_sentences(&line);
+    let last = pieces.len().saturating_sub(1);
+
+    for (index, sentence) in pieces.into_iter().enumerate().skip(*checked) {
+        // only the last sentence of the line may still grow
+        let (_, body) = nmea::split_tag_block(sentence);
+        let address_complete = body.starts_with(['$', '!']) && body.contains([',', '*']);
+        if index == last && !terminated && !address_complete {
+            break;
+        }
+
+        let (is_allowed, is_nmea, prefix) = apply_policy(sends, schemas, sentence.to_string());
+        if !is_allowed {
+            return Err(DropReason::SentenceNotAllowed(prefix));
+        }
+        if is_nmea {
+            sentences.push(prefix);
+        }
+        *checked = index + 1;
+    }
+    Ok(sentences)
+}
+
+fn segment(frame: &[u8]) -> Option<(FlowKey, TcpPacket<'_>)> {
+    let layout = PacketParser::new(frame).layout()?;
+    let tcp_packet = TcpPacket::new(&frame[layout.tcp?])?;
+    let key = FlowKey {
+        source: PacketParser::new(frame).source_ip()?,
+        source_port: tcp_packet.get_source(),
+        destination: destination_ip(frame, layout.network_offset, layout.family?)?,
+        destination_port: tcp_packet.get_destination(),
+    };
+    Some((key, tcp_packet))
+}
+
+fn destination_ip(frame: &[u8], network_offset: usize, family: AddressFamily) -> Option<IpAddr> {
+    let network = &frame[network_offset..];
+    match family {
+        AddressFamily::Ipv4 => pnet::packet::ipv4::Ipv4Packet::new(network)
+            .map(|packet| packet.get_destination().into()),
+        AddressFamily::Ipv6 => pnet::packet::ipv6::Ipv6Packet::new(network)
+            .map(|packet| packet.get_destination().into()),
+    }
+}
+
+// the resets tearing down the connection of a segment, to its sender and to its receiver
+pub fn tcp_resets(frame: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    let layout = PacketParser::new(frame).layout()?;
+    let tcp = layout.tcp?;
+    let tcp_packet = TcpPacket::new(&frame[tcp.clone()])?;
+    let flags = tcp_packet.get_flags();
+
+    // the receiver expects the segment sequence, the sender what it acknowledged last
+    let to_receiver = forge_reset(
+        &frame[..tcp.start],
+        layout.network_offset,
+        layout.family?,
+        false,
+        (tcp_packet.get_source(), tcp_packet.get_destination()),
+        tcp_packet.get_sequence(),
+        None,
+    )?;
+    let to_sender = match flags & TcpFlags::ACK != 0 {
+        true => forge_reset(
+            &frame[..tcp.start],
+            layout.network_offset,
+            layout.family?,
+            true,
+            (tcp_packet.get_destination(), tcp_packet.get_source()),
+            tcp_packet.get_acknowledgement(),
+            None,
+        )?,
+        false => forge_reset(
+            &frame[..tcp.start],
+            layout.network_offset,
+            layout.family?,
+            true,
+            (tcp_packet.get_destination(), tcp_packet.get_source()),
+            0,
+            Some(
+                tcp_packet
+                    .get_sequence()
+                    .wrapping_add(tcp_packet.payload().len() as u32),
+            ),
+        )?,
+    };
+
+    Some((to_sender, to_receiver))
+}
+
+fn forge_reset(
+    headers: &[u8],
+    network_offset: usize,
+    family: AddressFamily,
+    reverse: bool,
+    (source_port, destination_port): (u16, u16),
+    seq: u32,
+    ack: Option<u32>,
+) -> Option<Vec<u8>> {
+    let mut frame = headers.to_vec();
+    frame.resize(headers.len() + TCP_HEADER_LEN, 0);
+
+    if reverse {
+        let mut ethernet_packet = MutableEthernetPacket::new(&mut frame)?;
+        let source = ethernet_packet.get_source();
+        ethernet_packet.set_source(ethernet_packet.get_destination());
+        ethernet_packet.set_destination(source);
+    }
+
+    let (network, transport) = frame.split_at_mut(headers.len());
+    let network = &mut network[network_offset..];
+    let mut tcp_packet = MutableTcpPacket::new(transport)?;
+    tcp_packet.set_source(source_port);
+    tcp_packet.set_destination(destination_port);
+    tcp_packet.set_sequence(seq);
+    tcp_packet.set_acknowledgement(ack.unwrap_or(0));
+    tcp_packet.set_data_offset((TCP_HEADER_LEN / 4) as u8);
+    tcp_packet.set_flags(match ack {
+        Some(_) => TcpFlags::RST | TcpFlags::ACK,
+        None => TcpFlags::RST,
+    });
+    tcp_packet.set_window(0);
+
+    match family {
+        AddressFamily::Ipv4 => {
+            let header_length = network.len();
+            let mut ipv4_packet = MutableIpv4Packet::new(network)?;
+            if reverse {
+                let source = ipv4_packet.get_source();
+                ipv4_packet.set_source(ipv4_packet.get_destination());
+                ipv4_packet.set_destination(source);
+            }
+            ipv4_packet.set_total_length((header_length + TCP_HEADER_LEN) as u16);
+            ipv4_packet.set_checksum(0);
+            let checksum = pnet::packet::ipv4::checksum(&ipv4_packet.to_immutable());
+            ipv4_packet.set_checksum(checksum);
+
+            let checksum = pnet::packet::tcp::ipv4_checksum(
+                &tcp_packet.to_immutable(),
+                &ipv4_packet.get_source(),
+                &ipv4_packet.get_destination(),
+            );
+            tcp_packet.set_checksum(checksum);
+        }
+        AddressFamily::Ipv6 => {
+            let extension_length = network.len() - 40;
+            let mut ipv6_packet = MutableIpv6Packet::new(network)?;
+            if reverse {
+                let source = ipv6_packet.get_source();
+                ipv6_packet.set_source(ipv6_packet.get_destination());
+                ipv6_packet.set_destination(source);
+            }
+            ipv6_packet.set_payload_length((extension_length + TCP_HEADER_LEN) as u16);
+
+            let checksum = pnet::packet::tcp::ipv6_checksum(
+                &tcp_packet.to_immutable(),
+                &ipv6_packet.get_source(),
+                &ipv6_packet.get_destination(),
+            );
+            tcp_packet.set_checksum(checksum);
+        }
+    }
+
+    Some(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::ethernet::EtherTypes;
+    use pnet::packet::ip::IpNextHeaderProtocols;
+    use std::net::Ipv4Addr;
+
+    fn segment_frame(seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0u8; 14 + 20 + TCP_HEADER_LEN + payload.len()];
+        MutableEthernetPacket::new(&mut frame)
+            .unwrap()
+            .set_ethertype(EtherTypes::Ipv4);
+
+        let mut ipv4_packet = MutableIpv4Packet::new(&mut frame[14..]).unwrap();
+        ipv4_packet.set_version(4);
+        ipv4_packet.set_header_length(5);
+        ipv4_packet.set_total_length((20 + TCP_HEADER_LEN + payload.len()) as u16);
+        ipv4_packet.set_ttl(64);
+        ipv4_packet.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
+        ipv4_packet.set_source(Ipv4Addr::new(10, 42, 0, 10));
+        ipv4_packet.set_destination(Ipv4Addr::new(10, 42, 0, 20));
+
+        let mut tcp_packet = MutableTcpPacket::new(&mut frame[34..]).unwrap();
+        tcp_packet.set_source(40000);
+        tcp_packet.set_destination(NMEA_TCP_PORT);
+        tcp_packet.set_sequence(seq);
+        tcp_packet.set_data_offset((TCP_HEADER_LEN / 4) as u8);
+        tcp_packet.set_flags(flags);
+        tcp_packet.set_payload(payload);
+        frame
+    }
+
+    fn sends() -> Vec<String> {
+        vec![String::from("$GPHDT")]
+    }
+
+    // an inspector following a connection whose first data byte has sequence number seq
+    fn connected(seq: u32) -> (TcpInspector, Instant) {
+        let mut inspector = TcpInspector::new();
+        let now = Instant::now();
+        let syn = segment_frame(seq.wrapping_sub(1), TcpFlags::SYN, b"");
+        assert_eq!(
+            inspector.inspect(&syn, &sends(), SentenceSchemas::builtin(), now),
+            TcpVerdict::Forward(Vec::new())
+        );
+        (inspector, now)
+    }
+
+    #[test]
+    fn sentences_are_judged_once_their_address_is_complete() {
+        let (mut inspector, now) = connected(1000);
+
+        let verdict = inspector.inspect(
+            &segment_frame(1000, TcpFlags::ACK, b"$GPHDT,"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(verdict, TcpVerdict::Forward(vec![String::from("$GPHDT")]));
+        let verdict = inspector.inspect(
+            &segment_frame(1007, TcpFlags::ACK, b"1.0,T*00\r\n"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(verdict, TcpVerdict::Forward(Vec::new()));
+
+        // the bytes of a forbidden sentence never go through, even without its line terminator
+        let verdict = inspector.inspect(
+            &segment_frame(1017, TcpFlags::ACK, b"$GPGGA,1"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(
+            verdict,
+            TcpVerdict::Reset(DropReason::SentenceNotAllowed(String::from("$GPGGA")))
+        );
+    }
+
+    #[test]
+    fn partial_address_waits_for_the_rest() {
+        let (mut inspector, now) = connected(1);
+
+        let verdict = inspector.inspect(
+            &segment_frame(1, TcpFlags::ACK, b"$GPG"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(verdict, TcpVerdict::Forward(Vec::new()));
+        let verdict = inspector.inspect(
+            &segment_frame(5, TcpFlags::ACK, b"GA,"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(
+            verdict,
+            TcpVerdict::Reset(DropReason::SentenceNotAllowed(String::from("$GPGGA")))
+        );
+    }
+
+    #[test]
+    fn sentence_hidden_after_another_on_the_same_line_is_judged() {
+        let (mut inspector, now) = connected(1);
+
+        let verdict = inspector.inspect(
+            &segment_frame(1, TcpFlags::ACK, b"$GPHDT,1.0,T*00$GPGGA,1*00\r\n"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(
+            verdict,
+            TcpVerdict::Reset(DropReason::SentenceNotAllowed(String::from("$GPGGA")))
+        );
+    }
+
+    #[test]
+    fn unterminated_line_is_judged_on_fin() {
+        let (mut inspector, now) = connected(1);
+
+        let verdict = inspector.inspect(
+            &segment_frame(1, TcpFlags::ACK | TcpFlags::FIN, b"$GPGGA"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(
+            verdict,
+            TcpVerdict::Reset(DropReason::SentenceNotAllowed(String::from("$GPGGA")))
+        );
+    }
+
+    #[test]
+    fn flows_are_only_created_on_syn() {
+        let mut inspector = TcpInspector::new();
+        let now = Instant::now();
+
+        let verdict = inspector.inspect(
+            &segment_frame(1, TcpFlags::ACK, b"$GPHDT,1*00\r\n"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(
+            verdict,
+            TcpVerdict::Reset(DropReason::TcpStream(TcpError::UntrackedFlow))
+        );
+        // the last ack of a closed connection carries nothing to inspect
+        let verdict = inspector.inspect(
+            &segment_frame(1, TcpFlags::ACK, b""),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(verdict, TcpVerdict::Forward(Vec::new()));
+        assert_eq!(inspector.flows(), 0);
+    }
+
+    #[test]
+    fn flow_limit_refuses_new_connections() {
+        let mut inspector = TcpInspector::new();
+        inspector.max_flows = 1;
+        let now = Instant::now();
+
+        inspector.inspect(
+            &segment_frame(0, TcpFlags::SYN, b""),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        let mut other = segment_frame(0, TcpFlags::SYN, b"");
+        MutableTcpPacket::new(&mut other[34..])
+            .unwrap()
+            .set_source(40001);
+        assert_eq!(
+            inspector.inspect(&other, &sends(), SentenceSchemas::builtin(), now),
+            TcpVerdict::Drop(DropReason::TcpStream(TcpError::FlowLimit))
+        );
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        let (mut inspector, now) = connected(u32::MAX - 3);
+
+        let verdict = inspector.inspect(
+            &segment_frame(u32::MAX - 3, TcpFlags::ACK, b"$GPHDT,"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(verdict, TcpVerdict::Forward(vec![String::from("$GPHDT")]));
+        let verdict = inspector.inspect(
+            &segment_frame(3, TcpFlags::ACK, b"1*00\r\n"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(verdict, TcpVerdict::Forward(Vec::new()));
+    }
+
+    #[test]
+    fn retransmissions_must_match_what_was_inspected() {
+        let (mut inspector, now) = connected(1);
+        inspector.inspect(
+            &segment_frame(1, TcpFlags::ACK, b"$GPHDT,1*00\r\n"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+
+        let verdict = inspector.inspect(
+            &segment_frame(1, TcpFlags::ACK, b"$GPHDT,1*00\r\n"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(verdict, TcpVerdict::Forward(Vec::new()));
+        let verdict = inspector.inspect(
+            &segment_frame(1, TcpFlags::ACK, b"$GPGGA,1*00\r\n"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(
+            verdict,
+            TcpVerdict::Reset(DropReason::TcpStream(TcpError::RetransmissionMismatch))
+        );
+    }
+
+    #[test]
+    fn segments_past_a_hole_are_dropped() {
+        let (mut inspector, now) = connected(1);
+
+        let verdict = inspector.inspect(
+            &segment_frame(100, TcpFlags::ACK, b"$GPHDT,1*00\r\n"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(
+            verdict,
+            TcpVerdict::Drop(DropReason::TcpStream(TcpError::OutOfOrder))
+        );
+    }
+
+    #[test]
+    fn long_lines_reset_the_connection() {
+        let (mut inspector, now) = connected(1);
+        inspector.max_line_length = 8;
+
+        let verdict = inspector.inspect(
+            &segment_frame(1, TcpFlags::ACK, b"$GPHDT,1.000"),
+            &sends(),
+            SentenceSchemas::builtin(),
+            now,
+        );
+        assert_eq!(
+            verdict,
+            TcpVerdict::Reset(DropReason::TcpStream(TcpError::LineTooLong))
+        );
+    }
+}
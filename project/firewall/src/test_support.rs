@@ -0,0 +1,26 @@
+use pnet::packet::ethernet::{EtherTypes, MutableEthernetPacket};
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv4::MutableIpv4Packet;
+use pnet::packet::udp::MutableUdpPacket;
+
+// an ipv4 datagram to the nmea port carrying payload, the addresses left empty
+pub(crate) fn udp_frame(payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0u8; 14 + 20 + 8 + payload.len()];
+    MutableEthernetPacket::new(&mut frame)
+        .unwrap()
+        .set_ethertype(EtherTypes::Ipv4);
+
+    let mut ipv4_packet = MutableIpv4Packet::new(&mut frame[14..]).unwrap();
+    ipv4_packet.set_version(4);
+    ipv4_packet.set_header_length(5);
+    ipv4_packet.set_total_length((20 + 8 + payload.len()) as u16);
+    ipv4_packet.set_ttl(64);
+    ipv4_packet.set_next_level_protocol(IpNextHeaderProtocols::Udp);
+
+    let mut udp_packet = MutableUdpPacket::new(&mut frame[34..]).unwrap();
+    udp_packet.set_source(10110);
+    udp_packet.set_destination(10110);
+    udp_packet.set_length((8 + payload.len()) as u16);
+    udp_packet.set_payload(payload);
+    frame
+}
@@ -0,0 +1,122 @@
+use crate::IgmpSnooping;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::time::Instant;
+
+// IEC 61162-450 transmission groups, one multicast address and port each
+const STANDARD_GROUPS: [&str; 20] = [
+    "MISC", "TGTD", "SATD", "NAVD", "VDRD", "RCOM", "TIME", "PROP", "USR1", "USR2", "USR3", "USR4",
+    "USR5", "USR6", "USR7", "USR8", "BAM1", "BAM2", "CAM1", "CAM2",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransmissionGroup {
+    pub name: String,
+    pub address: Ipv4Addr,
+    pub port: u16,
+}
+
+// the groups the ship knows, the standard ones and those added by the policy
+#[derive(Debug, Clone)]
+pub struct TransmissionGroups {
+    groups: Vec<TransmissionGroup>,
+}
+
+impl Default for TransmissionGroups {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransmissionGroups {
+    // MISC is 239.192.0.1 on port 60001, the others follow in order
+    pub fn new() -> Self {
+        let groups = STANDARD_GROUPS
+            .iter()
+            .zip(1u8..)
+            .map(|(name, index)| TransmissionGroup {
+                name: name.to_string(),
+                address: Ipv4Addr::new(239, 192, 0, index),
+                port: 60000 + index as u16,
+            })
+            .collect();
+        TransmissionGroups { groups }
+    }
+
+    // the standard groups and those of the policy, name and address:port
+    pub fn configure(custom: &[(String, String)]) -> Result<Self, String> {
+        let mut groups = Self::new();
+        for (name, endpoint) in custom {
+            groups.add(name, endpoint)?;
+        }
+        Ok(groups)
+    }
+
+    // a group from the policy, written as address:port
+    pub fn add(&mut self, name: &str, endpoint: &str) -> Result<(), String> {
+        let (address, port) = endpoint
+            .rsplit_once(':')
+            .ok_or_else(|| format!("group {} has no port in {}", name, endpoint))?;
+        let address = Ipv4Addr::from_str(address)
+            .ok()
+            .filter(Ipv4Addr::is_multicast)
+            .ok_or_else(|| format!("group {} is not on a multicast address", name))?;
+        let port = port
+            .parse()
+            .map_err(|_| format!("group {} has an invalid port {}", name, port))?;
+
+        if let Some(other) = self.groups.iter().find(|group| group.address == address) {
+            if other.name != name {
+                return Err(format!("group {} uses the address of {}", name, other.name));
+            }
+        }
+        self.groups.retain(|group| group.name != name);
+        self.groups.push(TransmissionGroup {
+            name: name.to_string(),
+            address,
+            port,
+        });
+        Ok(())
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&TransmissionGroup> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    pub fn by_address(&self, address: &Ipv4Addr) -> Option<&TransmissionGroup> {
+        self.groups.iter().find(|group| group.address == *address)
+    }
+
+    // the group an ipv4 frame is sent to
+    pub fn of_frame(&self, frame: &[u8]) -> Option<&TransmissionGroup> {
+        self.by_address(&crate::multicast_destination(frame)?)
+    }
+
+    // the ports a datagram sent to a multicast address goes to, the components subscribed to its group
+    // in the policy and those that joined it with igmp, the ingress port excluded
+    pub fn members(
+        &self,
+        address: &Ipv4Addr,
+        subscriptions: &[&[String]],
+        igmp: &IgmpSnooping,
+        ingress_port: usize,
+        now: Instant,
+    ) -> Vec<usize> {
+        let group = self.by_address(address);
+        let joined = igmp.members(address, now).unwrap_or_default();
+        subscriptions
+            .iter()
+            .enumerate()
+            .filter(|(port, subscribed)| {
+                *port != ingress_port
+                    && (group.is_some_and(|group| subscribed.contains(&group.name))
+                        || joined.contains(port))
+            })
+            .map(|(port, _)| port)
+            .collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TransmissionGroup> {
+        self.groups.iter()
+    }
+}
@@ -0,0 +1,437 @@
+use crate::{split_sentences, DropReason};
+use nmea::{FieldSchema, SentenceSchema, SentenceSchemas};
+use packet_parser::PacketParser;
+use std::fmt;
+use std::sync::Arc;
+
+// IEC 61162-1, start delimiter and line terminator included
+const MAX_SENTENCE_LENGTH: usize = 82;
+// reserved by IEC 61162-1, never part of a field
+const RESERVED_CHARACTERS: &str = "$!*\\~";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Violation {
+    TooLong,
+    // outside printable ascii, or reserved
+    InvalidCharacter,
+    MissingCrlf,
+    // missing, malformed or not matching the sentence
+    BadChecksum,
+    FieldCount,
+    FieldFormat,
+    // a number outside the bounds of its schema
+    FieldRange,
+}
+
+impl Violation {
+    pub const ALL: [Violation; 7] = [
+        Violation::TooLong,
+        Violation::InvalidCharacter,
+        Violation::MissingCrlf,
+        Violation::BadChecksum,
+        Violation::FieldCount,
+        Violation::FieldFormat,
+        Violation::FieldRange,
+    ];
+
+    // the key of the violation in the policy
+    pub fn name(&self) -> &'static str {
+        match self {
+            Violation::TooLong => "too_long",
+            Violation::InvalidCharacter => "invalid_character",
+            Violation::MissingCrlf => "missing_crlf",
+            Violation::BadChecksum => "bad_checksum",
+            Violation::FieldCount => "field_count",
+            Violation::FieldFormat => "field_format",
+            Violation::FieldRange => "field_range",
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Violation::TooLong => "sentence_too_long",
+            Violation::InvalidCharacter => "sentence_invalid_character",
+            Violation::MissingCrlf => "sentence_missing_crlf",
+            Violation::BadChecksum => "sentence_bad_checksum",
+            Violation::FieldCount => "sentence_field_count",
+            Violation::FieldFormat => "sentence_field_format",
+            Violation::FieldRange => "sentence_field_range",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Violation::ALL
+            .into_iter()
+            .find(|violation| violation.name() == name)
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::TooLong => write!(f, "sentence too long"),
+            Violation::InvalidCharacter => write!(f, "invalid character in sentence"),
+            Violation::MissingCrlf => write!(f, "sentence not terminated by crlf"),
+            Violation::BadChecksum => write!(f, "bad sentence checksum"),
+            Violation::FieldCount => write!(f, "wrong sentence field count"),
+            Violation::FieldFormat => write!(f, "malformed sentence field"),
+            Violation::FieldRange => write!(f, "sentence field out of range"),
+        }
+    }
+}
+
+// what the ship does with a sentence breaking a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationAction {
+    Drop,
+    // logged and counted, the sentence goes on
+    Alert,
+    Allow,
+}
+
+impl ViolationAction {
+    pub fn parse(action: &str) -> Option<Self> {
+        match action.to_lowercase().as_str() {
+            "drop" => Some(ViolationAction::Drop),
+            "alert" => Some(ViolationAction::Alert),
+            "allow" => Some(ViolationAction::Allow),
+            _ => None,
+        }
+    }
+}
+
+// checks the syntax of every sentence beyond what the nmea parser looks at
+#[derive(Clone)]
+pub struct SentenceValidator {
+    schemas: Arc<SentenceSchemas>,
+    actions: hashbrown::HashMap<Violation, ViolationAction>,
+    pub max_length: usize,
+    pub alerts: u64,
+}
+
+impl Default for SentenceValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SentenceValidator {
+    // every violation drops the sentence
+    pub fn new() -> Self {
+        SentenceValidator {
+            schemas: Arc::new(SentenceSchemas::default()),
+            actions: Violation::ALL
+                .into_iter()
+                .map(|violation| (violation, ViolationAction::Drop))
+                .collect(),
+            max_length: MAX_SENTENCE_LENGTH,
+            alerts: 0,
+        }
+    }
+
+    // a validator from the policy, actions are pairs of violation and action names
+    // and schemas those the sentences are parsed with
+    pub fn configure(
+        max_length: usize,
+        actions: &[(&str, &str)],
+        schemas: Arc<SentenceSchemas>,
+    ) -> Result<Self, String> {
+        let mut validator = SentenceValidator::new();
+        validator.max_length = max_length;
+        validator.schemas = schemas;
+        for (name, action) in actions {
+            let violation =
+                Violation::parse(name).ok_or_else(|| format!("unknown violation {}", name))?;
+            let action = ViolationAction::parse(action)
+                .ok_or_else(|| format!("unknown action {} for {}", action, name))?;
+            validator.set_action(violation, action);
+        }
+        Ok(validator)
+    }
+
+    // the schema of an address field, a whole address like PGRMZ comes before its formatter
+    pub fn schema(&self, address: &str) -> Option<&SentenceSchema> {
+        self.schemas.get(address)
+    }
+
+    pub fn set_action(&mut self, violation: Violation, action: ViolationAction) {
+        self.actions.insert(violation, action);
+    }
+
+    pub fn action(&self, violation: Violation) -> ViolationAction {
+        self.actions
+            .get(&violation)
+            .copied()
+            .unwrap_or(ViolationAction::Drop)
+    }
+
+    // the reason to drop the frame, when one of its sentences breaks a rule whose action is drop
+    pub fn inspect(&mut self, frame: &[u8]) -> Option<DropReason> {
+        let payload = PacketParser::new(frame).parse_traffic().ok()?;
+
+        // only what claims to be a sentence is held to the standard
+        for sentence in split_sentences(&payload)
+            .into_iter()
+            .filter(|sentence| nmea::split_tag_block(sentence).1.starts_with(['$', '!']))
+        {
+            for violation in self.violations(sentence) {
+                match self.action(violation) {
+                    ViolationAction::Drop => return Some(DropReason::InvalidSentence(violation)),
+                    ViolationAction::Alert => {
+                        self.alerts += 1;
+                        log::warn!(
+                            "| {}: {}",
+                            violation.to_string().to_uppercase(),
+                            sentence.trim_end().escape_debug()
+                        );
+                    }
+                    ViolationAction::Allow => {}
+                }
+            }
+        }
+
+        None
+    }
+
+    // every rule the sentence breaks
+    pub fn violations(&self, sentence: &str) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        // the tag block of IEC 61162-450 does not count towards the length of the sentence
+        let (tag_block, sentence) = nmea::split_tag_block(sentence);
+        if tag_block.is_some_and(|tag_block| !tag_block.valid_checksum) {
+            violations.push(Violation::BadChecksum);
+        }
+
+        if sentence.len() > self.max_length {
+            violations.push(Violation::TooLong);
+        }
+        let body = match sentence.strip_suffix("\r\n") {
+            Some(body) => body,
+            None => {
+                violations.push(Violation::MissingCrlf);
+                sentence.trim_end_matches(['\r', '\n'])
+            }
+        };
+
+        // the start delimiter, then the fields up to the checksum delimiter
+        let body = body.get(1..).unwrap_or_default();
+        let (fields, checksum) = match body.split_once('*') {
+            Some((fields, checksum)) => (fields, Some(checksum)),
+            None => (body, None),
+        };
+        if body
+            .chars()
+            .any(|character| !(' '..='~').contains(&character))
+            || fields
+                .chars()
+                .any(|character| RESERVED_CHARACTERS.contains(character))
+        {
+            violations.push(Violation::InvalidCharacter);
+        }
+
+        let expected = fields.bytes().fold(0u8, |checksum, byte| checksum ^ byte);
+        let valid_checksum = checksum
+            .filter(|checksum| checksum.len() == 2)
+            .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+            .is_some_and(|checksum| checksum == expected);
+        if !valid_checksum {
+            violations.push(Violation::BadChecksum);
+        }
+
+        let mut fields = fields.split(',');
+        let address = fields.next().unwrap_or_default();
+        if let Some(schema) = self.schema(address) {
+            let fields: Vec<&str> = fields.collect();
+            if !schema.fits(fields.len()) {
+                violations.push(Violation::FieldCount);
+            }
+            let fields: Vec<(&str, &FieldSchema)> = fields
+                .iter()
+                .zip(schema.layout(fields.len()))
+                .filter_map(|(field, (_, schema))| Some((*field, schema?)))
+                .collect();
+            if fields.iter().any(|(field, schema)| {
+                !schema.format.matches(field) || (schema.required && field.is_empty())
+            }) {
+                violations.push(Violation::FieldFormat);
+            }
+            if fields.iter().any(|(field, schema)| !schema.in_range(field)) {
+                violations.push(Violation::FieldRange);
+            }
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::udp_frame;
+
+    // the sentence with its checksum and line terminator
+    fn sentence(fields: &str) -> String {
+        let checksum = fields.bytes().fold(0u8, |checksum, byte| checksum ^ byte);
+        format!("${}*{:02X}\r\n", fields, checksum)
+    }
+
+    #[test]
+    fn a_sentence_following_its_schema_breaks_nothing() {
+        let validator = SentenceValidator::new();
+        assert!(validator.violations(&sentence("HEHDT,274.5,T")).is_empty());
+        assert!(validator
+            .violations(&sentence("GPGSV,3,1,11,03,03,111,00,04,15,270,00,0"))
+            .is_empty());
+    }
+
+    #[test]
+    fn field_count_follows_the_schema() {
+        let validator = SentenceValidator::new();
+        assert_eq!(
+            validator.violations(&sentence("HEHDT,274.5")),
+            [Violation::FieldCount]
+        );
+        assert_eq!(
+            validator.violations(&sentence("IIXDR,C,19.5,C,AIR,P")),
+            [Violation::FieldCount]
+        );
+    }
+
+    #[test]
+    fn field_format_and_range_follow_the_schema() {
+        let validator = SentenceValidator::new();
+        assert_eq!(
+            validator.violations(&sentence("HEHDT,north,T")),
+            [Violation::FieldFormat]
+        );
+        assert_eq!(
+            validator.violations(&sentence("HEHDT,274.5,M")),
+            [Violation::FieldFormat]
+        );
+        assert_eq!(
+            validator.violations(&sentence("HEHDT,361.0,T")),
+            [Violation::FieldRange]
+        );
+        // the elevation of the second satellite of the group
+        assert_eq!(
+            validator.violations(&sentence("GPGSV,3,1,11,03,03,111,00,04,95,270,00")),
+            [Violation::FieldRange]
+        );
+    }
+
+    #[test]
+    fn sentences_without_a_schema_only_get_the_syntax_checks() {
+        let validator = SentenceValidator::new();
+        assert!(validator.violations(&sentence("IIXYZ,anything")).is_empty());
+        assert_eq!(
+            validator.violations("$IIXYZ,anything*00\r\n"),
+            [Violation::BadChecksum]
+        );
+    }
+
+    #[test]
+    fn policy_schemas_are_checked_as_well() {
+        let mut schemas = SentenceSchemas::default();
+        let path = std::env::temp_dir().join(format!("validation-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[XDL]\nfields = [{ name = \"tank_level\", type = \"decimal\", min = 0, max = 100 }]\n",
+        )
+        .unwrap();
+        schemas.load_file(&path.to_string_lossy()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let validator = SentenceValidator::configure(82, &[], Arc::new(schemas)).unwrap();
+        assert!(validator.violations(&sentence("IIXDL,57.5")).is_empty());
+        assert_eq!(
+            validator.violations(&sentence("IIXDL,157.5")),
+            [Violation::FieldRange]
+        );
+    }
+
+    #[test]
+    fn padding_breaks_the_length_terminator_and_character_rules() {
+        // what test/sender puts on the wire
+        let mut payload = b"$IIHDT,33,T*44".to_vec();
+        payload.resize(5800, 0);
+        let violations = SentenceValidator::new().violations(&String::from_utf8(payload).unwrap());
+        for violation in [
+            Violation::TooLong,
+            Violation::MissingCrlf,
+            Violation::InvalidCharacter,
+        ] {
+            assert!(violations.contains(&violation), "{:?}", violation);
+        }
+    }
+
+    #[test]
+    fn the_length_limit_is_configurable() {
+        let long = sentence(&format!("IIXYZ,{}", "A".repeat(80)));
+        assert_eq!(
+            SentenceValidator::new().violations(&long),
+            [Violation::TooLong]
+        );
+
+        let validator =
+            SentenceValidator::configure(100, &[], Arc::new(SentenceSchemas::default())).unwrap();
+        assert!(validator.violations(&long).is_empty());
+    }
+
+    #[test]
+    fn reserved_characters_are_invalid_in_fields() {
+        assert_eq!(
+            SentenceValidator::new().violations(&sentence("IIXYZ,a~b")),
+            [Violation::InvalidCharacter]
+        );
+    }
+
+    #[test]
+    fn non_utf8_payloads_are_still_checked() {
+        let mut payload = sentence("HEHDT,274.5,T").into_bytes();
+        payload[8] = 0xff;
+        assert_eq!(
+            SentenceValidator::new().inspect(&udp_frame(&payload)),
+            Some(DropReason::InvalidSentence(Violation::InvalidCharacter))
+        );
+    }
+
+    #[test]
+    fn only_what_claims_to_be_a_sentence_is_checked() {
+        let mut validator = SentenceValidator::new();
+        assert_eq!(validator.inspect(&udp_frame(b"hello\x00")), None);
+        assert_eq!(validator.inspect(&udp_frame(&[0xff, 0xfe, 0x00])), None);
+    }
+
+    #[test]
+    fn alerts_are_counted_and_let_the_sentence_go() {
+        let frame = udp_frame(b"$HEHDT,274.5,T*2B");
+        let schemas = Arc::new(SentenceSchemas::default());
+
+        let mut validator = SentenceValidator::new();
+        assert_eq!(
+            validator.inspect(&frame),
+            Some(DropReason::InvalidSentence(Violation::MissingCrlf))
+        );
+
+        let mut validator =
+            SentenceValidator::configure(82, &[("missing_crlf", "alert")], schemas.clone())
+                .unwrap();
+        assert_eq!(validator.inspect(&frame), None);
+        assert_eq!(validator.alerts, 1);
+
+        let mut validator =
+            SentenceValidator::configure(82, &[("missing_crlf", "allow")], schemas).unwrap();
+        assert_eq!(validator.inspect(&frame), None);
+        assert_eq!(validator.alerts, 0);
+    }
+
+    #[test]
+    fn unknown_violations_and_actions_are_rejected() {
+        let schemas = Arc::new(SentenceSchemas::default());
+        assert!(
+            SentenceValidator::configure(82, &[("too_short", "drop")], schemas.clone()).is_err()
+        );
+        assert!(SentenceValidator::configure(82, &[("too_long", "ignore")], schemas).is_err());
+    }
+}
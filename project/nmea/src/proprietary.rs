@@ -0,0 +1,163 @@
+use hashbrown::HashMap;
+
+// manufacturer codes of the NMEA registry commonly heard on a bridge
+const MANUFACTURERS: [(&str, &str); 11] = [
+    ("ASH", "Ashtech"),
+    ("FEC", "Furuno"),
+    ("GRM", "Garmin"),
+    ("MTK", "MediaTek"),
+    ("MVX", "Magnavox"),
+    ("QTM", "Quectel"),
+    ("RWI", "Rockwell"),
+    ("SRF", "SiRF"),
+    ("SXN", "Kongsberg Seatex"),
+    ("TNL", "Trimble"),
+    ("UBX", "u-blox"),
+];
+
+// the field names of the proprietary sentences known well enough to be decoded
+const SENTENCES: [(&str, &str, &[&str]); 6] = [
+    (
+        "GRM",
+        "Z",
+        &["altitude", "altitude_units", "position_fix_dimension"],
+    ),
+    (
+        "GRM",
+        "E",
+        &[
+            "horizontal_error",
+            "horizontal_error_units",
+            "vertical_error",
+            "vertical_error_units",
+            "spherical_error",
+            "spherical_error_units",
+        ],
+    ),
+    ("GRM", "M", &["map_datum"]),
+    (
+        "ASH",
+        "R",
+        &[
+            "utc_time",
+            "heading",
+            "true_heading",
+            "roll",
+            "pitch",
+            "heave",
+            "roll_accuracy",
+            "pitch_accuracy",
+            "heading_accuracy",
+            "aiding_status",
+            "imu_status",
+        ],
+    ),
+    // the layout of the other fields depends on the message id
+    ("SXN", "", &["message_id"]),
+    ("UBX", "", &["message_id"]),
+];
+
+// a sentence whose address field is P, a three letter manufacturer code and its own sentence id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proprietary {
+    pub manufacturer: String,
+    // what follows the manufacturer code in the address field, empty when the id is the first field
+    pub sentence: String,
+    pub fields: Vec<String>,
+}
+
+impl Proprietary {
+    pub fn parse(sentence: &str) -> Option<Self> {
+        let body = sentence.trim_end().strip_prefix("$P")?;
+        let body = body.split_once('*').map_or(body, |(fields, _)| fields);
+        let mut fields = body.split(',');
+        let address = fields.next()?;
+        if address.len() < 3 || !address.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+
+        Some(Proprietary {
+            manufacturer: address[..3].to_string(),
+            sentence: address[3..].to_string(),
+            fields: fields.map(|field| field.trim().to_string()).collect(),
+        })
+    }
+
+    // the name of the manufacturer when its code is in the registry
+    pub fn manufacturer_name(&self) -> Option<&'static str> {
+        manufacturer_name(&self.manufacturer)
+    }
+
+    // the fields named after the registry, or numbered from 1 for unknown sentences
+    pub fn decode(&self) -> HashMap<String, String> {
+        let names = SENTENCES
+            .iter()
+            .find(|(manufacturer, sentence, _)| {
+                *manufacturer == self.manufacturer && *sentence == self.sentence
+            })
+            .map(|(_, _, names)| *names)
+            .unwrap_or_default();
+
+        self.fields
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let name = names
+                    .get(index)
+                    .map_or_else(|| format!("field_{}", index + 1), |name| name.to_string());
+                (name, value.clone())
+            })
+            .collect()
+    }
+}
+
+pub fn manufacturer_name(code: &str) -> Option<&'static str> {
+    MANUFACTURERS
+        .iter()
+        .find(|(known, _)| *known == code)
+        .map(|(_, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_address_splits_into_manufacturer_and_sentence() {
+        let proprietary = Proprietary::parse("$PGRMZ,246,f,3*1B\r\n").unwrap();
+        assert_eq!(proprietary.manufacturer, "GRM");
+        assert_eq!(proprietary.sentence, "Z");
+        assert_eq!(proprietary.fields, ["246", "f", "3"]);
+        assert_eq!(proprietary.manufacturer_name(), Some("Garmin"));
+
+        // the sentence id of some manufacturers is the first field
+        let proprietary = Proprietary::parse("$PUBX,00,081350.00*00").unwrap();
+        assert_eq!(proprietary.manufacturer, "UBX");
+        assert_eq!(proprietary.sentence, "");
+    }
+
+    #[test]
+    fn only_proprietary_addresses_are_parsed() {
+        assert_eq!(Proprietary::parse("$GPGGA,123519*00"), None);
+        assert_eq!(Proprietary::parse("$PGR,1*00"), None);
+        assert_eq!(Proprietary::parse("$PGR-Z,1*00"), None);
+    }
+
+    #[test]
+    fn known_sentences_are_decoded_by_name() {
+        let fields = Proprietary::parse("$PGRMZ,246,f,3*1B").unwrap().decode();
+        assert_eq!(fields["altitude"], "246");
+        assert_eq!(fields["altitude_units"], "f");
+        assert_eq!(fields["position_fix_dimension"], "3");
+
+        let fields = Proprietary::parse("$PXYZA,1,2*00").unwrap().decode();
+        assert_eq!(fields["field_1"], "1");
+        assert_eq!(fields["field_2"], "2");
+    }
+
+    #[test]
+    fn unknown_manufacturers_have_no_name() {
+        assert_eq!(manufacturer_name("SXN"), Some("Kongsberg Seatex"));
+        assert_eq!(manufacturer_name("XYZ"), None);
+    }
+}
@@ -0,0 +1,606 @@
+use crate::split_tag_block;
+use hashbrown::HashMap;
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+const BUILTIN_SCHEMAS: &str = include_str!("sentences.toml");
+
+// the shape of a field, an empty field is always valid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldFormat {
+    Any,
+    // optional sign, digits
+    Integer,
+    // optional sign, digits, optional fraction
+    Decimal,
+    // hhmmss with optional fraction
+    Time,
+    // ddmmyy
+    Date,
+    // ddmm.mm
+    Latitude,
+    // dddmm.mm
+    Longitude,
+    // a single character out of these
+    OneOf(String),
+}
+
+impl FieldFormat {
+    fn one_of(characters: &str) -> Self {
+        FieldFormat::OneOf(String::from(characters))
+    }
+
+    pub fn matches(&self, field: &str) -> bool {
+        if field.is_empty() {
+            return true;
+        }
+        match self {
+            FieldFormat::Any => true,
+            FieldFormat::Integer => is_digits(field.strip_prefix(['-', '+']).unwrap_or(field)),
+            FieldFormat::Decimal => is_decimal(field.strip_prefix(['-', '+']).unwrap_or(field)),
+            FieldFormat::Time => fixed_point(field, 6, |digits| {
+                &digits[0..2] < "24" && &digits[2..4] < "60" && &digits[4..6] <= "60"
+            }),
+            FieldFormat::Date => {
+                field.len() == 6
+                    && is_digits(field)
+                    && ("01"..="31").contains(&&field[0..2])
+                    && ("01"..="12").contains(&&field[2..4])
+            }
+            FieldFormat::Latitude => fixed_point(field, 4, |digits| {
+                &digits[0..2] <= "90" && &digits[2..4] < "60"
+            }),
+            FieldFormat::Longitude => fixed_point(field, 5, |digits| {
+                &digits[0..3] <= "180" && &digits[3..5] < "60"
+            }),
+            FieldFormat::OneOf(characters) => {
+                field.chars().count() == 1 && characters.contains(field)
+            }
+        }
+    }
+}
+
+fn is_digits(field: &str) -> bool {
+    !field.is_empty() && field.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+fn is_decimal(field: &str) -> bool {
+    match field.split_once('.') {
+        Some((integer, fraction)) => {
+            (integer.is_empty() || is_digits(integer))
+                && (fraction.is_empty() || is_digits(fraction))
+                && !(integer.is_empty() && fraction.is_empty())
+        }
+        None => is_digits(field),
+    }
+}
+
+// a fixed number of integer digits, checked by valid, and an optional fraction
+fn fixed_point(field: &str, integer_digits: usize, valid: impl Fn(&str) -> bool) -> bool {
+    let (integer, fraction) = field.split_once('.').unwrap_or((field, ""));
+    integer.len() == integer_digits
+        && is_digits(integer)
+        && (fraction.is_empty() || is_digits(fraction))
+        && valid(integer)
+}
+
+// a field of a sentence type as written in a schema file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "FieldDefinition")]
+pub struct FieldSchema {
+    pub name: String,
+    pub format: FieldFormat,
+    pub unit: Option<String>,
+    // an empty field breaks the format of the sentence
+    pub required: bool,
+    // bounds of integer and decimal fields
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl FieldSchema {
+    // an empty field or one that is not a number is never out of range
+    pub fn in_range(&self, field: &str) -> bool {
+        let Ok(value) = field.parse::<f64>() else {
+            return true;
+        };
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldDefinition {
+    name: String,
+    #[serde(rename = "type", default = "default_field_type")]
+    field_type: String,
+    // the characters of a one_of field
+    #[serde(default)]
+    values: String,
+    unit: Option<String>,
+    #[serde(default)]
+    required: bool,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+fn default_field_type() -> String {
+    String::from("any")
+}
+
+impl TryFrom<FieldDefinition> for FieldSchema {
+    type Error = String;
+
+    fn try_from(definition: FieldDefinition) -> Result<Self, Self::Error> {
+        let format = match definition.field_type.as_str() {
+            "any" => FieldFormat::Any,
+            "integer" => FieldFormat::Integer,
+            "decimal" => FieldFormat::Decimal,
+            "time" => FieldFormat::Time,
+            "date" => FieldFormat::Date,
+            "latitude" => FieldFormat::Latitude,
+            "longitude" => FieldFormat::Longitude,
+            "one_of" if !definition.values.is_empty() => FieldFormat::one_of(&definition.values),
+            "one_of" => return Err(format!("field {} has no values", definition.name)),
+            other => {
+                return Err(format!(
+                    "field {} has an unknown type {}",
+                    definition.name, other
+                ));
+            }
+        };
+
+        let ranged = definition.min.is_some() || definition.max.is_some();
+        if ranged && !matches!(format, FieldFormat::Integer | FieldFormat::Decimal) {
+            return Err(format!(
+                "field {} has a range but is not a number",
+                definition.name
+            ));
+        }
+        if let (Some(min), Some(max)) = (definition.min, definition.max)
+            && min > max
+        {
+            return Err(format!("field {} has min above max", definition.name));
+        }
+
+        Ok(FieldSchema {
+            name: definition.name,
+            format,
+            unit: definition.unit,
+            required: definition.required,
+            min: definition.min,
+            max: definition.max,
+        })
+    }
+}
+
+// the fields of a sentence type, the last ones may be left out by older talkers.
+// with repeat the last fields are a group coming as often as needed, like the satellites of GSV,
+// and the trailing fields may follow the last group
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "SentenceDefinition")]
+pub struct SentenceSchema {
+    pub fields: Vec<FieldSchema>,
+    pub min_fields: usize,
+    // fields in the repeated group, none when 0
+    pub repeat: usize,
+    pub trailing: Vec<FieldSchema>,
+}
+
+impl SentenceSchema {
+    // whether a sentence with this many fields after its address follows the schema
+    pub fn fits(&self, count: usize) -> bool {
+        let fixed = self.fields.len() - self.repeat;
+        match self.repeat {
+            0 => (self.min_fields..=self.fields.len()).contains(&count),
+            repeat => {
+                count >= self.min_fields
+                    && (count <= fixed || (count - fixed) % repeat <= self.trailing.len())
+            }
+        }
+    }
+
+    // the name and schema of each field of a sentence with this many fields, the fields of the groups
+    // numbered from 1 and those past the schema by their position
+    pub fn layout(&self, count: usize) -> Vec<(String, Option<&FieldSchema>)> {
+        let fixed = self.fields.len() - self.repeat;
+        let grouped = match self.repeat {
+            0 => 0,
+            repeat => count.saturating_sub(fixed) / repeat * repeat,
+        };
+
+        (0..count)
+            .map(|index| {
+                if index < fixed {
+                    let field = &self.fields[index];
+                    (field.name.clone(), Some(field))
+                } else if index < fixed + grouped {
+                    let field = &self.fields[fixed + (index - fixed) % self.repeat];
+                    let group = (index - fixed) / self.repeat + 1;
+                    (format!("{}_{}", field.name, group), Some(field))
+                } else {
+                    match self.trailing.get(index - fixed - grouped) {
+                        Some(field) => (field.name.clone(), Some(field)),
+                        None => (format!("field_{}", index + 1), None),
+                    }
+                }
+            })
+            .collect()
+    }
+
+    // the fields of a sentence keyed by their name in the schema
+    pub fn decode(&self, sentence: &str) -> HashMap<String, String> {
+        let fields = fields(sentence);
+        self.layout(fields.len())
+            .into_iter()
+            .zip(fields)
+            .map(|((name, _), field)| (name, field.to_string()))
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SentenceDefinition {
+    fields: Vec<FieldSchema>,
+    // every field when left out
+    min_fields: Option<usize>,
+    #[serde(default)]
+    repeat: usize,
+    #[serde(default)]
+    trailing: Vec<FieldSchema>,
+}
+
+impl TryFrom<SentenceDefinition> for SentenceSchema {
+    type Error = String;
+
+    fn try_from(definition: SentenceDefinition) -> Result<Self, Self::Error> {
+        let min_fields = definition.min_fields.unwrap_or(definition.fields.len());
+        if min_fields > definition.fields.len() {
+            return Err(format!(
+                "min_fields {} is above the {} fields",
+                min_fields,
+                definition.fields.len()
+            ));
+        }
+        if definition.repeat > definition.fields.len() {
+            return Err(format!(
+                "repeat {} is above the {} fields",
+                definition.repeat,
+                definition.fields.len()
+            ));
+        }
+        // as many trailing fields as a group has could be either
+        if !definition.trailing.is_empty() && definition.trailing.len() >= definition.repeat {
+            return Err(String::from(
+                "trailing fields need a repeated group with more fields",
+            ));
+        }
+        Ok(SentenceSchema {
+            fields: definition.fields,
+            min_fields,
+            repeat: definition.repeat,
+            trailing: definition.trailing,
+        })
+    }
+}
+
+// the schemas of a schema file, keyed by sentence formatter like MWV
+// or by the whole address field like PGRMZ
+pub fn parse_schemas(text: &str) -> Result<HashMap<String, SentenceSchema>, String> {
+    let schemas: std::collections::HashMap<String, SentenceSchema> =
+        toml::from_str(text).map_err(|e| e.to_string())?;
+    if let Some(key) = schemas.keys().find(|key| {
+        key.len() < 3
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+    }) {
+        return Err(format!("{} is not a sentence formatter or address", key));
+    }
+    Ok(schemas.into_iter().collect())
+}
+
+// the sentence types the parser recognises and the validator checks,
+// the builtin ones with those of the policy's schema files on top
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentenceSchemas {
+    schemas: HashMap<String, SentenceSchema>,
+}
+
+impl Default for SentenceSchemas {
+    fn default() -> Self {
+        SentenceSchemas::builtin().clone()
+    }
+}
+
+impl SentenceSchemas {
+    // parsed once, every sentence of every frame is looked up here
+    pub fn builtin() -> &'static SentenceSchemas {
+        static BUILTIN: OnceLock<SentenceSchemas> = OnceLock::new();
+        BUILTIN.get_or_init(|| SentenceSchemas {
+            schemas: parse_schemas(BUILTIN_SCHEMAS).expect("builtin sentence schemas are valid"),
+        })
+    }
+
+    // the builtin schemas and those of the files, a later file taking over the sentences of an earlier one
+    pub fn load(paths: &[String]) -> Result<Self, String> {
+        let mut schemas = SentenceSchemas::default();
+        for path in paths {
+            schemas.load_file(path)?;
+        }
+        Ok(schemas)
+    }
+
+    // adds the schemas of a schema file, replacing those of the same sentences,
+    // and returns how many it held
+    pub fn load_file(&mut self, path: &str) -> Result<usize, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let schemas = parse_schemas(&text).map_err(|e| format!("{}: {}", path, e))?;
+        let loaded = schemas.len();
+        self.schemas.extend(schemas);
+        Ok(loaded)
+    }
+
+    pub fn len(&self) -> usize {
+        self.schemas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty()
+    }
+
+    // the schema of an address field, a whole address like PGRMZ comes before its formatter
+    pub fn get(&self, address: &str) -> Option<&SentenceSchema> {
+        self.address(address).or_else(|| {
+            address
+                .get(address.len().saturating_sub(3)..)
+                .and_then(|formatter| self.schemas.get(formatter))
+        })
+    }
+
+    // the schema of this very address only, the formatter of a proprietary sentence means nothing
+    pub fn address(&self, address: &str) -> Option<&SentenceSchema> {
+        self.schemas.get(address)
+    }
+}
+
+// the address field of a sentence, without its start delimiter
+pub fn address(sentence: &str) -> Option<&str> {
+    let (_, sentence) = split_tag_block(sentence);
+    sentence.get(1..)?.split([',', '*']).next()
+}
+
+// the fields after the address, without checksum nor line terminator
+fn fields(sentence: &str) -> Vec<&str> {
+    let (_, sentence) = split_tag_block(sentence);
+    let body = sentence.trim_end().get(1..).unwrap_or_default();
+    let body = body.split_once('*').map_or(body, |(fields, _)| fields);
+    body.split(',').skip(1).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Nmea;
+
+    fn field(definition: &str) -> Result<FieldSchema, String> {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            field: FieldSchema,
+        }
+        toml::from_str::<Wrapper>(&format!("field = {}", definition))
+            .map(|wrapper| wrapper.field)
+            .map_err(|e| e.to_string())
+    }
+
+    fn names(schema: &SentenceSchema, count: usize) -> Vec<String> {
+        schema
+            .layout(count)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    #[test]
+    fn empty_fields_match_every_format() {
+        for format in [
+            FieldFormat::Any,
+            FieldFormat::Integer,
+            FieldFormat::Decimal,
+            FieldFormat::Time,
+            FieldFormat::Date,
+            FieldFormat::Latitude,
+            FieldFormat::Longitude,
+            FieldFormat::one_of("AV"),
+        ] {
+            assert!(format.matches(""), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn integer_and_decimal_fields() {
+        assert!(FieldFormat::Integer.matches("42"));
+        assert!(FieldFormat::Integer.matches("-7"));
+        assert!(!FieldFormat::Integer.matches("4.2"));
+        assert!(!FieldFormat::Integer.matches("-"));
+
+        assert!(FieldFormat::Decimal.matches("274.5"));
+        assert!(FieldFormat::Decimal.matches("+.5"));
+        assert!(FieldFormat::Decimal.matches("12."));
+        assert!(!FieldFormat::Decimal.matches("."));
+        assert!(!FieldFormat::Decimal.matches("1.2.3"));
+        assert!(!FieldFormat::Decimal.matches("1e3"));
+    }
+
+    #[test]
+    fn time_and_date_fields() {
+        assert!(FieldFormat::Time.matches("235960"));
+        assert!(FieldFormat::Time.matches("120000.25"));
+        assert!(!FieldFormat::Time.matches("240000"));
+        assert!(!FieldFormat::Time.matches("126000"));
+        assert!(!FieldFormat::Time.matches("1200"));
+
+        assert!(FieldFormat::Date.matches("311224"));
+        assert!(!FieldFormat::Date.matches("001224"));
+        assert!(!FieldFormat::Date.matches("011324"));
+        assert!(!FieldFormat::Date.matches("1224"));
+    }
+
+    #[test]
+    fn latitude_and_longitude_fields() {
+        assert!(FieldFormat::Latitude.matches("4807.038"));
+        assert!(FieldFormat::Latitude.matches("9000"));
+        assert!(!FieldFormat::Latitude.matches("9100.0"));
+        assert!(!FieldFormat::Latitude.matches("4860.0"));
+        assert!(!FieldFormat::Latitude.matches("01131.000"));
+
+        assert!(FieldFormat::Longitude.matches("01131.000"));
+        assert!(FieldFormat::Longitude.matches("18000"));
+        assert!(!FieldFormat::Longitude.matches("18100.0"));
+        assert!(!FieldFormat::Longitude.matches("4807.038"));
+    }
+
+    #[test]
+    fn one_of_fields() {
+        let format = FieldFormat::one_of("AV");
+        assert!(format.matches("A"));
+        assert!(!format.matches("X"));
+        assert!(!format.matches("AV"));
+        assert!(FieldFormat::Any.matches("anything at all"));
+    }
+
+    #[test]
+    fn ranges_bound_numbers_only() {
+        let schema =
+            field(r#"{ name = "heading", type = "decimal", min = 0, max = 360 }"#).unwrap();
+        assert!(schema.in_range("0"));
+        assert!(schema.in_range("360"));
+        assert!(!schema.in_range("360.1"));
+        assert!(!schema.in_range("-1"));
+        assert!(schema.in_range(""));
+        assert!(schema.in_range("north"));
+    }
+
+    #[test]
+    fn field_definitions_are_checked() {
+        assert!(field(r#"{ name = "a", type = "one_of" }"#).is_err());
+        assert!(field(r#"{ name = "a", type = "float" }"#).is_err());
+        assert!(field(r#"{ name = "a", type = "any", min = 1 }"#).is_err());
+        assert!(field(r#"{ name = "a", type = "integer", min = 2, max = 1 }"#).is_err());
+        assert!(field(r#"{ name = "a", colour = "red" }"#).is_err());
+        assert_eq!(field(r#"{ name = "a" }"#).unwrap().format, FieldFormat::Any);
+    }
+
+    #[test]
+    fn sentence_definitions_are_checked() {
+        let one = r#"fields = [{ name = "a" }]"#;
+        assert!(parse_schemas(&format!("[XYZ]\n{}\nmin_fields = 2", one)).is_err());
+        assert!(parse_schemas(&format!("[XYZ]\n{}\nrepeat = 2", one)).is_err());
+        assert!(
+            parse_schemas(&format!(
+                "[XYZ]\n{}\nrepeat = 1\ntrailing = [{{ name = \"b\" }}]",
+                one
+            ))
+            .is_err()
+        );
+        assert!(parse_schemas(&format!("[xy]\n{}", one)).is_err());
+        assert!(parse_schemas(&format!("[PXYZA]\n{}", one)).is_ok());
+    }
+
+    #[test]
+    fn builtin_schemas_parse() {
+        assert!(SentenceSchemas::builtin().len() > 20);
+        assert!(SentenceSchemas::builtin().get("GPGSV").is_some());
+        assert!(SentenceSchemas::builtin().get("GPXYZ").is_none());
+    }
+
+    #[test]
+    fn fixed_sentences_fit_their_minimum_to_all_fields() {
+        let gsa = SentenceSchemas::builtin().get("GPGSA").unwrap();
+        assert!(!gsa.fits(16));
+        assert!(gsa.fits(17));
+        assert!(gsa.fits(18));
+        assert!(!gsa.fits(19));
+    }
+
+    #[test]
+    fn repeated_groups_and_trailing_fields() {
+        let xdr = SentenceSchemas::builtin().get("IIXDR").unwrap();
+        assert!(xdr.fits(4));
+        assert!(xdr.fits(8));
+        assert!(!xdr.fits(6));
+        assert_eq!(
+            names(xdr, 8)[4..],
+            [
+                "transducer_type_2",
+                "measurement_2",
+                "units_2",
+                "transducer_name_2"
+            ]
+        );
+
+        let gsv = SentenceSchemas::builtin().get("GPGSV").unwrap();
+        assert!(!gsv.fits(2));
+        assert!(gsv.fits(3));
+        assert!(gsv.fits(7));
+        assert!(gsv.fits(8));
+        assert!(!gsv.fits(9));
+        assert!(gsv.fits(19));
+        assert!(gsv.fits(20));
+        let names = names(gsv, 12);
+        assert_eq!(names[3], "prn_1");
+        assert_eq!(names[9], "azimuth_2");
+        assert_eq!(names[10], "snr_2");
+        assert_eq!(names[11], "signal_id");
+        assert_eq!(gsv.layout(13)[12].0, "field_13");
+    }
+
+    #[test]
+    fn decode_names_the_fields() {
+        let mwd = SentenceSchemas::builtin().get("WIMWD").unwrap();
+        let fields = mwd.decode("$WIMWD,274.5,T,270.1,M,12.4,N,6.4,M*5C\r\n");
+        assert_eq!(fields["true_wind_direction"], "274.5");
+        assert_eq!(fields["meters_per_second"], "M");
+    }
+
+    #[test]
+    fn policy_schemas_extend_the_builtin_ones() {
+        let path = std::env::temp_dir().join(format!("schemas-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[XDL]\nfields = [{ name = \"tank_level\", type = \"decimal\" }]\n",
+        )
+        .unwrap();
+        let schemas = SentenceSchemas::load(&[path.to_string_lossy().into_owned()]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(schemas.len(), SentenceSchemas::builtin().len() + 1);
+        assert!(schemas.get("IIXDL").is_some());
+        assert!(SentenceSchemas::load(&[String::from("/nonexistent.toml")]).is_err());
+    }
+
+    #[test]
+    fn the_parser_recognises_sentences_by_schema() {
+        let mut nmea = Nmea::new();
+        assert!(
+            nmea.parse(String::from("$WIMWD,274.5,T,270.1,M,12.4,N,6.4,M*5C\r\n"))
+                .is_ok()
+        );
+        assert_eq!(nmea.str_sentence_type(), "MWD");
+        assert_eq!(nmea.sentence_fields["wind_speed_knots"], "12.4");
+
+        let sentence = String::from("$IIXDL,57.5*00\r\n");
+        assert!(Nmea::new().parse(sentence.clone()).is_err());
+
+        let mut schemas = SentenceSchemas::builtin().clone();
+        schemas.schemas.extend(
+            parse_schemas("[XDL]\nfields = [{ name = \"tank_level\", type = \"decimal\" }]")
+                .unwrap(),
+        );
+        let mut nmea = Nmea::new();
+        assert!(nmea.parse_with(sentence, &schemas).is_ok());
+        assert_eq!(nmea.str_sentence_type(), "XDL");
+        assert_eq!(nmea.sentence_fields["tank_level"], "57.5");
+    }
+}
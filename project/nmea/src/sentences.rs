@@ -0,0 +1,447 @@
+use crate::split_tag_block;
+use std::str::FromStr;
+
+// a struct per sentence with a fixed layout, every field optional since any of them may be null
+macro_rules! sentences {
+    ($($name:ident $formatter:literal { $($field:ident: $kind:ty),* $(,)? })*) => {
+        $(
+            #[derive(Debug, Clone, PartialEq, Default)]
+            pub struct $name {
+                $(pub $field: Option<$kind>,)*
+            }
+
+            impl $name {
+                pub const FORMATTER: &'static str = $formatter;
+
+                pub fn parse(sentence: &str) -> Option<Self> {
+                    let mut fields = sentence_fields(sentence, $formatter)?.into_iter();
+                    Some($name {
+                        $($field: field(fields.next()),)*
+                    })
+                }
+            }
+        )*
+    };
+}
+
+sentences! {
+    // Track made good and ground speed
+    Vtg "VTG" {
+        true_track: f64,
+        true_indicator: char,
+        magnetic_track: f64,
+        magnetic_indicator: char,
+        speed_knots: f64,
+        knots_indicator: char,
+        speed_kmh: f64,
+        kmh_indicator: char,
+        mode_indicator: char,
+    }
+    // Heading from a magnetic sensor, with its deviation and the magnetic variation
+    Hdg "HDG" {
+        magnetic_heading: f64,
+        deviation: f64,
+        deviation_direction: char,
+        variation: f64,
+        variation_direction: char,
+    }
+    // Heading, magnetic
+    Hdm "HDM" {
+        heading: f64,
+        magnetic_indicator: char,
+    }
+    // Rate of turn in degrees per minute, negative to port
+    Rot "ROT" {
+        rate_of_turn: f64,
+        status: char,
+    }
+    // Rudder sensor angle
+    Rsa "RSA" {
+        starboard_rudder_angle: f64,
+        starboard_status: char,
+        port_rudder_angle: f64,
+        port_status: char,
+    }
+    // Wind speed and angle, relative or true
+    Mwv "MWV" {
+        wind_angle: f64,
+        reference: char,
+        wind_speed: f64,
+        wind_speed_units: char,
+        status: char,
+    }
+    // Wind direction and speed
+    Mwd "MWD" {
+        true_wind_direction: f64,
+        true_indicator: char,
+        magnetic_wind_direction: f64,
+        magnetic_indicator: char,
+        wind_speed_knots: f64,
+        knots_indicator: char,
+        wind_speed_ms: f64,
+        ms_indicator: char,
+    }
+    // Water temperature
+    Mtw "MTW" {
+        temperature: f64,
+        units: char,
+    }
+    // Depth below transducer
+    Dbt "DBT" {
+        depth_feet: f64,
+        feet_indicator: char,
+        depth_meters: f64,
+        meters_indicator: char,
+        depth_fathoms: f64,
+        fathoms_indicator: char,
+    }
+    // GNSS fix data, one mode indicator per satellite system
+    Gns "GNS" {
+        utc_time: String,
+        latitude: String,
+        north_or_south: char,
+        longitude: String,
+        east_or_west: char,
+        mode_indicator: String,
+        number_of_satellites: u32,
+        horizontal_dilution_precision: f64,
+        antenna_altitude: f64,
+        geoidal_separation: f64,
+        differential_gps_data_age: f64,
+        differential_reference_station_id: String,
+        nav_status: char,
+    }
+    // GNSS pseudorange noise statistics
+    Gst "GST" {
+        utc_time: String,
+        rms_deviation: f64,
+        semi_major_error: f64,
+        semi_minor_error: f64,
+        semi_major_orientation: f64,
+        latitude_error: f64,
+        longitude_error: f64,
+        altitude_error: f64,
+    }
+    // Dual ground and water speed
+    Vbw "VBW" {
+        longitudinal_water_speed: f64,
+        transverse_water_speed: f64,
+        water_speed_status: char,
+        longitudinal_ground_speed: f64,
+        transverse_ground_speed: f64,
+        ground_speed_status: char,
+        stern_transverse_water_speed: f64,
+        stern_water_speed_status: char,
+        stern_transverse_ground_speed: f64,
+        stern_ground_speed_status: char,
+    }
+    // Distance travelled through the water and over the ground
+    Vlw "VLW" {
+        total_water_distance: f64,
+        total_water_distance_units: char,
+        water_distance: f64,
+        water_distance_units: char,
+        total_ground_distance: f64,
+        total_ground_distance_units: char,
+        ground_distance: f64,
+        ground_distance_units: char,
+    }
+    // Revolutions of a shaft or engine
+    Rpm "RPM" {
+        source: char,
+        number: u32,
+        speed: f64,
+        propeller_pitch: f64,
+        status: char,
+    }
+    // True heading and status
+    Ths "THS" {
+        heading: f64,
+        mode_indicator: char,
+    }
+    // Set alarm state
+    Alr "ALR" {
+        utc_time: String,
+        alarm_number: String,
+        condition: char,
+        acknowledged: char,
+        description: String,
+    }
+    // Alert sentence of the bridge alert management
+    Alf "ALF" {
+        total_sentences: u32,
+        sentence_number: u32,
+        sequential_message_id: u32,
+        utc_time: String,
+        alert_category: char,
+        alert_priority: char,
+        alert_state: char,
+        manufacturer_mnemonic: String,
+        alert_identifier: String,
+        alert_instance: u32,
+        revision_counter: u32,
+        escalation_counter: u32,
+        alert_text: String,
+    }
+    // Acknowledge alarm
+    Ack "ACK" {
+        alarm_number: String,
+    }
+    // Text transmission
+    Txt "TXT" {
+        total_sentences: u32,
+        sentence_number: u32,
+        text_identifier: String,
+        text: String,
+    }
+    // Own ship data
+    Osd "OSD" {
+        heading: f64,
+        heading_status: char,
+        course: f64,
+        course_reference: char,
+        speed: f64,
+        speed_reference: char,
+        set: f64,
+        drift: f64,
+        speed_units: char,
+    }
+    // Radar system data
+    Rsd "RSD" {
+        origin_1_range: f64,
+        origin_1_bearing: f64,
+        variable_range_marker_1: f64,
+        bearing_line_1: f64,
+        origin_2_range: f64,
+        origin_2_bearing: f64,
+        variable_range_marker_2: f64,
+        bearing_line_2: f64,
+        cursor_range: f64,
+        cursor_bearing: f64,
+        range_scale: f64,
+        range_units: char,
+        display_rotation: char,
+    }
+    // Datum reference
+    Dtm "DTM" {
+        local_datum: String,
+        local_datum_subdivision: String,
+        latitude_offset: f64,
+        north_or_south: char,
+        longitude_offset: f64,
+        east_or_west: char,
+        altitude_offset: f64,
+        reference_datum: String,
+    }
+    // Set and drift of the current
+    Vdr "VDR" {
+        true_direction: f64,
+        true_indicator: char,
+        magnetic_direction: f64,
+        magnetic_indicator: char,
+        speed: f64,
+        speed_units: char,
+    }
+}
+
+// GNSS DOP and active satellites
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Gsa {
+    pub mode: Option<char>,
+    pub fix_type: Option<u32>,
+    pub satellites: Vec<u32>,
+    pub position_dilution_precision: Option<f64>,
+    pub horizontal_dilution_precision: Option<f64>,
+    pub vertical_dilution_precision: Option<f64>,
+    pub system_id: Option<String>,
+}
+
+impl Gsa {
+    pub const FORMATTER: &'static str = "GSA";
+
+    pub fn parse(sentence: &str) -> Option<Self> {
+        let fields = sentence_fields(sentence, Self::FORMATTER)?;
+        let at = |index: usize| fields.get(index).copied();
+        Some(Gsa {
+            mode: field(at(0)),
+            fix_type: field(at(1)),
+            satellites: (2..14).filter_map(|index| field(at(index))).collect(),
+            position_dilution_precision: field(at(14)),
+            horizontal_dilution_precision: field(at(15)),
+            vertical_dilution_precision: field(at(16)),
+            system_id: field(at(17)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SatelliteInView {
+    pub prn: Option<u32>,
+    pub elevation: Option<f64>,
+    pub azimuth: Option<f64>,
+    pub snr: Option<f64>,
+}
+
+// GNSS satellites in view, up to four per sentence
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Gsv {
+    pub total_messages: Option<u32>,
+    pub message_number: Option<u32>,
+    pub satellites_in_view: Option<u32>,
+    pub satellites: Vec<SatelliteInView>,
+    pub signal_id: Option<String>,
+}
+
+impl Gsv {
+    pub const FORMATTER: &'static str = "GSV";
+
+    pub fn parse(sentence: &str) -> Option<Self> {
+        let fields = sentence_fields(sentence, Self::FORMATTER)?;
+        let at = |index: usize| fields.get(index).copied();
+        let satellites = fields.get(3..).unwrap_or_default().chunks(4);
+        Some(Gsv {
+            total_messages: field(at(0)),
+            message_number: field(at(1)),
+            satellites_in_view: field(at(2)),
+            // a field left over after the satellites is the signal id of NMEA 4.10
+            signal_id: satellites
+                .clone()
+                .find(|satellite| satellite.len() == 1)
+                .and_then(|satellite| field(satellite.first().copied())),
+            satellites: satellites
+                .filter(|satellite| satellite.len() == 4)
+                .map(|satellite| SatelliteInView {
+                    prn: field(satellite.first().copied()),
+                    elevation: field(satellite.get(1).copied()),
+                    azimuth: field(satellite.get(2).copied()),
+                    snr: field(satellite.get(3).copied()),
+                })
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Measurement {
+    pub transducer_type: Option<char>,
+    pub value: Option<f64>,
+    pub units: Option<char>,
+    pub name: Option<String>,
+}
+
+// Transducer measurements, any number of them
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Xdr {
+    pub measurements: Vec<Measurement>,
+}
+
+impl Xdr {
+    pub const FORMATTER: &'static str = "XDR";
+
+    pub fn parse(sentence: &str) -> Option<Self> {
+        let fields = sentence_fields(sentence, Self::FORMATTER)?;
+        Some(Xdr {
+            measurements: fields
+                .chunks(4)
+                .map(|measurement| Measurement {
+                    transducer_type: field(measurement.first().copied()),
+                    value: field(measurement.get(1).copied()),
+                    units: field(measurement.get(2).copied()),
+                    name: field(measurement.get(3).copied()),
+                })
+                .collect(),
+        })
+    }
+}
+
+// any of the sentences above, typed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sentence {
+    Vtg(Vtg),
+    Hdg(Hdg),
+    Hdm(Hdm),
+    Rot(Rot),
+    Rsa(Rsa),
+    Mwv(Mwv),
+    Mwd(Mwd),
+    Mtw(Mtw),
+    Dbt(Dbt),
+    Xdr(Xdr),
+    Gsa(Gsa),
+    Gsv(Gsv),
+    Gns(Gns),
+    Gst(Gst),
+    Vbw(Vbw),
+    Vlw(Vlw),
+    Rpm(Rpm),
+    Ths(Ths),
+    Alr(Alr),
+    Alf(Alf),
+    Ack(Ack),
+    Txt(Txt),
+    Osd(Osd),
+    Rsd(Rsd),
+    Dtm(Dtm),
+    Vdr(Vdr),
+}
+
+impl Sentence {
+    pub fn parse(sentence: &str) -> Option<Self> {
+        match formatter(sentence)? {
+            Vtg::FORMATTER => Vtg::parse(sentence).map(Sentence::Vtg),
+            Hdg::FORMATTER => Hdg::parse(sentence).map(Sentence::Hdg),
+            Hdm::FORMATTER => Hdm::parse(sentence).map(Sentence::Hdm),
+            Rot::FORMATTER => Rot::parse(sentence).map(Sentence::Rot),
+            Rsa::FORMATTER => Rsa::parse(sentence).map(Sentence::Rsa),
+            Mwv::FORMATTER => Mwv::parse(sentence).map(Sentence::Mwv),
+            Mwd::FORMATTER => Mwd::parse(sentence).map(Sentence::Mwd),
+            Mtw::FORMATTER => Mtw::parse(sentence).map(Sentence::Mtw),
+            Dbt::FORMATTER => Dbt::parse(sentence).map(Sentence::Dbt),
+            Xdr::FORMATTER => Xdr::parse(sentence).map(Sentence::Xdr),
+            Gsa::FORMATTER => Gsa::parse(sentence).map(Sentence::Gsa),
+            Gsv::FORMATTER => Gsv::parse(sentence).map(Sentence::Gsv),
+            Gns::FORMATTER => Gns::parse(sentence).map(Sentence::Gns),
+            Gst::FORMATTER => Gst::parse(sentence).map(Sentence::Gst),
+            Vbw::FORMATTER => Vbw::parse(sentence).map(Sentence::Vbw),
+            Vlw::FORMATTER => Vlw::parse(sentence).map(Sentence::Vlw),
+            Rpm::FORMATTER => Rpm::parse(sentence).map(Sentence::Rpm),
+            Ths::FORMATTER => Ths::parse(sentence).map(Sentence::Ths),
+            Alr::FORMATTER => Alr::parse(sentence).map(Sentence::Alr),
+            Alf::FORMATTER => Alf::parse(sentence).map(Sentence::Alf),
+            Ack::FORMATTER => Ack::parse(sentence).map(Sentence::Ack),
+            Txt::FORMATTER => Txt::parse(sentence).map(Sentence::Txt),
+            Osd::FORMATTER => Osd::parse(sentence).map(Sentence::Osd),
+            Rsd::FORMATTER => Rsd::parse(sentence).map(Sentence::Rsd),
+            Dtm::FORMATTER => Dtm::parse(sentence).map(Sentence::Dtm),
+            Vdr::FORMATTER => Vdr::parse(sentence).map(Sentence::Vdr),
+            _ => None,
+        }
+    }
+}
+
+// the sentence formatter, the last three characters of the address field
+fn formatter(sentence: &str) -> Option<&str> {
+    let (_, sentence) = split_tag_block(sentence);
+    let address = sentence.get(1..)?.split([',', '*']).next()?;
+    address.get(address.len().checked_sub(3)?..)
+}
+
+// the fields after the address, without checksum nor line terminator
+fn sentence_fields<'a>(sentence: &'a str, formatter: &str) -> Option<Vec<&'a str>> {
+    let (_, sentence) = split_tag_block(sentence);
+    let body = sentence.trim_end().strip_prefix(['$', '!'])?;
+    let body = body.split_once('*').map_or(body, |(fields, _)| fields);
+    let mut fields = body.split(',');
+    let address = fields.next()?;
+    if address.len() != 5 || !address.ends_with(formatter) {
+        return None;
+    }
+    Some(fields.map(str::trim).collect())
+}
+
+// null fields are empty
+fn field<T: FromStr>(value: Option<&str>) -> Option<T> {
+    value
+        .filter(|value| !value.is_empty())
+        .and_then(|value| value.parse().ok())
+}
@@ -0,0 +1,71 @@
+// IEC 61162-450 header of a datagram carrying sentences
+pub const UDPBC_HEADER: &str = "UdPbC\0";
+
+// one sentence out of a group sent together, g:line-lines-id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SentenceGroup {
+    pub line: u32,
+    pub lines: u32,
+    pub id: u32,
+}
+
+// the IEC 61162-450 tag block in front of a sentence, \s:GP0001,n:12*hh\
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagBlock {
+    // source and destination identifiers, talker id and instance number
+    pub source: Option<String>,
+    pub destination: Option<String>,
+    // line count of the source, 1 to 999
+    pub line_count: Option<u32>,
+    pub group: Option<SentenceGroup>,
+    // unix time
+    pub time: Option<u64>,
+    pub valid_checksum: bool,
+}
+
+// the payload without its IEC 61162-450 header, and whether it had one
+pub fn strip_udpbc_header(payload: &str) -> (bool, &str) {
+    match payload.strip_prefix(UDPBC_HEADER) {
+        Some(sentences) => (true, sentences),
+        None => (false, payload),
+    }
+}
+
+// the tag block in front of a sentence, and the sentence after it
+pub fn split_tag_block(sentence: &str) -> (Option<TagBlock>, &str) {
+    let Some(tagged) = sentence.strip_prefix('\\') else {
+        return (None, sentence);
+    };
+    let Some((block, rest)) = tagged.split_once('\\') else {
+        return (None, sentence);
+    };
+
+    let (parameters, checksum) = block.split_once('*').unwrap_or((block, ""));
+    let expected = parameters
+        .bytes()
+        .fold(0u8, |checksum, byte| checksum ^ byte);
+    let mut tag_block = TagBlock {
+        valid_checksum: checksum.len() == 2
+            && u8::from_str_radix(checksum, 16).is_ok_and(|checksum| checksum == expected),
+        ..TagBlock::default()
+    };
+
+    parameters
+        .split(',')
+        .filter_map(|parameter| parameter.split_once(':'))
+        .for_each(|(code, value)| match code {
+            "s" => tag_block.source = Some(value.to_string()),
+            "d" => tag_block.destination = Some(value.to_string()),
+            "n" => tag_block.line_count = value.parse().ok(),
+            "c" => tag_block.time = value.parse().ok(),
+            "g" => {
+                let numbers: Vec<u32> = value.split('-').filter_map(|n| n.parse().ok()).collect();
+                if let [line, lines, id] = numbers[..] {
+                    tag_block.group = Some(SentenceGroup { line, lines, id });
+                }
+            }
+            _ => {}
+        });
+
+    (Some(tag_block), rest)
+}
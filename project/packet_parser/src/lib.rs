@@ -1,8 +1,49 @@
-use pnet::packet::ethernet::{EtherTypes, EthernetPacket};
-use pnet::packet::ip::IpNextHeaderProtocols;
-use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ethernet::{EtherType, EtherTypes, EthernetPacket};
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::ipv4::{Ipv4Flags, Ipv4Packet};
+use pnet::packet::ipv6::Ipv6Packet;
 use pnet::packet::udp::UdpPacket;
 use pnet::packet::Packet;
+use std::net::IpAddr;
+use std::ops::Range;
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const VLAN_TAG_LEN: usize = 4;
+const IPV6_HEADER_LEN: usize = 40;
+// more stacked tags than this is not a frame any bridge would produce
+const MAX_VLAN_TAGS: usize = 4;
+
+// 802.1Q tag, and the 802.1ad service tag of QinQ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VlanTag {
+    pub tpid: u16,
+    pub pcp: u8,
+    pub dei: bool,
+    pub vid: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+// where the headers of a frame are, so they can be read or rewritten in place
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameLayout {
+    // outermost tag first
+    pub vlan_tags: Vec<VlanTag>,
+    // ethertype after the tags
+    pub ethertype: EtherType,
+    pub network_offset: usize,
+    pub family: Option<AddressFamily>,
+    // the udp datagram, header included, when the frame carries a whole one
+    pub udp: Option<Range<usize>>,
+    // the tcp segment, header included
+    pub tcp: Option<Range<usize>>,
+    // an ipv4 or ipv6 fragment, the first one included
+    pub fragment: bool,
+}
 
 pub struct PacketParser<'a> {
     packet: &'a [u8],
@@ -13,65 +54,148 @@ impl<'a> PacketParser<'a> {
         PacketParser { packet }
     }
 
+    // bytes that are not utf-8 come out as U+FFFD, so a stray byte cannot hide a sentence from the checks
     pub fn parse_traffic(&self) -> Result<String, i32> {
-        if let Some(eth_packet) = EthernetPacket::new(self.packet) {
-            // println!(
-            //     "| ETH [SRC: {:?}] [DST: {:?}]",
-            //     eth_packet.get_source(),
-            //     eth_packet.get_destination()
-            // );
-            match eth_packet.get_ethertype() {
-                EtherTypes::Ipv4 => {
-                    if let Some(ipv4packet) = Ipv4Packet::new(eth_packet.payload()) {
-                        return self.parse_protocol_ipv4(ipv4packet);
-                    } else {
-                        return Err(-1);
-                    }
-                }
-                _ => {
-                    // println!("{}", eth_packet.get_ethertype().to_string().to_uppercase());
-                    return Err(-2);
-                }
-            }
+        let layout = self.layout().ok_or(-1)?;
+        if layout.family.is_none() {
+            return Err(-2);
         }
 
-        Err(-1)
+        let udp = layout.udp.ok_or(-1)?;
+        let udp_packet = UdpPacket::new(&self.packet[udp]).ok_or(-1)?;
+        Ok(String::from_utf8_lossy(udp_packet.payload()).into_owned())
     }
 
-    fn parse_protocol_ipv4(&self, ipv4_packet: Ipv4Packet) -> Result<String, i32> {
-        // println!(
-        //     "| IPV4 [SRC: {:}] [DST: {:?}]",
-        //     ipv4_packet.get_source(),
-        //     ipv4_packet.get_destination()
-        // );
-        match ipv4_packet.get_next_level_protocol() {
-            IpNextHeaderProtocols::Udp => self.parse_udp(ipv4_packet),
-            _ => {
-                // println!(
-                //     "{}",
-                //     ipv4_packet
-                //         .get_next_level_protocol()
-                //         .to_string()
-                //         .to_uppercase()
-                // );
-                Err(-1)
+    pub fn source_ip(&self) -> Option<IpAddr> {
+        let layout = self.layout()?;
+        let network = &self.packet[layout.network_offset..];
+        match layout.family? {
+            AddressFamily::Ipv4 => {
+                Ipv4Packet::new(network).map(|packet| packet.get_source().into())
+            }
+            AddressFamily::Ipv6 => {
+                Ipv6Packet::new(network).map(|packet| packet.get_source().into())
             }
         }
     }
 
-    fn parse_udp(&self, ipv4_packet: Ipv4Packet) -> Result<String, i32> {
-        if let Some(udp_packet) = UdpPacket::new(ipv4_packet.payload()) {
-            // println!(
-            //     "| UDP [SRC PRT: {:?}] [DST PRT: {:?}]",
-            //     udp_packet.get_source(),
-            //     udp_packet.get_destination()
-            // );
-            let payload = udp_packet.payload();
-            if let Ok(payload_str) = std::str::from_utf8(payload) {
-                // println!("| {}", payload_str);
-                return Ok(String::from(payload_str));
+    pub fn layout(&self) -> Option<FrameLayout> {
+        let ethernet_packet = EthernetPacket::new(self.packet)?;
+        let mut ethertype = ethernet_packet.get_ethertype();
+        let mut network_offset = ETHERNET_HEADER_LEN;
+        let mut vlan_tags = Vec::new();
+
+        while is_vlan_tpid(ethertype) {
+            if vlan_tags.len() == MAX_VLAN_TAGS {
+                return None;
+            }
+            // the tag control information follows the tpid, the next ethertype follows the tag
+            let tag = self
+                .packet
+                .get(network_offset..network_offset + VLAN_TAG_LEN)?;
+            let tci = u16::from_be_bytes([tag[0], tag[1]]);
+            vlan_tags.push(VlanTag {
+                tpid: ethertype.0,
+                pcp: (tci >> 13) as u8,
+                dei: tci & 0x1000 != 0,
+                vid: tci & 0x0fff,
+            });
+            ethertype = EtherType(u16::from_be_bytes([tag[2], tag[3]]));
+            network_offset += VLAN_TAG_LEN;
+        }
+
+        let (family, transport, fragment) = match ethertype {
+            EtherTypes::Ipv4 => (
+                Some(AddressFamily::Ipv4),
+                self.ipv4_transport(network_offset),
+                Ipv4Packet::new(&self.packet[network_offset..]).is_some_and(|packet| {
+                    packet.get_fragment_offset() != 0
+                        || packet.get_flags() & Ipv4Flags::MoreFragments != 0
+                }),
+            ),
+            EtherTypes::Ipv6 => {
+                let (transport, fragment) = self.ipv6_transport(network_offset);
+                (Some(AddressFamily::Ipv6), transport, fragment)
             }
+            _ => (None, None, false),
+        };
+        let (udp, tcp) = match transport {
+            Some((IpNextHeaderProtocols::Udp, range)) => (Some(range), None),
+            Some((IpNextHeaderProtocols::Tcp, range)) => (None, Some(range)),
+            _ => (None, None),
+        };
+
+        Some(FrameLayout {
+            vlan_tags,
+            ethertype,
+            network_offset,
+            family,
+            udp,
+            tcp,
+            fragment,
+        })
+    }
+
+    // the transport protocol and where its header and payload are
+    fn ipv4_transport(&self, offset: usize) -> Option<(IpNextHeaderProtocol, Range<usize>)> {
+        let ipv4_packet = Ipv4Packet::new(&self.packet[offset..])?;
+        // later fragments carry no transport header
+        if ipv4_packet.get_fragment_offset() != 0 {
+            return None;
+        }
+        let start = offset + ipv4_packet.get_header_length() as usize * 4;
+        let end = (offset + ipv4_packet.get_total_length() as usize).min(self.packet.len());
+        (start < end).then_some((ipv4_packet.get_next_level_protocol(), start..end))
+    }
+
+    // walks the extension headers up to the transport header, a fragment header splitting the
+    // datagram hides the transport from the inspection and is reported along
+    fn ipv6_transport(
+        &self,
+        offset: usize,
+    ) -> (Option<(IpNextHeaderProtocol, Range<usize>)>, bool) {
+        let Some(ipv6_packet) = Ipv6Packet::new(&self.packet[offset..]) else {
+            return (None, false);
+        };
+        let end = (offset + IPV6_HEADER_LEN + ipv6_packet.get_payload_length() as usize)
+            .min(self.packet.len());
+        let mut next_header = ipv6_packet.get_next_header();
+        let mut start = offset + IPV6_HEADER_LEN;
+
+        loop {
+            let Some(header) = self.packet.get(start..start + 2) else {
+                return (None, false);
+            };
+            let header_length = match next_header {
+                IpNextHeaderProtocols::Udp | IpNextHeaderProtocols::Tcp => break,
+                IpNextHeaderProtocols::Hopopt
+                | IpNextHeaderProtocols::Ipv6Route
+                | IpNextHeaderProtocols::Ipv6Opts => (header[1] as usize + 1) * 8,
+                IpNextHeaderProtocols::Ipv6Frag => {
+                    // only an unfragmented datagram carries the whole udp payload
+                    let Some(fragment) = self.packet.get(start..start + 8) else {
+                        return (None, true);
+                    };
+                    let offset_and_flags = u16::from_be_bytes([fragment[2], fragment[3]]);
+                    if offset_and_flags & 0xfff9 != 0 {
+                        return (None, true);
+                    }
+                    8
+                }
+                IpNextHeaderProtocols::Ah => (header[1] as usize + 2) * 4,
+                // esp, no next header and the other transports
+                _ => return (None, false),
+            };
+            next_header = IpNextHeaderProtocol(header[0]);
+            start += header_length;
         }
-        Err(-1)
+
+        ((start < end).then_some((next_header, start..end)), false)
     }
 }
+
+fn is_vlan_tpid(ethertype: EtherType) -> bool {
+    ethertype == EtherTypes::Vlan
+        || ethertype == EtherTypes::PBridge
+        || ethertype == EtherTypes::QinQ
+}
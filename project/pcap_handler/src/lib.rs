@@ -0,0 +1,879 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// pcapng block types
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const SIMPLE_PACKET_BLOCK: u32 = 0x0000_0003;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+// pcapng option codes
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const IF_NAME: u16 = 2;
+const IF_TSRESOL: u16 = 9;
+const EPB_FLAGS: u16 = 2;
+
+// classic pcap magic numbers, microsecond and nanosecond resolution
+const PCAP_MAGIC_MICROS: u32 = 0xA1B2_C3D4;
+const PCAP_MAGIC_NANOS: u32 = 0xA1B2_3C4D;
+
+const LINKTYPE_ETHERNET: u16 = 1;
+const SNAPLEN: u32 = 65535;
+// the lengths in a capture are untrusted, no frame or block is read past this many bytes
+const MAX_READ_LENGTH: usize = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+impl Direction {
+    // epb_flags bits 0-1 carry the packet direction
+    fn epb_flags(&self) -> u32 {
+        match self {
+            Direction::Inbound => 0b01,
+            Direction::Outbound => 0b10,
+        }
+    }
+}
+
+pub struct PcapngWriter {
+    file: BufWriter<File>,
+    bytes_written: u64,
+}
+
+impl PcapngWriter {
+    pub fn create(path: &PathBuf, ifname: &str) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut writer = PcapngWriter {
+            file,
+            bytes_written: 0,
+        };
+
+        writer.write_section_header()?;
+        writer.write_interface_description(ifname)?;
+        Ok(writer)
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    pub fn write_packet(
+        &mut self,
+        data: &[u8],
+        direction: Direction,
+        comment: Option<&str>,
+    ) -> io::Result<()> {
+        // timestamps use the default resolution of microseconds
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        let captured_len = data.len().min(SNAPLEN as usize);
+
+        let mut body = Vec::with_capacity(captured_len + 64);
+        body.extend_from_slice(&0u32.to_le_bytes()); // interface id
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        body.extend_from_slice(&(captured_len as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&data[..captured_len]);
+        pad_to_32_bits(&mut body);
+
+        if let Some(comment) = comment {
+            push_option(&mut body, OPT_COMMENT, comment.as_bytes());
+        }
+        push_option(&mut body, EPB_FLAGS, &direction.epb_flags().to_le_bytes());
+        push_option(&mut body, OPT_ENDOFOPT, &[]);
+
+        self.write_block(ENHANCED_PACKET_BLOCK, &body)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    fn write_section_header(&mut self) -> io::Result<()> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes()); // major version
+        body.extend_from_slice(&0u16.to_le_bytes()); // minor version
+        body.extend_from_slice(&(-1i64).to_le_bytes()); // section length not specified
+        self.write_block(SECTION_HEADER_BLOCK, &body)
+    }
+
+    fn write_interface_description(&mut self, ifname: &str) -> io::Result<()> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes()); // reserved
+        body.extend_from_slice(&SNAPLEN.to_le_bytes());
+        push_option(&mut body, IF_NAME, ifname.as_bytes());
+        push_option(&mut body, OPT_ENDOFOPT, &[]);
+        self.write_block(INTERFACE_DESCRIPTION_BLOCK, &body)
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> io::Result<()> {
+        // block type + two total length fields wrap the body
+        let total_length = (body.len() + 12) as u32;
+        self.file.write_all(&block_type.to_le_bytes())?;
+        self.file.write_all(&total_length.to_le_bytes())?;
+        self.file.write_all(body)?;
+        self.file.write_all(&total_length.to_le_bytes())?;
+        self.bytes_written += total_length as u64;
+        Ok(())
+    }
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    pad_to_32_bits(body);
+}
+
+fn pad_to_32_bits(body: &mut Vec<u8>) {
+    while !body.len().is_multiple_of(4) {
+        body.push(0);
+    }
+}
+
+// a single capture stream that moves on to a new file when it gets too big or too old
+struct RotatingStream {
+    name: &'static str,
+    writer: Option<PcapngWriter>,
+    opened_at: Instant,
+    file_index: u32,
+}
+
+impl RotatingStream {
+    fn new(name: &'static str) -> Self {
+        RotatingStream {
+            name,
+            writer: None,
+            opened_at: Instant::now(),
+            file_index: 0,
+        }
+    }
+}
+
+pub struct PcapRecorder {
+    pub directory: PathBuf,
+    pub ifname: String,
+    pub split: bool,
+    pub max_file_size: u64,
+    pub max_file_duration: Duration,
+    started_at: u64,
+    forwarded: RotatingStream,
+    dropped: RotatingStream,
+}
+
+impl PcapRecorder {
+    pub fn new(
+        directory: PathBuf,
+        ifname: String,
+        split: bool,
+        max_file_size: u64,
+        max_file_duration: Duration,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(&directory)?;
+
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        // when not splitting, every frame ends up in the "all" stream annotated with its verdict
+        let (forwarded, dropped) = if split {
+            (
+                RotatingStream::new("forwarded"),
+                RotatingStream::new("dropped"),
+            )
+        } else {
+            (RotatingStream::new("all"), RotatingStream::new("all"))
+        };
+
+        Ok(PcapRecorder {
+            directory,
+            ifname,
+            split,
+            max_file_size,
+            max_file_duration,
+            started_at,
+            forwarded,
+            dropped,
+        })
+    }
+
+    // frames without a drop reason were let in or, on their way out, forwarded,
+    // the others carry the reason as a packet comment
+    pub fn record(
+        &mut self,
+        data: &[u8],
+        direction: Direction,
+        drop_reason: Option<&str>,
+    ) -> io::Result<()> {
+        match drop_reason {
+            Some(reason) => self.write(data, direction, format!("dropped: {}", reason), true),
+            None if direction == Direction::Inbound => {
+                self.write(data, direction, String::from("allowed"), false)
+            }
+            None => self.write(data, direction, String::from("forwarded"), false),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        for stream in [&mut self.forwarded, &mut self.dropped] {
+            if let Some(writer) = stream.writer.as_mut() {
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write(
+        &mut self,
+        data: &[u8],
+        direction: Direction,
+        comment: String,
+        is_dropped: bool,
+    ) -> io::Result<()> {
+        let stream = if is_dropped && self.split {
+            &mut self.dropped
+        } else {
+            &mut self.forwarded
+        };
+
+        let needs_rotation = match &stream.writer {
+            Some(writer) => {
+                (self.max_file_size > 0 && writer.bytes_written() >= self.max_file_size)
+                    || (!self.max_file_duration.is_zero()
+                        && stream.opened_at.elapsed() >= self.max_file_duration)
+            }
+            None => true,
+        };
+
+        if needs_rotation {
+            if let Some(mut writer) = stream.writer.take() {
+                writer.flush()?;
+            }
+
+            let file_name = format!(
+                "{}-{}-{}-{:04}.pcapng",
+                self.ifname, stream.name, self.started_at, stream.file_index
+            );
+            stream.writer = Some(PcapngWriter::create(
+                &self.directory.join(file_name),
+                &self.ifname,
+            )?);
+            stream.opened_at = Instant::now();
+            stream.file_index += 1;
+        }
+
+        match stream.writer.as_mut() {
+            Some(writer) => writer.write_packet(data, direction, Some(&comment)),
+            None => Ok(()),
+        }
+    }
+}
+
+pub struct PcapPacket {
+    // interface name from the pcapng interface description, if any
+    pub interface: Option<String>,
+    // seconds since the unix epoch
+    pub timestamp: f64,
+    pub data: Vec<u8>,
+}
+
+enum PcapFormat {
+    Classic { nanos: bool },
+    Pcapng,
+}
+
+struct InterfaceDescription {
+    name: Option<String>,
+    link_type: u16,
+    // timestamp units per second
+    resolution: f64,
+}
+
+// reads ethernet frames from classic pcap and pcapng files
+pub struct PcapReader {
+    file: BufReader<File>,
+    format: PcapFormat,
+    big_endian: bool,
+    interfaces: Vec<InterfaceDescription>,
+}
+
+impl PcapReader {
+    pub fn open(path: &PathBuf) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic)?;
+
+        let mut reader = PcapReader {
+            file,
+            format: PcapFormat::Pcapng,
+            big_endian: false,
+            interfaces: Vec::new(),
+        };
+
+        if u32::from_le_bytes(magic) == SECTION_HEADER_BLOCK {
+            reader.read_section_header()?;
+            return Ok(reader);
+        }
+
+        let (nanos, big_endian) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAP_MAGIC_MICROS, _) => (false, false),
+            (PCAP_MAGIC_NANOS, _) => (true, false),
+            (_, PCAP_MAGIC_MICROS) => (false, true),
+            (_, PCAP_MAGIC_NANOS) => (true, true),
+            _ => return Err(invalid_data("not a pcap or pcapng file")),
+        };
+
+        // rest of the global header: versions, timezone, sigfigs, snaplen, link type
+        let mut header = [0u8; 20];
+        reader.file.read_exact(&mut header)?;
+        reader.format = PcapFormat::Classic { nanos };
+        reader.big_endian = big_endian;
+
+        let link_type = reader.u32_at(&header, 16) as u16;
+        if link_type != LINKTYPE_ETHERNET {
+            return Err(invalid_data("only ethernet captures are supported"));
+        }
+
+        Ok(reader)
+    }
+
+    pub fn next_packet(&mut self) -> io::Result<Option<PcapPacket>> {
+        match self.format {
+            PcapFormat::Classic { nanos } => self.next_classic_packet(nanos),
+            PcapFormat::Pcapng => self.next_pcapng_packet(),
+        }
+    }
+
+    fn next_classic_packet(&mut self, nanos: bool) -> io::Result<Option<PcapPacket>> {
+        let mut header = [0u8; 16];
+        if !self.read_or_eof(&mut header)? {
+            return Ok(None);
+        }
+
+        let seconds = self.u32_at(&header, 0) as f64;
+        let fraction = self.u32_at(&header, 4) as f64;
+        let captured_len = self.u32_at(&header, 8) as usize;
+        if captured_len > MAX_READ_LENGTH {
+            return Err(invalid_data("pcap packet too long"));
+        }
+
+        let mut data = vec![0u8; captured_len];
+        self.file.read_exact(&mut data)?;
+
+        let resolution = if nanos { 1e9 } else { 1e6 };
+        Ok(Some(PcapPacket {
+            interface: None,
+            timestamp: seconds + fraction / resolution,
+            data,
+        }))
+    }
+
+    fn next_pcapng_packet(&mut self) -> io::Result<Option<PcapPacket>> {
+        loop {
+            let mut header = [0u8; 8];
+            if !self.read_or_eof(&mut header)? {
+                return Ok(None);
+            }
+
+            let block_type = self.u32_at(&header, 0);
+            if block_type == SECTION_HEADER_BLOCK {
+                // a new section can change the byte order and resets the interfaces
+                self.interfaces.clear();
+                self.file.seek_relative(-4)?;
+                self.read_section_header()?;
+                continue;
+            }
+
+            let total_length = self.u32_at(&header, 4) as usize;
+            if total_length < 12 {
+                return Err(invalid_data("pcapng block too short"));
+            }
+            if total_length > MAX_READ_LENGTH {
+                return Err(invalid_data("pcapng block too long"));
+            }
+
+            let mut body = vec![0u8; total_length - 12];
+            self.file.read_exact(&mut body)?;
+            let mut trailer = [0u8; 4];
+            self.file.read_exact(&mut trailer)?;
+
+            match block_type {
+                INTERFACE_DESCRIPTION_BLOCK => {
+                    let interface = self.parse_interface_description(&body)?;
+                    self.interfaces.push(interface);
+                }
+                ENHANCED_PACKET_BLOCK => {
+                    if let Some(packet) = self.parse_enhanced_packet(&body)? {
+                        return Ok(Some(packet));
+                    }
+                }
+                SIMPLE_PACKET_BLOCK => {
+                    if body.len() < 4 {
+                        return Err(invalid_data("simple packet block too short"));
+                    }
+                    let original_len = self.u32_at(&body, 0) as usize;
+                    let captured_len = original_len.min(body.len() - 4);
+                    return Ok(Some(PcapPacket {
+                        interface: self.interfaces.first().and_then(|i| i.name.clone()),
+                        timestamp: 0.0,
+                        data: body[4..4 + captured_len].to_vec(),
+                    }));
+                }
+                // statistics, name resolution and custom blocks carry no frames
+                _ => {}
+            }
+        }
+    }
+
+    fn read_section_header(&mut self) -> io::Result<()> {
+        // block length, byte order magic
+        let mut header = [0u8; 8];
+        self.file.read_exact(&mut header)?;
+
+        let magic = [header[4], header[5], header[6], header[7]];
+        self.big_endian = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (BYTE_ORDER_MAGIC, _) => false,
+            (_, BYTE_ORDER_MAGIC) => true,
+            _ => return Err(invalid_data("invalid pcapng byte order magic")),
+        };
+
+        // skip the rest of the block: versions, section length, options and trailer
+        let total_length = self.u32_at(&header, 0) as usize;
+        if total_length < 28 {
+            return Err(invalid_data("pcapng section header too short"));
+        }
+        if total_length > MAX_READ_LENGTH {
+            return Err(invalid_data("pcapng section header too long"));
+        }
+        let mut rest = vec![0u8; total_length - 12];
+        self.file.read_exact(&mut rest)
+    }
+
+    fn parse_interface_description(&self, body: &[u8]) -> io::Result<InterfaceDescription> {
+        if body.len() < 8 {
+            return Err(invalid_data("interface description block too short"));
+        }
+
+        let mut interface = InterfaceDescription {
+            name: None,
+            link_type: self.u16_at(body, 0),
+            resolution: 1e6,
+        };
+
+        for (code, value) in self.options(&body[8..]) {
+            match code {
+                IF_NAME => {
+                    interface.name = Some(
+                        String::from_utf8_lossy(value)
+                            .trim_end_matches('\0')
+                            .to_string(),
+                    )
+                }
+                IF_TSRESOL if !value.is_empty() => {
+                    // the high bit selects a power of two instead of a power of ten
+                    let exponent = (value[0] & 0x7F) as i32;
+                    interface.resolution = if value[0] & 0x80 != 0 {
+                        2f64.powi(exponent)
+                    } else {
+                        10f64.powi(exponent)
+                    };
+                }
+                _ => {}
+            }
+        }
+
+        Ok(interface)
+    }
+
+    fn parse_enhanced_packet(&self, body: &[u8]) -> io::Result<Option<PcapPacket>> {
+        if body.len() < 20 {
+            return Err(invalid_data("enhanced packet block too short"));
+        }
+
+        let interface_id = self.u32_at(body, 0) as usize;
+        let timestamp = ((self.u32_at(body, 4) as u64) << 32) | self.u32_at(body, 8) as u64;
+        let captured_len = self.u32_at(body, 12) as usize;
+        if body.len() < 20 + captured_len {
+            return Err(invalid_data("enhanced packet block truncated"));
+        }
+
+        let interface = match self.interfaces.get(interface_id) {
+            Some(interface) => interface,
+            None => return Err(invalid_data("packet refers to an unknown interface")),
+        };
+
+        if interface.link_type != LINKTYPE_ETHERNET {
+            return Ok(None);
+        }
+
+        Ok(Some(PcapPacket {
+            interface: interface.name.clone(),
+            timestamp: timestamp as f64 / interface.resolution,
+            data: body[20..20 + captured_len].to_vec(),
+        }))
+    }
+
+    fn options<'b>(&self, mut options: &'b [u8]) -> Vec<(u16, &'b [u8])> {
+        let mut parsed = Vec::new();
+        while options.len() >= 4 {
+            let code = self.u16_at(options, 0);
+            let length = self.u16_at(options, 2) as usize;
+            if code == OPT_ENDOFOPT || options.len() < 4 + length {
+                break;
+            }
+            parsed.push((code, &options[4..4 + length]));
+
+            let padded_length = (length + 3) & !3;
+            options = &options[(4 + padded_length).min(options.len())..];
+        }
+        parsed
+    }
+
+    // reads a whole buffer, returning false on a clean end of file
+    fn read_or_eof(&mut self, buffer: &mut [u8]) -> io::Result<bool> {
+        match self.file.read_exact(buffer) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn u16_at(&self, bytes: &[u8], offset: usize) -> u16 {
+        let value = [bytes[offset], bytes[offset + 1]];
+        if self.big_endian {
+            u16::from_be_bytes(value)
+        } else {
+            u16::from_le_bytes(value)
+        }
+    }
+
+    fn u32_at(&self, bytes: &[u8], offset: usize) -> u32 {
+        let value = [
+            bytes[offset],
+            bytes[offset + 1],
+            bytes[offset + 2],
+            bytes[offset + 3],
+        ];
+        if self.big_endian {
+            u32::from_be_bytes(value)
+        } else {
+            u32::from_le_bytes(value)
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a capture file of its own for every test, they run in parallel
+    fn capture(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("pcap-{}-{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn classic_header() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&PCAP_MAGIC_MICROS.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&4u16.to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 8]);
+        bytes.extend_from_slice(&SNAPLEN.to_le_bytes());
+        bytes.extend_from_slice(&(LINKTYPE_ETHERNET as u32).to_le_bytes());
+        bytes
+    }
+
+    fn classic_record(seconds: u32, captured_len: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&seconds.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&captured_len.to_le_bytes());
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn pcapng_header(name: &str) -> Vec<u8> {
+        let path = capture(name, &[]);
+        PcapngWriter::create(&path, "test1")
+            .unwrap()
+            .flush()
+            .unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    fn error_kind(path: &PathBuf) -> io::ErrorKind {
+        let result = PcapReader::open(path).and_then(|mut reader| reader.next_packet());
+        fs::remove_file(path).unwrap();
+        result.err().map(|e| e.kind()).unwrap()
+    }
+
+    #[test]
+    fn written_frames_are_read_back() {
+        let path = capture("round-trip", &[]);
+        let mut writer = PcapngWriter::create(&path, "test1").unwrap();
+        writer
+            .write_packet(&[1, 2, 3, 4, 5], Direction::Inbound, Some("comment"))
+            .unwrap();
+        writer
+            .write_packet(&[6u8; 60], Direction::Outbound, None)
+            .unwrap();
+        writer.flush().unwrap();
+
+        let mut reader = PcapReader::open(&path).unwrap();
+        let first = reader.next_packet().unwrap().unwrap();
+        assert_eq!(first.interface.as_deref(), Some("test1"));
+        assert_eq!(first.data, vec![1, 2, 3, 4, 5]);
+        assert_eq!(reader.next_packet().unwrap().unwrap().data, vec![6u8; 60]);
+        assert!(reader.next_packet().unwrap().is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn classic_captures_are_read() {
+        let mut bytes = classic_header();
+        bytes.extend(classic_record(3, 4, &[9u8; 4]));
+        let path = capture("classic", &bytes);
+
+        let mut reader = PcapReader::open(&path).unwrap();
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.timestamp, 3.0);
+        assert_eq!(packet.data, vec![9u8; 4]);
+        assert!(reader.next_packet().unwrap().is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn an_oversized_classic_length_is_invalid_data() {
+        let mut bytes = classic_header();
+        bytes.extend(classic_record(0, u32::MAX, &[]));
+        let path = capture("classic-oversized", &bytes);
+        assert_eq!(error_kind(&path), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn a_classic_record_shorter_than_its_length_is_an_error() {
+        let mut bytes = classic_header();
+        bytes.extend(classic_record(0, 100, &[0u8; 10]));
+        let path = capture("classic-truncated", &bytes);
+        assert_eq!(error_kind(&path), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn pcapng_block_lengths_are_bounded() {
+        for (name, total_length) in [
+            ("pcapng-short", 8u32),
+            ("pcapng-oversized", (MAX_READ_LENGTH + 4) as u32),
+            ("pcapng-huge", u32::MAX),
+        ] {
+            let mut bytes = pcapng_header(&format!("{}-header", name));
+            bytes.extend_from_slice(&ENHANCED_PACKET_BLOCK.to_le_bytes());
+            bytes.extend_from_slice(&total_length.to_le_bytes());
+            let path = capture(name, &bytes);
+            assert_eq!(error_kind(&path), io::ErrorKind::InvalidData, "{}", name);
+        }
+    }
+
+    #[test]
+    fn an_oversized_section_header_is_invalid_data() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&SECTION_HEADER_BLOCK.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        let path = capture("section-oversized", &bytes);
+        assert_eq!(error_kind(&path), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn an_enhanced_packet_longer_than_its_block_is_invalid_data() {
+        let mut body = Vec::new();
+        body.extend_from_slice(&0u32.to_le_bytes());
+        body.extend_from_slice(&[0u8; 8]);
+        body.extend_from_slice(&1000u32.to_le_bytes());
+        body.extend_from_slice(&1000u32.to_le_bytes());
+        body.extend_from_slice(&[0u8; 8]);
+
+        let mut bytes = pcapng_header("epb-truncated-header");
+        let total_length = (body.len() + 12) as u32;
+        bytes.extend_from_slice(&ENHANCED_PACKET_BLOCK.to_le_bytes());
+        bytes.extend_from_slice(&total_length.to_le_bytes());
+        bytes.extend_from_slice(&body);
+        bytes.extend_from_slice(&total_length.to_le_bytes());
+        let path = capture("epb-truncated", &bytes);
+        assert_eq!(error_kind(&path), io::ErrorKind::InvalidData);
+    }
+
+    // an empty directory of its own for the files of a recorder
+    fn recording_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("pcap-recorder-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    // the file names of a recording in order, each read back as a whole capture
+    fn recording(recorder: &mut PcapRecorder) -> Vec<(String, Vec<Vec<u8>>)> {
+        recorder.flush().unwrap();
+        let mut paths: Vec<PathBuf> = fs::read_dir(&recorder.directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+
+        let files = paths
+            .iter()
+            .map(|path| {
+                let mut reader = PcapReader::open(path).unwrap();
+                let mut frames = Vec::new();
+                while let Some(packet) = reader.next_packet().unwrap() {
+                    assert_eq!(packet.interface.as_deref(), Some("eth0"));
+                    frames.push(packet.data);
+                }
+                (
+                    path.file_name().unwrap().to_string_lossy().into_owned(),
+                    frames,
+                )
+            })
+            .collect();
+        fs::remove_dir_all(&recorder.directory).unwrap();
+        files
+    }
+
+    fn recorder(
+        name: &str,
+        split: bool,
+        max_file_size: u64,
+        max_file_duration: Duration,
+    ) -> PcapRecorder {
+        PcapRecorder::new(
+            recording_directory(name),
+            String::from("eth0"),
+            split,
+            max_file_size,
+            max_file_duration,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn every_frame_goes_to_one_stream_unless_split() {
+        let mut recorder = recorder("all", false, 0, Duration::ZERO);
+        recorder
+            .record(&[1u8; 60], Direction::Inbound, None)
+            .unwrap();
+        recorder
+            .record(&[2u8; 60], Direction::Inbound, Some("component disabled"))
+            .unwrap();
+        recorder
+            .record(&[3u8; 60], Direction::Outbound, None)
+            .unwrap();
+
+        let started_at = recorder.started_at;
+        assert_eq!(
+            recording(&mut recorder),
+            [(
+                format!("eth0-all-{}-0000.pcapng", started_at),
+                vec![vec![1u8; 60], vec![2u8; 60], vec![3u8; 60]]
+            )]
+        );
+    }
+
+    #[test]
+    fn split_recordings_keep_dropped_frames_apart() {
+        let mut recorder = recorder("split", true, 0, Duration::ZERO);
+        recorder
+            .record(&[1u8; 60], Direction::Inbound, None)
+            .unwrap();
+        recorder
+            .record(&[2u8; 60], Direction::Outbound, Some("tx wakeup failed"))
+            .unwrap();
+        recorder
+            .record(&[3u8; 60], Direction::Outbound, None)
+            .unwrap();
+
+        let started_at = recorder.started_at;
+        assert_eq!(
+            recording(&mut recorder),
+            [
+                (
+                    format!("eth0-dropped-{}-0000.pcapng", started_at),
+                    vec![vec![2u8; 60]]
+                ),
+                (
+                    format!("eth0-forwarded-{}-0000.pcapng", started_at),
+                    vec![vec![1u8; 60], vec![3u8; 60]]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn files_rotate_once_they_reach_their_size() {
+        // the headers alone fill a file this small, every frame opens the next one
+        let mut recorder = recorder("size", false, 1, Duration::ZERO);
+        for byte in 0..3u8 {
+            recorder
+                .record(&[byte; 60], Direction::Inbound, None)
+                .unwrap();
+        }
+
+        let started_at = recorder.started_at;
+        let files = recording(&mut recorder);
+        assert_eq!(files.len(), 3);
+        for (index, (name, frames)) in files.into_iter().enumerate() {
+            assert_eq!(name, format!("eth0-all-{}-{:04}.pcapng", started_at, index));
+            assert_eq!(frames, [vec![index as u8; 60]]);
+        }
+    }
+
+    #[test]
+    fn files_rotate_once_they_are_open_long_enough() {
+        let mut recorder = recorder("duration", true, 0, Duration::from_millis(20));
+        recorder
+            .record(&[1u8; 60], Direction::Inbound, None)
+            .unwrap();
+        recorder
+            .record(&[2u8; 60], Direction::Inbound, None)
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+        recorder
+            .record(&[3u8; 60], Direction::Inbound, None)
+            .unwrap();
+
+        let started_at = recorder.started_at;
+        assert_eq!(
+            recording(&mut recorder),
+            [
+                (
+                    format!("eth0-forwarded-{}-0000.pcapng", started_at),
+                    vec![vec![1u8; 60], vec![2u8; 60]]
+                ),
+                (
+                    format!("eth0-forwarded-{}-0001.pcapng", started_at),
+                    vec![vec![3u8; 60]]
+                ),
+            ]
+        );
+    }
+}
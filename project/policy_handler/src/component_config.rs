@@ -0,0 +1,124 @@
+use crate::PolicyHandler;
+use firewall::{
+    MultiSentence, ProprietaryRules, Reassembler, SentenceSchemas, SentenceValidator,
+    SourceBinding, TcpInspector, TransmissionGroups,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+// how the frames of one component are inspected, the ship, its reload and the replay of a capture
+// all set their components up from it so that a policy is judged the same way everywhere
+pub struct ComponentConfig {
+    // none unless the policy enforces source binding
+    pub binding: Option<SourceBinding>,
+    // none where the policy turns reassembly or tcp inspection off
+    pub reassembler: Option<Reassembler>,
+    pub tcp: Option<TcpInspector>,
+    pub multi_sentence: MultiSentence,
+    pub validator: Option<SentenceValidator>,
+    pub proprietary: Option<ProprietaryRules>,
+    pub groups: Vec<String>,
+    pub schemas: Arc<SentenceSchemas>,
+}
+
+impl ComponentConfig {
+    // the settings of the component called name, failing on anything of the policy it cannot use
+    pub fn from_policy(policy: &PolicyHandler, name: &str) -> Result<Self, String> {
+        let component = policy
+            .policy
+            .values()
+            .find(|component| component.name == name)
+            .ok_or_else(|| format!("unknown component {}", name))?;
+
+        let binding = match policy.get_binding().enforce {
+            true => {
+                let binding = SourceBinding::new(
+                    &component.mac,
+                    &component.ip,
+                    &component.allowed_macs,
+                    &component.allowed_source_ips(),
+                )
+                .map_err(|e| format!("source binding for {} failed: {}", name, e))?;
+                Some(SourceBinding {
+                    source_ids: component.source_ids.clone(),
+                    ..binding
+                })
+            }
+            false => None,
+        };
+
+        let groups = TransmissionGroups::configure(&policy.get_groups())
+            .map_err(|e| format!("groups: {}", e))?;
+        if let Some(group) = component
+            .groups
+            .iter()
+            .find(|group| groups.by_name(group).is_none())
+        {
+            return Err(format!("{}: unknown group {}", name, group));
+        }
+
+        let datagram = policy.get_datagram();
+        let multi_sentence =
+            MultiSentence::parse(&datagram.multiple_sentences).ok_or_else(|| {
+                format!(
+                    "datagram: unknown multiple_sentences mode {}",
+                    datagram.multiple_sentences
+                )
+            })?;
+
+        let schemas = Arc::new(
+            SentenceSchemas::load(&policy.get_sentences().schemas)
+                .map_err(|e| format!("sentences: {}", e))?,
+        );
+        let validator = policy
+            .get_validation()
+            .map(|validation| {
+                SentenceValidator::configure(
+                    validation.max_length,
+                    &validation.actions(),
+                    schemas.clone(),
+                )
+            })
+            .transpose()
+            .map_err(|e| format!("validation: {}", e))?;
+        let proprietary = policy.get_proprietary().map(|proprietary| {
+            ProprietaryRules::new(&proprietary.deny, proprietary.deny_unregistered)
+        });
+
+        // without a section the defaults apply
+        let reassembler = match policy.get_reassembly() {
+            Some(reassembly) => reassembly.enabled.then(|| {
+                let mut reassembler = Reassembler::new();
+                reassembler.timeout = Duration::from_millis(reassembly.timeout_ms);
+                reassembler.max_datagrams = reassembly.max_datagrams;
+                reassembler.max_datagram_size = reassembly.max_datagram_size;
+                reassembler.max_fragments = reassembly.max_fragments;
+                reassembler.min_fragment_size = reassembly.min_fragment_size;
+                reassembler
+            }),
+            None => Some(Reassembler::new()),
+        };
+        let tcp = match policy.get_tcp() {
+            Some(tcp) => tcp.enabled.then(|| {
+                let mut inspector = TcpInspector::new();
+                inspector.ports = tcp.ports.clone();
+                inspector.max_flows = tcp.max_flows;
+                inspector.max_line_length = tcp.max_line_length;
+                inspector.idle_timeout = Duration::from_secs(tcp.idle_timeout);
+                inspector
+            }),
+            None => Some(TcpInspector::new()),
+        };
+
+        Ok(ComponentConfig {
+            binding,
+            reassembler,
+            tcp,
+            multi_sentence,
+            validator,
+            proprietary,
+            groups: component.groups.clone(),
+            schemas,
+        })
+    }
+}
@@ -1,10 +1,31 @@
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+mod component_config;
+pub use component_config::ComponentConfig;
 
 #[derive(Debug, Deserialize)]
 pub struct PolicyHandler {
     policy: HashMap<String, Component>,
+    capture: Option<CaptureConfig>,
+    audit: Option<AuditConfig>,
+    metrics: Option<MetricsConfig>,
+    plots: Option<PlotsConfig>,
+    report: Option<ReportConfig>,
+    control: Option<ControlConfig>,
+    switch: Option<SwitchConfig>,
+    binding: Option<BindingConfig>,
+    arp: Option<ArpConfig>,
+    igmp: Option<IgmpConfig>,
+    reassembly: Option<ReassemblyConfig>,
+    tcp: Option<TcpConfig>,
+    datagram: Option<DatagramConfig>,
+    validation: Option<ValidationConfig>,
+    sentences: Option<SentencesConfig>,
+    proprietary: Option<ProprietaryConfig>,
+    // IEC 61162-450 transmission groups besides the standard ones, name = "address:port"
+    groups: Option<HashMap<String, String>>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -15,23 +36,563 @@ pub struct Component {
     pub ip: String,
     pub sends: Vec<String>,
     pub receives: Vec<String>,
+    #[serde(default)]
+    pub ipv6: Option<String>,
+    // addresses the component may use as source besides its own mac and ip
+    #[serde(default)]
+    pub allowed_macs: Vec<String>,
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
+    // IEC 61162-450 source identifiers the component may put in its tag blocks
+    #[serde(default)]
+    pub source_ids: Vec<String>,
+    // IEC 61162-450 transmission groups the component listens to, like NAVD or TGTD
+    #[serde(default)]
+    pub groups: Vec<String>,
 }
 
-impl PolicyHandler {
-    pub fn new(policy_file_path: String) -> Self {
-        let toml_content =
-            fs::read_to_string(policy_file_path).expect("Failed to read config.toml");
+impl Component {
+    // addresses the component may send from besides its ip, its ipv6 address included
+    pub fn allowed_source_ips(&self) -> Vec<String> {
+        self.ipv6
+            .iter()
+            .chain(self.allowed_ips.iter())
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CaptureConfig {
+    pub directory: String,
+    // write forwarded and dropped frames to separate files instead of one annotated file
+    #[serde(default)]
+    pub split: bool,
+    // rotation thresholds, 0 disables the check
+    #[serde(default)]
+    pub max_file_size: u64,
+    #[serde(default)]
+    pub max_file_duration: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AuditConfig {
+    pub path: String,
+    // off, drops or all
+    #[serde(default = "default_audit_level")]
+    pub level: String,
+    // 0 disables the rate limit
+    #[serde(default)]
+    pub max_events_per_second: u64,
+    // log one out of this many allowed decisions
+    #[serde(default = "default_sample_allowed")]
+    pub sample_allowed: u64,
+}
+
+fn default_audit_level() -> String {
+    String::from("drops")
+}
+
+fn default_sample_allowed() -> u64 {
+    1
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MetricsConfig {
+    pub address: String,
+    #[serde(default = "default_refresh_interval_ms")]
+    pub refresh_interval_ms: u64,
+}
+
+fn default_refresh_interval_ms() -> u64 {
+    1000
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PlotsConfig {
+    #[serde(default = "default_plots_directory")]
+    pub directory: String,
+    // png or svg
+    #[serde(default = "default_plots_format")]
+    pub format: String,
+    #[serde(default = "default_true")]
+    pub auto_scale: bool,
+    // fixed axes used when auto_scale is off
+    #[serde(default = "default_time_range")]
+    pub time_range: f64,
+    #[serde(default = "default_throughput_range")]
+    pub throughput_range: f64,
+    #[serde(default = "default_packet_rate_range")]
+    pub packet_rate_range: f64,
+    // throughput, packet_rate, drops, latency, sentences
+    #[serde(default = "default_plots_charts")]
+    pub charts: Vec<String>,
+    #[serde(default = "default_true")]
+    pub dashboard: bool,
+}
+
+fn default_plots_directory() -> String {
+    String::from("./test/imgs")
+}
+
+fn default_plots_format() -> String {
+    String::from("png")
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_time_range() -> f64 {
+    30.0
+}
+
+fn default_throughput_range() -> f64 {
+    4000.0
+}
+
+fn default_packet_rate_range() -> f64 {
+    10000.0
+}
+
+fn default_plots_charts() -> Vec<String> {
+    ["throughput", "packet_rate", "drops", "latency", "sentences"]
+        .iter()
+        .map(|chart| chart.to_string())
+        .collect()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReportConfig {
+    // paths of the end of run reports, each one is optional
+    pub json: Option<String>,
+    pub csv: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ControlConfig {
+    // unix socket the operator tools connect to
+    pub path: String,
+    // group whose members may use the socket besides its owner, only the owner when left out
+    #[serde(default)]
+    pub group: Option<String>,
+}
 
-        let policy_handler: PolicyHandler =
-            toml::from_str(&toml_content).expect("Failed to parse config.toml");
+#[derive(Debug, Deserialize, Clone)]
+pub struct SwitchConfig {
+    // seconds before a learned mac address is forgotten, 0 disables aging
+    #[serde(default = "default_aging_time")]
+    pub aging_time: u64,
+    // learned mac addresses per component port, 0 means no limit
+    #[serde(default)]
+    pub max_macs_per_port: usize,
+}
+
+fn default_aging_time() -> u64 {
+    300
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BindingConfig {
+    // drop the frames whose source mac or ip the component is not bound to
+    #[serde(default = "default_true")]
+    pub enforce: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ArpConfig {
+    // answer arp requests for the components' addresses instead of flooding them
+    #[serde(default = "default_true")]
+    pub proxy: bool,
+    // drop the arp frames that contradict the components' addresses
+    #[serde(default = "default_true")]
+    pub inspection: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct IgmpConfig {
+    // restrict multicast to the ports that joined its group instead of flooding it
+    #[serde(default = "default_true")]
+    pub snooping: bool,
+    // seconds a membership lasts without being reported again
+    #[serde(default = "default_membership_interval")]
+    pub membership_interval: u64,
+}
+
+fn default_membership_interval() -> u64 {
+    260
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReassemblyConfig {
+    // without reassembly ipv4 fragments are forwarded uninspected
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_reassembly_timeout_ms")]
+    pub timeout_ms: u64,
+    // datagrams being reassembled at once, per component
+    #[serde(default = "default_max_datagrams")]
+    pub max_datagrams: usize,
+    // payload bytes of one reassembled datagram
+    #[serde(default = "default_max_datagram_size")]
+    pub max_datagram_size: usize,
+    #[serde(default = "default_max_fragments")]
+    pub max_fragments: usize,
+    // payload bytes of every fragment but the last one
+    #[serde(default = "default_min_fragment_size")]
+    pub min_fragment_size: usize,
+    // reassembled datagrams are fragmented again to fit this mtu when forwarded
+    #[serde(default = "default_mtu")]
+    pub mtu: usize,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TcpConfig {
+    // without inspection tcp is forwarded as any other non nmea traffic
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    // connections with one of these ports on either side are inspected
+    #[serde(default = "default_tcp_ports")]
+    pub ports: Vec<u16>,
+    // connections followed at once, per component
+    #[serde(default = "default_max_flows")]
+    pub max_flows: usize,
+    // bytes of a sentence before its line terminator
+    #[serde(default = "default_max_line_length")]
+    pub max_line_length: usize,
+    // seconds a silent connection is still followed
+    #[serde(default = "default_idle_timeout")]
+    pub idle_timeout: u64,
+}
 
-        policy_handler
+#[derive(Debug, Deserialize, Clone)]
+pub struct DatagramConfig {
+    // drop or filter, for datagrams packing a forbidden sentence with allowed ones
+    #[serde(default = "default_multiple_sentences")]
+    pub multiple_sentences: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ProprietaryConfig {
+    // manufacturers like $PSRF and proprietary sentences like $PGRMM no component may send
+    #[serde(default)]
+    pub deny: Vec<String>,
+    // drop the proprietary sentences of manufacturers missing from the registry
+    #[serde(default)]
+    pub deny_unregistered: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ValidationConfig {
+    // characters of a sentence, start delimiter and line terminator included
+    #[serde(default = "default_max_length")]
+    pub max_length: usize,
+    // drop, alert or allow, for each rule a sentence may break
+    #[serde(default = "default_violation_action")]
+    pub too_long: String,
+    #[serde(default = "default_violation_action")]
+    pub invalid_character: String,
+    #[serde(default = "default_violation_action")]
+    pub missing_crlf: String,
+    #[serde(default = "default_violation_action")]
+    pub bad_checksum: String,
+    #[serde(default = "default_violation_action")]
+    pub field_count: String,
+    #[serde(default = "default_violation_action")]
+    pub field_format: String,
+    #[serde(default = "default_violation_action")]
+    pub field_range: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct SentencesConfig {
+    // files of sentence schemas, for site specific and proprietary sentences
+    #[serde(default)]
+    pub schemas: Vec<String>,
+}
+
+impl ValidationConfig {
+    // the action of every violation, keyed by its name
+    pub fn actions(&self) -> Vec<(&'static str, &str)> {
+        vec![
+            ("too_long", &self.too_long),
+            ("invalid_character", &self.invalid_character),
+            ("missing_crlf", &self.missing_crlf),
+            ("bad_checksum", &self.bad_checksum),
+            ("field_count", &self.field_count),
+            ("field_format", &self.field_format),
+            ("field_range", &self.field_range),
+        ]
+    }
+}
+
+fn default_max_length() -> usize {
+    82
+}
+
+fn default_violation_action() -> String {
+    String::from("drop")
+}
+
+fn default_multiple_sentences() -> String {
+    String::from("drop")
+}
+
+fn default_tcp_ports() -> Vec<u16> {
+    vec![10110]
+}
+
+fn default_max_flows() -> usize {
+    256
+}
+
+fn default_max_line_length() -> usize {
+    1024
+}
+
+fn default_idle_timeout() -> u64 {
+    300
+}
+
+fn default_reassembly_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_max_datagrams() -> usize {
+    64
+}
+
+fn default_max_datagram_size() -> usize {
+    65515
+}
+
+fn default_max_fragments() -> usize {
+    64
+}
+
+fn default_min_fragment_size() -> usize {
+    64
+}
+
+fn default_mtu() -> usize {
+    1500
+}
+
+impl PolicyHandler {
+    pub fn new(policy_file_path: String) -> Result<Self, String> {
+        let toml_content = fs::read_to_string(&policy_file_path)
+            .map_err(|e| format!("cannot read policy {}: {}", policy_file_path, e))?;
+
+        toml::from_str(&toml_content)
+            .map_err(|e| format!("cannot parse policy {}: {}", policy_file_path, e))
     }
 
     pub fn get_policy(&self) -> Vec<Component> {
         self.policy.values().cloned().collect()
     }
 
+    pub fn get_capture(&self) -> Option<CaptureConfig> {
+        self.capture.clone()
+    }
+
+    pub fn get_audit(&self) -> Option<AuditConfig> {
+        self.audit.clone()
+    }
+
+    pub fn get_metrics(&self) -> Option<MetricsConfig> {
+        self.metrics.clone()
+    }
+
+    pub fn get_plots(&self) -> Option<PlotsConfig> {
+        self.plots.clone()
+    }
+
+    pub fn get_report(&self) -> Option<ReportConfig> {
+        self.report.clone()
+    }
+
+    pub fn get_control(&self) -> Option<ControlConfig> {
+        self.control.clone()
+    }
+
+    pub fn get_switch(&self) -> Option<SwitchConfig> {
+        self.switch.clone()
+    }
+
+    // source binding is only enforced when the policy has a [binding] section
+    pub fn get_binding(&self) -> BindingConfig {
+        self.binding
+            .clone()
+            .unwrap_or(BindingConfig { enforce: false })
+    }
+
+    // proxy arp and arp inspection are on unless the policy turns them off
+    pub fn get_arp(&self) -> ArpConfig {
+        self.arp.clone().unwrap_or(ArpConfig {
+            proxy: true,
+            inspection: true,
+        })
+    }
+
+    pub fn get_igmp(&self) -> IgmpConfig {
+        self.igmp.clone().unwrap_or(IgmpConfig {
+            snooping: true,
+            membership_interval: default_membership_interval(),
+        })
+    }
+
+    pub fn get_reassembly(&self) -> Option<ReassemblyConfig> {
+        self.reassembly.clone()
+    }
+
+    pub fn get_tcp(&self) -> Option<TcpConfig> {
+        self.tcp.clone()
+    }
+
+    pub fn get_proprietary(&self) -> Option<ProprietaryConfig> {
+        self.proprietary.clone()
+    }
+
+    pub fn get_validation(&self) -> Option<ValidationConfig> {
+        self.validation.clone()
+    }
+
+    // the sentences the ship knows besides the builtin ones
+    pub fn get_sentences(&self) -> SentencesConfig {
+        self.sentences.clone().unwrap_or_default()
+    }
+
+    // the groups the policy adds, by name
+    pub fn get_groups(&self) -> Vec<(String, String)> {
+        let mut groups: Vec<(String, String)> = self
+            .groups
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    // a forbidden sentence drops its whole datagram unless the policy asks for filtering
+    pub fn get_datagram(&self) -> DatagramConfig {
+        self.datagram.clone().unwrap_or(DatagramConfig {
+            multiple_sentences: default_multiple_sentences(),
+        })
+    }
+
+    // problems that would only show up once the components are set up
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut ifaces = HashMap::new();
+        let mut macs = HashMap::new();
+        let mut ips = HashMap::new();
+        let mut source_ids = HashMap::new();
+
+        let mut keys: Vec<&String> = self.policy.keys().collect();
+        keys.sort();
+        for key in keys {
+            let component = &self.policy[key];
+
+            if !is_mac_address(&component.mac) {
+                problems.push(format!("{}: invalid mac address {}", key, component.mac));
+            }
+            if component.ip.parse::<Ipv4Addr>().is_err() {
+                problems.push(format!("{}: invalid ip address {}", key, component.ip));
+            }
+
+            component
+                .allowed_macs
+                .iter()
+                .filter(|mac| !is_mac_address(mac))
+                .for_each(|mac| problems.push(format!("{}: invalid allowed mac {}", key, mac)));
+            if let Some(ipv6) = &component.ipv6 {
+                if ipv6.parse::<Ipv6Addr>().is_err() {
+                    problems.push(format!("{}: invalid ipv6 address {}", key, ipv6));
+                }
+            }
+            component
+                .allowed_ips
+                .iter()
+                .filter(|ip| ip.parse::<IpAddr>().is_err())
+                .for_each(|ip| problems.push(format!("{}: invalid allowed ip {}", key, ip)));
+
+            for (seen, value, what) in [
+                (&mut ifaces, &component.iface, "iface"),
+                (&mut macs, &component.mac, "mac address"),
+                (&mut ips, &component.ip, "ip address"),
+            ] {
+                if let Some(other) = seen.insert(value.to_lowercase(), key) {
+                    problems.push(format!(
+                        "{}: {} {} already used by {}",
+                        key, what, value, other
+                    ));
+                }
+            }
+            // written in canonical form, so that two spellings of one ipv6 address collide
+            if let Some(ipv6) = component
+                .ipv6
+                .as_ref()
+                .and_then(|ipv6| ipv6.parse::<Ipv6Addr>().ok())
+            {
+                if let Some(other) = ips.insert(ipv6.to_string(), key) {
+                    problems.push(format!(
+                        "{}: ipv6 address {} already used by {}",
+                        key, ipv6, other
+                    ));
+                }
+            }
+
+            // a source identifier names one talker, it cannot be shared
+            for source_id in &component.source_ids {
+                if !is_source_id(source_id) {
+                    problems.push(format!("{}: invalid source id {}", key, source_id));
+                }
+                if let Some(other) = source_ids.insert(source_id.as_str(), key) {
+                    if other != key {
+                        problems.push(format!(
+                            "{}: source id {} already used by {}",
+                            key, source_id, other
+                        ));
+                    }
+                }
+            }
+
+            component
+                .sends
+                .iter()
+                .chain(component.receives.iter())
+                .filter(|prefix| !is_sentence_prefix(prefix))
+                .for_each(|prefix| {
+                    problems.push(format!("{}: invalid sentence prefix {}", key, prefix))
+                });
+        }
+
+        // a component allowed to use the address of another one could impersonate it
+        let mut keys: Vec<&String> = self.policy.keys().collect();
+        keys.sort();
+        for key in keys {
+            let component = &self.policy[key];
+            for (allowed, owners, what) in [
+                (&component.allowed_macs, &macs, "allowed mac"),
+                (&component.allowed_ips, &ips, "allowed ip"),
+            ] {
+                for address in allowed {
+                    if let Some(owner) = owners.get(&address.to_lowercase()) {
+                        if *owner != key {
+                            problems.push(format!(
+                                "{}: {} {} belongs to {}",
+                                key, what, address, owner
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        problems
+    }
+
     pub fn show_policy(&self) {
         self.policy.iter().for_each(|(_, field)| {
             println!("|-----------------");
@@ -39,9 +600,85 @@ impl PolicyHandler {
             println!("| Iface: {}", field.iface);
             println!("| MAC: {}", field.mac);
             println!("| IP: {}", field.ip);
+            if let Some(ipv6) = &field.ipv6 {
+                println!("| IPv6: {}", ipv6);
+            }
             println!("| Sends: {:?}", field.sends);
             println!("| Receives: {:?}", field.receives);
+            if !field.allowed_macs.is_empty() || !field.allowed_ips.is_empty() {
+                println!(
+                    "| Also allowed: {:?} {:?}",
+                    field.allowed_macs, field.allowed_ips
+                );
+            }
+            if !field.source_ids.is_empty() {
+                println!("| Source IDs: {:?}", field.source_ids);
+            }
+            if !field.groups.is_empty() {
+                println!("| Groups: {:?}", field.groups);
+            }
             println!("|-----------------");
         });
     }
 }
+
+fn is_mac_address(mac: &str) -> bool {
+    let octets: Vec<&str> = mac.split(':').collect();
+    octets.len() == 6
+        && octets
+            .iter()
+            .all(|octet| octet.len() == 2 && u8::from_str_radix(octet, 16).is_ok())
+}
+
+// the talker id and a four digit instance number, like GP0001
+fn is_source_id(source_id: &str) -> bool {
+    let characters: Vec<char> = source_id.chars().collect();
+    characters.len() == 6
+        && characters[..2]
+            .iter()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        && characters[2..].iter().all(|c| c.is_ascii_digit())
+}
+
+// $ or ! followed by the talker id and the sentence type, like $GPGGA
+fn is_sentence_prefix(prefix: &str) -> bool {
+    ((prefix.starts_with('$') || prefix.starts_with('!'))
+        && prefix.len() == 6
+        && prefix[1..].chars().all(|c| c.is_ascii_alphanumeric()))
+        || is_proprietary_prefix(prefix)
+}
+
+// a manufacturer like $PGRM, or one of its sentences like $PGRMZ
+pub fn is_proprietary_prefix(prefix: &str) -> bool {
+    prefix.starts_with("$P")
+        && (5..=16).contains(&prefix.len())
+        && prefix[2..]
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_ids_are_a_talker_and_four_digits() {
+        assert!(is_source_id("GP0001"));
+        assert!(is_source_id("A10001"));
+        assert!(!is_source_id("gp0001"));
+        assert!(!is_source_id("GP001"));
+        assert!(!is_source_id("GP00A1"));
+        // six bytes, but not six characters
+        assert!(!is_source_id("é0001"));
+        assert!(!is_source_id("GPé01"));
+    }
+
+    #[test]
+    fn source_binding_takes_a_binding_section() {
+        let policy: PolicyHandler = toml::from_str("[policy]\n").unwrap();
+        assert!(!policy.get_binding().enforce);
+
+        let policy: PolicyHandler = toml::from_str("[policy]\n[binding]\n").unwrap();
+        assert!(policy.get_binding().enforce);
+    }
+}
@@ -0,0 +1,715 @@
+use firewall::{
+    DropReason, Forwarder, Forwarding, Inspector, MultiSentence, ProprietaryRules, Reassembler,
+    Receiver, SentenceSchemas, SentenceValidator, ShipSwitch, SourceBinding, TcpInspector,
+    TransmissionGroups,
+};
+use pcap_handler::{PcapPacket, PcapReader};
+use pnet::util::MacAddr;
+use policy_handler::{Component, ComponentConfig, PolicyHandler};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    // the frame passed the policy and was handed to these components
+    Allowed { delivered_to: Vec<String> },
+    Dropped(DropReason),
+    // an arp request the ship answered itself
+    Answered,
+    // a fragment waiting for the rest of its datagram
+    Held,
+    // the frame could not be attributed to a component or is not a full ethernet frame
+    Skipped(String),
+}
+
+pub struct FrameVerdict {
+    pub index: usize,
+    pub timestamp: f64,
+    pub ingress: Option<String>,
+    pub source_mac: String,
+    pub prefix: String,
+    pub verdict: Verdict,
+}
+
+impl fmt::Display for FrameVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:>6}] [{:.6}] [{}] [{}] [{}] ",
+            self.index,
+            self.timestamp,
+            self.ingress.as_deref().unwrap_or("-"),
+            self.source_mac,
+            self.prefix
+        )?;
+
+        match &self.verdict {
+            Verdict::Allowed { delivered_to } if delivered_to.is_empty() => {
+                write!(f, "ALLOWED -> NOBODY")
+            }
+            Verdict::Allowed { delivered_to } => {
+                write!(f, "ALLOWED -> {}", delivered_to.join(", "))
+            }
+            Verdict::Dropped(reason) => write!(f, "DROPPED ({})", reason),
+            Verdict::Answered => write!(f, "ANSWERED BY PROXY ARP"),
+            Verdict::Held => write!(f, "HELD FOR REASSEMBLY"),
+            Verdict::Skipped(reason) => write!(f, "SKIPPED ({})", reason),
+        }
+    }
+}
+
+// totals over the verdicts of a whole capture
+pub struct ReplaySummary {
+    pub frames: usize,
+    pub allowed: usize,
+    pub dropped: usize,
+    pub skipped: usize,
+}
+
+impl ReplaySummary {
+    pub fn new(verdicts: &[FrameVerdict]) -> Self {
+        let count = |matches: fn(&Verdict) -> bool| {
+            verdicts
+                .iter()
+                .filter(|verdict| matches(&verdict.verdict))
+                .count()
+        };
+
+        ReplaySummary {
+            frames: verdicts.len(),
+            allowed: count(|verdict| {
+                matches!(verdict, Verdict::Allowed { .. } | Verdict::Answered)
+            }),
+            dropped: count(|verdict| matches!(verdict, Verdict::Dropped(_))),
+            skipped: count(|verdict| matches!(verdict, Verdict::Skipped(_) | Verdict::Held)),
+        }
+    }
+}
+
+impl fmt::Display for ReplaySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[FRAMES: {}] [ALLOWED: {}] [DROPPED: {}] [SKIPPED: {}]",
+            self.frames, self.allowed, self.dropped, self.skipped
+        )
+    }
+}
+
+// runs recorded traffic through the same decisions the ship takes, without any network namespace
+pub struct Replay {
+    pub components: Vec<Component>,
+    pub ship_switch: ShipSwitch,
+    // source addresses of every component, empty when the policy does not enforce them
+    pub bindings: Vec<Option<SourceBinding>>,
+    // one per component, fragments are only judged once their datagram is whole
+    // none where the policy turns reassembly off
+    pub reassemblers: Vec<Option<Reassembler>>,
+    // one per component, the sentences of tcp streams are judged as their segments complete them
+    // none where the policy turns tcp inspection off
+    pub tcp: Vec<Option<TcpInspector>>,
+    pub multi_sentence: MultiSentence,
+    pub validator: Option<SentenceValidator>,
+    pub proprietary: Option<ProprietaryRules>,
+    pub groups: TransmissionGroups,
+    // the sentences recognised besides the builtin ones
+    pub schemas: Arc<SentenceSchemas>,
+    // timestamp of the first frame and when it was replayed
+    clock: Option<(f64, Instant)>,
+}
+
+impl Replay {
+    pub fn new(components: Vec<Component>, enforce_binding: bool) -> Self {
+        // the components' addresses are known from the policy, as on the ship
+        let mut ship_switch = ShipSwitch::new();
+        components.iter().enumerate().for_each(|(port, component)| {
+            let _ = ship_switch.add_static_from_str(&component.mac, port);
+            if let Ok(binding) = SourceBinding::new(
+                &component.mac,
+                &component.ip,
+                &component.allowed_macs,
+                &component.allowed_source_ips(),
+            ) {
+                ship_switch.arp.bind(port, &binding);
+            }
+        });
+
+        let bindings = match enforce_binding {
+            true => components
+                .iter()
+                .map(|component| {
+                    SourceBinding::new(
+                        &component.mac,
+                        &component.ip,
+                        &component.allowed_macs,
+                        &component.allowed_source_ips(),
+                    )
+                    .map(|binding| SourceBinding {
+                        source_ids: component.source_ids.clone(),
+                        ..binding
+                    })
+                    .ok()
+                })
+                .collect(),
+            false => Vec::new(),
+        };
+
+        let reassemblers = components
+            .iter()
+            .map(|_| Some(Reassembler::new()))
+            .collect();
+        let tcp = components
+            .iter()
+            .map(|_| Some(TcpInspector::new()))
+            .collect();
+
+        Replay {
+            components,
+            ship_switch,
+            bindings,
+            reassemblers,
+            tcp,
+            multi_sentence: MultiSentence::Drop,
+            validator: None,
+            proprietary: None,
+            groups: TransmissionGroups::new(),
+            schemas: Arc::new(SentenceSchemas::default()),
+            clock: None,
+        }
+    }
+
+    // a replay set up as the ship would be from the same policy, failing where the ship would
+    pub fn from_policy(policy: &PolicyHandler) -> Result<Self, String> {
+        let components = policy.get_policy();
+        let configs = components
+            .iter()
+            .map(|component| ComponentConfig::from_policy(policy, &component.name))
+            .collect::<Result<Vec<ComponentConfig>, String>>()?;
+
+        let mut replay = Replay::new(components, false);
+        replay.groups = TransmissionGroups::configure(&policy.get_groups())
+            .map_err(|e| format!("groups: {}", e))?;
+        for (port, config) in configs.into_iter().enumerate() {
+            replay.bindings.push(config.binding);
+            replay.reassemblers[port] = config.reassembler;
+            replay.tcp[port] = config.tcp;
+            // the ship wide sections are the same in the configuration of every component
+            replay.multi_sentence = config.multi_sentence;
+            replay.validator = config.validator;
+            replay.proprietary = config.proprietary;
+            replay.schemas = config.schemas;
+        }
+
+        if let Some(switch) = policy.get_switch() {
+            replay.ship_switch.aging_time = Duration::from_secs(switch.aging_time);
+            replay.ship_switch.max_macs_per_port = switch.max_macs_per_port;
+        }
+        let arp = policy.get_arp();
+        replay.ship_switch.arp.proxy = arp.proxy;
+        replay.ship_switch.arp.inspection = arp.inspection;
+        let igmp = policy.get_igmp();
+        replay.ship_switch.igmp.enabled = igmp.snooping;
+        replay.ship_switch.igmp.membership_interval = Duration::from_secs(igmp.membership_interval);
+
+        Ok(replay)
+    }
+
+    pub fn replay_file(&mut self, path: &PathBuf) -> io::Result<Vec<FrameVerdict>> {
+        let mut reader = PcapReader::open(path)?;
+        let mut verdicts = Vec::new();
+        while let Some(packet) = reader.next_packet()? {
+            verdicts.push(self.replay_frame(verdicts.len(), &packet));
+        }
+        Ok(verdicts)
+    }
+
+    pub fn replay_frame(&mut self, index: usize, packet: &PcapPacket) -> FrameVerdict {
+        let mut frame_verdict = FrameVerdict {
+            index,
+            timestamp: packet.timestamp,
+            ingress: None,
+            source_mac: String::from("-"),
+            prefix: String::from("NONMEA"),
+            verdict: Verdict::Skipped(String::from("frame too short")),
+        };
+
+        let frame = &packet.data;
+        if frame.len() < 14 {
+            return frame_verdict;
+        }
+
+        let source_mac = MacAddr::new(frame[6], frame[7], frame[8], frame[9], frame[10], frame[11]);
+        frame_verdict.source_mac = source_mac.to_string();
+
+        let ingress_port = match self.ingress_port(source_mac, packet.interface.as_deref()) {
+            Some(port) => port,
+            None => {
+                frame_verdict.verdict = Verdict::Skipped(String::from("unknown ingress component"));
+                return frame_verdict;
+            }
+        };
+        frame_verdict.ingress = Some(self.components[ingress_port].name.clone());
+
+        // the reassembly timeouts follow the capture timestamps, not the replay speed
+        let now = self.capture_time(packet.timestamp);
+        if let Some(reassembler) = self.reassemblers[ingress_port].as_mut() {
+            reassembler.expire(now);
+        }
+
+        let mut inspector = Inspector {
+            enabled: true,
+            sends: &self.components[ingress_port].sends,
+            schemas: &self.schemas,
+            multi_sentence: self.multi_sentence,
+            binding: self.bindings.get(ingress_port).and_then(Option::as_ref),
+            reassembler: self.reassemblers[ingress_port].as_mut(),
+            tcp: self.tcp[ingress_port].as_mut(),
+            validator: self.validator.as_mut(),
+            proprietary: self.proprietary.as_mut(),
+        };
+        let Some(inspection) = inspector.inspect(frame, ingress_port, &mut self.ship_switch, now)
+        else {
+            frame_verdict.verdict = Verdict::Held;
+            return frame_verdict;
+        };
+        let traffic = inspector.traffic(
+            &inspection,
+            ingress_port,
+            self.components.len(),
+            &mut self.ship_switch,
+            now,
+        );
+
+        frame_verdict.prefix = inspection.prefix;
+        if let Some(reason) = inspection.drop_reason {
+            frame_verdict.verdict = Verdict::Dropped(reason);
+            return frame_verdict;
+        }
+        if inspection.arp_reply.is_some() {
+            frame_verdict.verdict = Verdict::Answered;
+            return frame_verdict;
+        }
+
+        let receivers: Vec<Receiver> = self
+            .components
+            .iter()
+            .map(|component| Receiver {
+                name: &component.name,
+                mac: &component.mac,
+                ip: &component.ip,
+                ipv6: component.ipv6.as_deref(),
+                receives: &component.receives,
+                groups: &component.groups,
+            })
+            .collect();
+        let forwarder = Forwarder {
+            receivers: &receivers,
+            groups: &self.groups,
+            ship_switch: &self.ship_switch,
+            schemas: &self.schemas,
+        };
+
+        let mut delivered_to = Vec::new();
+        for entry in traffic.iter() {
+            match forwarder.forward(entry, self.multi_sentence) {
+                Forwarding::Deliver(frames) => delivered_to.extend(
+                    frames
+                        .iter()
+                        .map(|(port, _)| self.components[*port].name.clone()),
+                ),
+                Forwarding::Reset(denied) => {
+                    // the ship resets the connection instead of delivering the segment
+                    if let Some(tcp) = self.tcp[ingress_port].as_mut() {
+                        tcp.forget(&entry.data);
+                    }
+                    frame_verdict.prefix = denied.clone();
+                    frame_verdict.verdict =
+                        Verdict::Dropped(DropReason::SentenceNotAllowed(denied));
+                    return frame_verdict;
+                }
+            }
+        }
+
+        frame_verdict.verdict = Verdict::Allowed { delivered_to };
+        frame_verdict
+    }
+
+    fn capture_time(&mut self, timestamp: f64) -> Instant {
+        let (first_timestamp, started) = *self.clock.get_or_insert((timestamp, Instant::now()));
+        started + Duration::from_secs_f64((timestamp - first_timestamp).max(0.0))
+    }
+
+    // frames are attributed to the captured interface, or to the component owning the source mac
+    // when the capture does not say where they came from, so that spoofed sources are not trusted
+    fn ingress_port(&self, source_mac: MacAddr, interface: Option<&str>) -> Option<usize> {
+        interface
+            .and_then(|interface| {
+                self.components
+                    .iter()
+                    .position(|component| component.iface == interface)
+            })
+            .or_else(|| {
+                self.components
+                    .iter()
+                    .position(|component| MacAddr::from_str(&component.mac) == Ok(source_mac))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use firewall::{FragmentError, TcpError, Violation};
+    use pnet::packet::arp::{ArpHardwareTypes, ArpOperation, ArpOperations, MutableArpPacket};
+    use pnet::packet::ethernet::{EtherType, EtherTypes, MutableEthernetPacket};
+    use pnet::packet::ip::IpNextHeaderProtocols;
+    use pnet::packet::ipv4::{Ipv4Flags, MutableIpv4Packet};
+    use pnet::packet::ipv6::MutableIpv6Packet;
+    use pnet::packet::tcp::{MutableTcpPacket, TcpFlags};
+    use pnet::packet::udp::MutableUdpPacket;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    const GPS_MAC: &str = "54:00:00:00:00:30";
+    const RADAR_MAC: &str = "54:00:00:00:00:60";
+    const GPS_IP: Ipv4Addr = Ipv4Addr::new(10, 42, 0, 30);
+    const RADAR_IP: Ipv4Addr = Ipv4Addr::new(10, 42, 0, 60);
+
+    // gps sends, radar and ecdis receive and listen to the navigation group, only ecdis has ipv6
+    const COMPONENTS: &str = r#"
+[policy]
+gps = { name = "gps", iface = "test3", mac = "54:00:00:00:00:30", ip = "10.42.0.30", ipv6 = "fd00::30", sends = ["$GPGGA", "$GPGLL", "$PGRMZ"], receives = [] }
+radar = { name = "radar", iface = "test6", mac = "54:00:00:00:00:60", ip = "10.42.0.60", sends = [], receives = ["$GPGGA"], groups = ["NAVD"] }
+ecdis = { name = "ecdis", iface = "test7", mac = "54:00:00:00:00:70", ip = "10.42.0.70", ipv6 = "fd00::70", sends = [], receives = ["$GPGGA", "$GPGLL"], groups = ["NAVD"] }
+"#;
+
+    // a replay of the components with the other sections of the policy
+    fn replay(name: &str, sections: &str) -> Replay {
+        let path =
+            std::env::temp_dir().join(format!("replay-{}-{}.toml", std::process::id(), name));
+        std::fs::write(&path, format!("{}\n{}", COMPONENTS, sections)).unwrap();
+        let policy = PolicyHandler::new(path.to_string_lossy().into_owned());
+        std::fs::remove_file(&path).unwrap();
+        Replay::from_policy(&policy.unwrap()).unwrap()
+    }
+
+    fn port(replay: &Replay, name: &str) -> usize {
+        replay
+            .components
+            .iter()
+            .position(|component| component.name == name)
+            .unwrap()
+    }
+
+    // the sentence with its checksum and line terminator
+    fn sentence(fields: &str) -> Vec<u8> {
+        let checksum = fields.bytes().fold(0u8, |checksum, byte| checksum ^ byte);
+        format!("${}*{:02X}\r\n", fields, checksum).into_bytes()
+    }
+
+    fn gga() -> Vec<u8> {
+        sentence("GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,")
+    }
+
+    fn gll() -> Vec<u8> {
+        sentence("GPGLL,4916.45,N,12311.12,W,225444,A")
+    }
+
+    fn ethernet(source: &str, destination: &str, ethertype: EtherType, len: usize) -> Vec<u8> {
+        let mut frame = vec![0u8; 14 + len];
+        let mut ethernet_packet = MutableEthernetPacket::new(&mut frame).unwrap();
+        ethernet_packet.set_source(source.parse().unwrap());
+        ethernet_packet.set_destination(destination.parse().unwrap());
+        ethernet_packet.set_ethertype(ethertype);
+        frame
+    }
+
+    fn udp(segment: &mut [u8], payload: &[u8]) {
+        let mut udp_packet = MutableUdpPacket::new(segment).unwrap();
+        udp_packet.set_source(10110);
+        udp_packet.set_destination(10110);
+        udp_packet.set_length((8 + payload.len()) as u16);
+        udp_packet.set_payload(payload);
+    }
+
+    fn ipv4(packet: &mut [u8], source: Ipv4Addr, destination: Ipv4Addr, len: usize) {
+        let mut ipv4_packet = MutableIpv4Packet::new(packet).unwrap();
+        ipv4_packet.set_version(4);
+        ipv4_packet.set_header_length(5);
+        ipv4_packet.set_total_length((20 + len) as u16);
+        ipv4_packet.set_ttl(64);
+        ipv4_packet.set_source(source);
+        ipv4_packet.set_destination(destination);
+        ipv4_packet.set_next_level_protocol(IpNextHeaderProtocols::Udp);
+    }
+
+    fn udp_frame(
+        destination_mac: &str,
+        source: IpAddr,
+        destination: IpAddr,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        match (source, destination) {
+            (IpAddr::V4(source), IpAddr::V4(destination)) => {
+                let len = 8 + payload.len();
+                let mut frame = ethernet(GPS_MAC, destination_mac, EtherTypes::Ipv4, 20 + len);
+                ipv4(&mut frame[14..], source, destination, len);
+                udp(&mut frame[34..], payload);
+                frame
+            }
+            (IpAddr::V6(source), IpAddr::V6(destination)) => {
+                let len = 8 + payload.len();
+                let mut frame = ethernet(GPS_MAC, destination_mac, EtherTypes::Ipv6, 40 + len);
+                let mut ipv6_packet = MutableIpv6Packet::new(&mut frame[14..]).unwrap();
+                ipv6_packet.set_version(6);
+                ipv6_packet.set_payload_length(len as u16);
+                ipv6_packet.set_next_header(IpNextHeaderProtocols::Udp);
+                ipv6_packet.set_hop_limit(64);
+                ipv6_packet.set_source(source);
+                ipv6_packet.set_destination(destination);
+                udp(&mut frame[54..], payload);
+                frame
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // gps to radar over ipv4
+    fn gps_frame(payload: &[u8]) -> Vec<u8> {
+        udp_frame(RADAR_MAC, GPS_IP.into(), RADAR_IP.into(), payload)
+    }
+
+    // a fragment of a udp datagram from gps to radar, its payload past the ip header
+    fn fragment(more_fragments: bool, payload: &[u8]) -> Vec<u8> {
+        let mut frame = ethernet(GPS_MAC, RADAR_MAC, EtherTypes::Ipv4, 20 + payload.len());
+        ipv4(&mut frame[14..], GPS_IP, RADAR_IP, payload.len());
+        let mut ipv4_packet = MutableIpv4Packet::new(&mut frame[14..]).unwrap();
+        ipv4_packet.set_identification(7);
+        ipv4_packet.set_flags(if more_fragments {
+            Ipv4Flags::MoreFragments
+        } else {
+            0
+        });
+        ipv4_packet.set_payload(payload);
+        frame
+    }
+
+    // a segment of the nmea tcp port from gps to radar
+    fn tcp_frame(flags: u8, sequence: u32, payload: &[u8]) -> Vec<u8> {
+        let len = 20 + payload.len();
+        let mut frame = ethernet(GPS_MAC, RADAR_MAC, EtherTypes::Ipv4, 20 + len);
+        ipv4(&mut frame[14..], GPS_IP, RADAR_IP, len);
+        MutableIpv4Packet::new(&mut frame[14..])
+            .unwrap()
+            .set_next_level_protocol(IpNextHeaderProtocols::Tcp);
+        let mut tcp_packet = MutableTcpPacket::new(&mut frame[34..]).unwrap();
+        tcp_packet.set_source(40000);
+        tcp_packet.set_destination(10110);
+        tcp_packet.set_sequence(sequence);
+        tcp_packet.set_data_offset(5);
+        tcp_packet.set_flags(flags);
+        tcp_packet.set_window(1024);
+        tcp_packet.set_payload(payload);
+        frame
+    }
+
+    fn arp_frame(operation: ArpOperation, sender_ip: Ipv4Addr, target_ip: Ipv4Addr) -> Vec<u8> {
+        let mut frame = ethernet(GPS_MAC, "ff:ff:ff:ff:ff:ff", EtherTypes::Arp, 28);
+        let mut arp_packet = MutableArpPacket::new(&mut frame[14..]).unwrap();
+        arp_packet.set_hardware_type(ArpHardwareTypes::Ethernet);
+        arp_packet.set_protocol_type(EtherTypes::Ipv4);
+        arp_packet.set_hw_addr_len(6);
+        arp_packet.set_proto_addr_len(4);
+        arp_packet.set_operation(operation);
+        arp_packet.set_sender_hw_addr(GPS_MAC.parse().unwrap());
+        arp_packet.set_sender_proto_addr(sender_ip);
+        arp_packet.set_target_proto_addr(target_ip);
+        frame
+    }
+
+    fn verdict(replay: &mut Replay, data: Vec<u8>) -> FrameVerdict {
+        let packet = PcapPacket {
+            interface: None,
+            timestamp: 0.0,
+            data,
+        };
+        replay.replay_frame(0, &packet)
+    }
+
+    // the components a frame was delivered to, in name order
+    fn delivered(replay: &mut Replay, data: Vec<u8>) -> Vec<String> {
+        match verdict(replay, data).verdict {
+            Verdict::Allowed { mut delivered_to } => {
+                delivered_to.sort();
+                delivered_to
+            }
+            other => panic!("frame not allowed: {:?}", other),
+        }
+    }
+
+    fn dropped(replay: &mut Replay, data: Vec<u8>) -> DropReason {
+        match verdict(replay, data).verdict {
+            Verdict::Dropped(reason) => reason,
+            other => panic!("frame not dropped: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_sentence_reaches_every_component_receiving_it() {
+        let mut replay = replay("allowed", "");
+        let frame_verdict = verdict(&mut replay, gps_frame(&gga()));
+        assert_eq!(frame_verdict.ingress.as_deref(), Some("gps"));
+        assert_eq!(frame_verdict.prefix, "$GPGGA");
+        assert_eq!(
+            delivered(&mut replay, gps_frame(&gga())),
+            ["ecdis", "radar"]
+        );
+        assert_eq!(delivered(&mut replay, gps_frame(&gll())), ["ecdis"]);
+    }
+
+    #[test]
+    fn a_sentence_the_component_does_not_send_is_dropped() {
+        let mut replay = replay("not-allowed", "");
+        assert_eq!(
+            dropped(&mut replay, gps_frame(&sentence("IIHDT,274.5,T"))),
+            DropReason::SentenceNotAllowed(String::from("$IIHDT"))
+        );
+    }
+
+    #[test]
+    fn spoofed_sources_are_dropped() {
+        let mut replay = replay("spoofed", "[binding]\nenforce = true\n");
+        let spoofed_ip = udp_frame(
+            RADAR_MAC,
+            Ipv4Addr::new(10, 42, 0, 99).into(),
+            RADAR_IP.into(),
+            &gga(),
+        );
+        assert!(matches!(
+            dropped(&mut replay, spoofed_ip),
+            DropReason::SpoofedSourceIp(_)
+        ));
+
+        let mut spoofed_mac = gps_frame(&gga());
+        spoofed_mac[11] = 0x99;
+        let packet = PcapPacket {
+            interface: Some(String::from("test3")),
+            timestamp: 0.0,
+            data: spoofed_mac,
+        };
+        assert!(matches!(
+            replay.replay_frame(0, &packet).verdict,
+            Verdict::Dropped(DropReason::SpoofedSourceMac(_))
+        ));
+
+        let gps = port(&replay, "gps");
+        replay.bindings[gps].as_mut().unwrap().source_ids = vec![String::from("GP0001")];
+        assert_eq!(
+            dropped(&mut replay, gps_frame(&gga())),
+            DropReason::SpoofedSourceId(String::new())
+        );
+    }
+
+    #[test]
+    fn arp_is_answered_or_checked_against_the_policy() {
+        let mut replay = replay("arp", "");
+        let request = arp_frame(ArpOperations::Request, GPS_IP, RADAR_IP);
+        assert_eq!(verdict(&mut replay, request).verdict, Verdict::Answered);
+
+        let claim = arp_frame(ArpOperations::Reply, RADAR_IP, RADAR_IP);
+        assert!(matches!(
+            dropped(&mut replay, claim),
+            DropReason::ArpSpoofing(_)
+        ));
+    }
+
+    #[test]
+    fn fragments_are_held_or_rejected() {
+        let mut replay = replay("fragments", "");
+        assert_eq!(
+            verdict(&mut replay, fragment(true, &[0u8; 64])).verdict,
+            Verdict::Held
+        );
+        assert_eq!(
+            dropped(&mut replay, fragment(true, &[0u8; 8])),
+            DropReason::Fragment(FragmentError::TinyFragment)
+        );
+    }
+
+    #[test]
+    fn invalid_and_denied_proprietary_sentences_are_dropped() {
+        let mut replay = replay(
+            "sentences",
+            "[validation]\n\n[proprietary]\ndeny = [\"$PGRM\"]\n",
+        );
+        let mut bad_checksum = gga();
+        let len = bad_checksum.len();
+        bad_checksum[len - 3] = b'0';
+        bad_checksum[len - 4] = b'0';
+        assert_eq!(
+            dropped(&mut replay, gps_frame(&bad_checksum)),
+            DropReason::InvalidSentence(Violation::BadChecksum)
+        );
+        assert_eq!(
+            dropped(&mut replay, gps_frame(&sentence("PGRMZ,246,f,3"))),
+            DropReason::ProprietaryDenied(String::from("$PGRMZ"))
+        );
+    }
+
+    #[test]
+    fn tcp_sentences_are_judged_against_the_receiver() {
+        let mut replay = replay("tcp", "");
+        assert_eq!(
+            dropped(&mut replay, tcp_frame(TcpFlags::ACK, 1, &gga())),
+            DropReason::TcpStream(TcpError::UntrackedFlow)
+        );
+
+        assert_eq!(
+            delivered(&mut replay, tcp_frame(TcpFlags::SYN, 0, &[])),
+            ["radar"]
+        );
+        assert_eq!(
+            delivered(&mut replay, tcp_frame(TcpFlags::ACK, 1, &gga())),
+            ["radar"]
+        );
+        // the ship resets the connection rather than hand radar a sentence it does not receive
+        let frame_verdict = verdict(
+            &mut replay,
+            tcp_frame(TcpFlags::ACK, 1 + gga().len() as u32, &gll()),
+        );
+        assert_eq!(frame_verdict.prefix, "$GPGLL");
+        assert_eq!(
+            frame_verdict.verdict,
+            Verdict::Dropped(DropReason::SentenceNotAllowed(String::from("$GPGLL")))
+        );
+    }
+
+    #[test]
+    fn group_traffic_reaches_the_subscribers_receiving_it() {
+        let mut replay = replay("group", "");
+        let navd = Ipv4Addr::new(239, 192, 0, 4);
+        let frame =
+            |payload: &[u8]| udp_frame("01:00:5e:40:00:04", GPS_IP.into(), navd.into(), payload);
+        assert_eq!(delivered(&mut replay, frame(&gga())), ["ecdis", "radar"]);
+        assert_eq!(delivered(&mut replay, frame(&gll())), ["ecdis"]);
+    }
+
+    #[test]
+    fn a_multicast_mac_without_a_group_is_not_delivered() {
+        let mut replay = replay("multicast-mac", "");
+        let frame = udp_frame("01:00:5e:00:00:01", GPS_IP.into(), RADAR_IP.into(), &gga());
+        assert!(delivered(&mut replay, frame).is_empty());
+    }
+
+    #[test]
+    fn ipv6_sentences_skip_receivers_without_an_ipv6_address() {
+        let mut replay = replay("ipv6", "");
+        let source: Ipv6Addr = "fd00::30".parse().unwrap();
+        let destination: Ipv6Addr = "fd00::60".parse().unwrap();
+        let frame = udp_frame(RADAR_MAC, source.into(), destination.into(), &gga());
+        assert_eq!(delivered(&mut replay, frame), ["ecdis"]);
+    }
+}
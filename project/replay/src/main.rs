@@ -0,0 +1,39 @@
+use policy_handler::PolicyHandler;
+use replay::{Replay, ReplaySummary};
+use std::path::PathBuf;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: {} <policy.toml> <capture.pcap>", args[0]);
+        std::process::exit(1);
+    }
+
+    let policy = match PolicyHandler::new(args[1].clone()) {
+        Ok(policy) => policy,
+        Err(e) => {
+            eprintln!("| {}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut replay = match Replay::from_policy(&policy) {
+        Ok(replay) => replay,
+        Err(e) => {
+            eprintln!("| {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let verdicts = match replay.replay_file(&PathBuf::from(&args[2])) {
+        Ok(verdicts) => verdicts,
+        Err(e) => {
+            eprintln!("| REPLAY OF {} FAILED: {}", args[2], e);
+            std::process::exit(1);
+        }
+    };
+
+    verdicts.iter().for_each(|verdict| println!("{}", verdict));
+
+    println!("----------------------------------");
+    println!("{}", ReplaySummary::new(&verdicts));
+}
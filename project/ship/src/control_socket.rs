@@ -0,0 +1,713 @@
+use crate::{Ship, DEFAULT_MTU};
+use control_protocol::{ControlRequest, REPLY_TIMEOUT};
+use firewall::{
+    format_mac, EntryKind, ProprietaryRules, Reassembler, SentenceSchemas, ShipSwitch,
+    SourceBinding, SwitchEntry, TcpInspector, TransmissionGroups,
+};
+use log::LevelFilter;
+use policy_handler::{ComponentConfig, PolicyHandler};
+use serde_json::{json, Value};
+use shipcomponent::{BucketRing, DropEvent};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fs::Permissions;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::Ipv4Addr;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// connections served at the same time, the others wait to be accepted
+const CONNECTION_WORKERS: usize = 4;
+// an idle client is disconnected so that it does not hold a worker forever
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+// bytes of a request line, a longer one is refused and its connection closed
+const MAX_REQUEST_LEN: usize = 64 * 1024;
+
+type PendingRequest = (ControlRequest, Sender<Value>);
+
+// accepts operator requests on a unix socket, they are answered by the monitoring loop
+// between two polls so that the ship state is never shared with another thread
+pub struct ControlSocket {
+    path: PathBuf,
+    pub policy_path: String,
+    requests: Receiver<PendingRequest>,
+}
+
+impl ControlSocket {
+    // the socket is only usable by its owner, and by the members of group when one is given
+    pub fn bind(path: &Path, policy_path: &str, group: Option<&str>) -> io::Result<Self> {
+        // a socket left behind by a previous run would make bind fail
+        if path.exists() && UnixStream::connect(path).is_err() {
+            std::fs::remove_file(path)?;
+        }
+
+        // created without any access for others, there is no window before the permissions are set
+        let umask = unsafe { libc::umask(0o177) };
+        let listener = UnixListener::bind(path);
+        unsafe { libc::umask(umask) };
+        let listener = listener?;
+        if let Some(group) = group {
+            if let Err(e) = share_with_group(path, group) {
+                let _ = std::fs::remove_file(path);
+                return Err(e);
+            }
+        }
+
+        let (sender, requests) = mpsc::channel();
+        for _ in 0..CONNECTION_WORKERS {
+            let listener = listener.try_clone()?;
+            let sender = sender.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    match serve(stream, &sender) {
+                        Ok(()) => {}
+                        Err(e)
+                            if matches!(
+                                e.kind(),
+                                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                            ) =>
+                        {
+                            log::debug!("| IDLE CONTROL CONNECTION CLOSED");
+                        }
+                        Err(e) => log::warn!("| CONTROL REQUEST FAILED: {}", e),
+                    }
+                }
+            });
+        }
+
+        Ok(ControlSocket {
+            path: path.to_path_buf(),
+            policy_path: policy_path.to_string(),
+            requests,
+        })
+    }
+}
+
+// the group of the socket, by name or id, gets to read and write it
+fn share_with_group(path: &Path, group: &str) -> io::Result<()> {
+    let gid = match group.parse::<u32>() {
+        Ok(gid) => gid,
+        Err(_) => {
+            let name = CString::new(group)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid group name"))?;
+            let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+            if entry.is_null() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("unknown group {}", group),
+                ));
+            }
+            unsafe { (*entry).gr_gid }
+        }
+    };
+    std::os::unix::fs::chown(path, None, Some(gid))?;
+    std::fs::set_permissions(path, Permissions::from_mode(0o660))
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn serve(stream: UnixStream, sender: &Sender<PendingRequest>) -> io::Result<()> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        // a client cannot make the worker buffer more than one bounded line
+        let mut line = String::new();
+        let read = (&mut reader)
+            .take(MAX_REQUEST_LEN as u64)
+            .read_line(&mut line)?;
+        if read == 0 {
+            return Ok(());
+        }
+        if read == MAX_REQUEST_LEN && !line.ends_with('\n') {
+            // the rest of the line cannot be told apart from the next request
+            let reply = failure(&format!("request longer than {} bytes", MAX_REQUEST_LEN));
+            writeln!(writer, "{}", reply)?;
+            return Ok(());
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let reply = match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(request) => {
+                let (reply_sender, reply) = mpsc::channel();
+                if sender.send((request, reply_sender)).is_err() {
+                    return Ok(());
+                }
+                reply
+                    .recv_timeout(REPLY_TIMEOUT)
+                    .unwrap_or_else(|_| failure("the ship did not answer in time"))
+            }
+            Err(e) => failure(&format!("invalid request: {}", e)),
+        };
+
+        writeln!(writer, "{}", reply)?;
+    }
+}
+
+fn success(result: Value) -> Value {
+    json!({ "ok": true, "result": result })
+}
+
+fn failure(error: &str) -> Value {
+    json!({ "ok": false, "error": error })
+}
+
+impl Ship<'_> {
+    pub(crate) fn serve_control_requests(&mut self, ship_switch: &mut ShipSwitch) {
+        let pending: Vec<PendingRequest> = match &self.control {
+            Some(control) => control.requests.try_iter().collect(),
+            None => return,
+        };
+
+        for (request, reply) in pending {
+            let response = match request {
+                ControlRequest::Stats => success(self.control_stats(ship_switch)),
+                ControlRequest::Switch => success(self.control_switch(ship_switch)),
+                ControlRequest::Rules => success(self.control_rules()),
+                ControlRequest::RecentDrops => success(self.control_recent_drops()),
+                ControlRequest::Reload { policy } => self.control_reload(policy, ship_switch),
+                ControlRequest::Enable { component } => self.control_enable(&component, true),
+                ControlRequest::Disable { component } => self.control_enable(&component, false),
+                ControlRequest::LogLevel { level } => match level.parse::<LevelFilter>() {
+                    Ok(level) => {
+                        log::set_max_level(level);
+                        success(json!({ "level": level.to_string().to_lowercase() }))
+                    }
+                    Err(_) => failure(&format!("unknown log level {}", level)),
+                },
+            };
+
+            // the client may have given up already
+            let _ = reply.send(response);
+        }
+    }
+
+    fn control_stats(&self, ship_switch: &ShipSwitch) -> Value {
+        let components: Vec<Value> = self
+            .components
+            .iter()
+            .map(|component| {
+                let stats = &component.stats;
+                json!({
+                    "name": component.name,
+                    "iface": component.ifname,
+                    "enabled": component.enabled,
+                    "packets_sent": stats.packets_sent,
+                    "packets_received": stats.packets_received,
+                    "bytes_sent": stats.total_bytes_sent as u64,
+                    "bytes_received": stats.total_bytes_received as u64,
+                    "throughput_sent_mbps": BucketRing::last_rate(&stats.buckets.throughput_sent()),
+                    "throughput_received_mbps": BucketRing::last_rate(&stats.buckets.throughput_received()),
+                    "packet_rate_sent": BucketRing::last_rate(&stats.buckets.packet_rate_sent()),
+                    "packet_rate_received": BucketRing::last_rate(&stats.buckets.packet_rate_received()),
+                    "drops": stats.drops.iter().collect::<BTreeMap<_, _>>(),
+                    "sentences": stats.sentences_sent.iter().collect::<BTreeMap<_, _>>(),
+                    "denied": stats.sentences_denied.iter().collect::<BTreeMap<_, _>>(),
+                    "reassembly": component.reassembler.as_ref().map(|reassembler| json!({
+                        "pending": reassembler.pending(),
+                        "reassembled": reassembler.reassembled,
+                        "timeouts": reassembler.timeouts,
+                    })),
+                    "tcp": component.tcp.as_ref().map(|tcp| json!({
+                        "flows": tcp.flows(),
+                        "resets": tcp.resets,
+                    })),
+                    "validation_alerts": component.validator.as_ref().map(|validator| validator.alerts),
+                    "proprietary_denied": component.proprietary.as_ref().map(|proprietary| proprietary.denied),
+                })
+            })
+            .collect();
+
+        json!({
+            "components": components,
+            "igmp": self.control_igmp(ship_switch),
+        })
+    }
+
+    // the multicast groups the ports joined, with the components behind them
+    fn control_igmp(&self, ship_switch: &ShipSwitch) -> Value {
+        let component_name = |port: usize| {
+            self.components
+                .get(port)
+                .map(|component| component.name.as_str())
+        };
+
+        let mut memberships: Vec<_> = ship_switch.igmp.iter().collect();
+        memberships.sort_by_key(|(group, _)| **group);
+        let groups: Vec<Value> = memberships
+            .into_iter()
+            .map(|(group, members)| {
+                let members: Vec<Value> = members
+                    .into_iter()
+                    .map(|(port, last_report)| {
+                        json!({
+                            "port": port,
+                            "component": component_name(port),
+                            "age_s": last_report.elapsed().as_secs_f64(),
+                        })
+                    })
+                    .collect();
+                json!({
+                    "group": group,
+                    "name": self.groups.by_address(group).map(|group| group.name.as_str()),
+                    "members": members,
+                })
+            })
+            .collect();
+
+        let routers: Vec<Value> = ship_switch
+            .igmp
+            .routers()
+            .into_iter()
+            .map(|port| json!({ "port": port, "component": component_name(port) }))
+            .collect();
+
+        json!({
+            "snooping": ship_switch.igmp.enabled,
+            "membership_interval_s": ship_switch.igmp.membership_interval.as_secs(),
+            "groups": groups,
+            "routers": routers,
+            "joins": ship_switch.igmp.joins,
+            "leaves": ship_switch.igmp.leaves,
+            "queries": ship_switch.igmp.queries,
+            "malformed": ship_switch.igmp.malformed,
+        })
+    }
+
+    fn control_recent_drops(&self) -> Value {
+        let mut drops: Vec<(&str, &DropEvent)> = self
+            .components
+            .iter()
+            .flat_map(|component| {
+                component
+                    .stats
+                    .recent_drops
+                    .iter()
+                    .map(|drop_event| (component.name.as_str(), drop_event))
+            })
+            .collect();
+        drops.sort_by(|a, b| a.1.at.total_cmp(&b.1.at));
+
+        let drops: Vec<Value> = drops
+            .into_iter()
+            .map(|(component, drop_event)| {
+                json!({
+                    "at": drop_event.at,
+                    "component": component,
+                    "prefix": drop_event.prefix,
+                    "kind": drop_event.reason.kind(),
+                    "reason": drop_event.reason.to_string(),
+                })
+            })
+            .collect();
+
+        json!({ "drops": drops })
+    }
+
+    fn control_switch(&self, ship_switch: &ShipSwitch) -> Value {
+        let mut entries: Vec<(usize, String, &SwitchEntry)> = ship_switch
+            .iter()
+            .map(|(mac, entry)| (entry.port, format_mac(mac), entry))
+            .collect();
+        entries.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+
+        let entries: Vec<Value> = entries
+            .into_iter()
+            .map(|(port, mac, entry)| {
+                json!({
+                    "mac": mac,
+                    "port": port,
+                    "component": self.components.get(port).map(|component| component.name.as_str()),
+                    "kind": match entry.kind {
+                        EntryKind::Static => "static",
+                        EntryKind::Dynamic => "dynamic",
+                    },
+                    "age_s": entry.last_seen.elapsed().as_secs_f64(),
+                })
+            })
+            .collect();
+
+        json!({
+            "entries": entries,
+            "aging_time_s": ship_switch.aging_time.as_secs(),
+            "max_macs_per_port": ship_switch.max_macs_per_port,
+            "moves": ship_switch.moves,
+            "static_conflicts": ship_switch.static_conflicts,
+            "port_limit_rejections": ship_switch.port_limit_rejections,
+            "aged_out": ship_switch.aged_out,
+            "arp": self.control_arp(ship_switch),
+        })
+    }
+
+    fn control_arp(&self, ship_switch: &ShipSwitch) -> Value {
+        let mut bindings: Vec<(&Ipv4Addr, usize, [u8; 6])> = ship_switch.arp.iter().collect();
+        bindings.sort();
+
+        let bindings: Vec<Value> = bindings
+            .into_iter()
+            .map(|(ip, port, mac)| {
+                json!({
+                    "ip": ip,
+                    "mac": format_mac(&mac),
+                    "port": port,
+                    "component": self.components.get(port).map(|component| component.name.as_str()),
+                })
+            })
+            .collect();
+
+        json!({
+            "bindings": bindings,
+            "proxy": ship_switch.arp.proxy,
+            "inspection": ship_switch.arp.inspection,
+            "proxy_replies": ship_switch.arp.proxy_replies,
+            "anomalies": ship_switch.arp.anomalies,
+        })
+    }
+
+    fn control_rules(&self) -> Value {
+        let rules: Vec<Value> = self
+            .components
+            .iter()
+            .map(|component| {
+                json!({
+                    "component": component.name,
+                    "iface": component.ifname,
+                    "mac": component.mac,
+                    "ip": component.ip,
+                    "ipv6": component.ipv6,
+                    "enabled": component.enabled,
+                    "binding": component.binding.as_ref().map(|binding| json!({
+                        "macs": binding.macs.iter().map(format_mac).collect::<Vec<String>>(),
+                        "ips": binding.ips,
+                        "source_ids": binding.source_ids,
+                    })),
+                    "sends": component.sends,
+                    "receives": component.receives,
+                    "groups": component.groups,
+                })
+            })
+            .collect();
+
+        json!({ "rules": rules })
+    }
+
+    // interfaces cannot be attached at runtime, so only the components already running are updated,
+    // the sections read when the ship starts are listed in the reply
+    fn control_reload(&mut self, policy: Option<String>, ship_switch: &mut ShipSwitch) -> Value {
+        let policy_path = match (policy, &self.control) {
+            (Some(policy), _) => policy,
+            (None, Some(control)) => control.policy_path.clone(),
+            (None, None) => return failure("no policy to reload"),
+        };
+
+        let policy = match PolicyHandler::new(policy_path.clone()) {
+            Ok(policy) => policy,
+            Err(e) => return failure(&e),
+        };
+        let problems = policy.validate();
+        if !problems.is_empty() {
+            return failure(&problems.join("; "));
+        }
+
+        let groups = match TransmissionGroups::configure(&policy.get_groups()) {
+            Ok(groups) => groups,
+            Err(e) => return failure(&e),
+        };
+        if let Some((rule, group)) = policy.get_policy().iter().find_map(|rule| {
+            rule.groups
+                .iter()
+                .find(|group| groups.by_name(group).is_none())
+                .map(|group| (rule.name.clone(), group.clone()))
+        }) {
+            return failure(&format!("{}: unknown group {}", rule, group));
+        }
+        let schemas = match SentenceSchemas::load(&policy.get_sentences().schemas) {
+            Ok(schemas) => Arc::new(schemas),
+            Err(e) => return failure(&format!("sentences: {}", e)),
+        };
+        // the components are set up as the ship sets them up when it starts
+        let mut rules = Vec::new();
+        for rule in policy.get_policy() {
+            match ComponentConfig::from_policy(&policy, &rule.name) {
+                Ok(config) => rules.push((rule, config)),
+                Err(e) => return failure(&e),
+            }
+        }
+
+        // nothing is changed before the whole policy is known to be usable
+        self.groups = groups;
+        self.schemas = schemas;
+        self.mtu = policy
+            .get_reassembly()
+            .map_or(DEFAULT_MTU, |reassembly| reassembly.mtu);
+
+        let defaults = ShipSwitch::new();
+        let switch = policy.get_switch();
+        ship_switch.aging_time = switch.as_ref().map_or(defaults.aging_time, |switch| {
+            Duration::from_secs(switch.aging_time)
+        });
+        ship_switch.max_macs_per_port = switch.map_or(defaults.max_macs_per_port, |switch| {
+            switch.max_macs_per_port
+        });
+        let arp = policy.get_arp();
+        ship_switch.arp.proxy = arp.proxy;
+        ship_switch.arp.inspection = arp.inspection;
+        let igmp = policy.get_igmp();
+        ship_switch.igmp.enabled = igmp.snooping;
+        ship_switch.igmp.membership_interval = Duration::from_secs(igmp.membership_interval);
+
+        // the components missing from the policy are left as they are,
+        // the others take its rules and keep the state they built up
+        let mut updated = Vec::new();
+        let mut not_attached = Vec::new();
+        for (rule, config) in rules {
+            match self
+                .components
+                .iter_mut()
+                .enumerate()
+                .find(|(_, component)| component.ifname == rule.iface)
+            {
+                Some((port, component)) => {
+                    component.binding = config.binding;
+                    component.schemas = config.schemas;
+                    component.multi_sentence = config.multi_sentence;
+                    let alerts = component
+                        .validator
+                        .as_ref()
+                        .map_or(0, |validator| validator.alerts);
+                    component.validator = config.validator.map(|mut validator| {
+                        validator.alerts = alerts;
+                        validator
+                    });
+                    let denied = component
+                        .proprietary
+                        .as_ref()
+                        .map_or(0, |proprietary| proprietary.denied);
+                    component.proprietary =
+                        config.proprietary.map(|proprietary| ProprietaryRules {
+                            denied,
+                            ..proprietary
+                        });
+                    component.reassembler =
+                        reload_reassembler(component.reassembler.take(), config.reassembler);
+                    component.tcp = reload_tcp(component.tcp.take(), config.tcp);
+
+                    ship_switch.arp.unbind(port);
+                    match SourceBinding::new(
+                        &rule.mac,
+                        &rule.ip,
+                        &rule.allowed_macs,
+                        &rule.allowed_source_ips(),
+                    ) {
+                        Ok(arp_binding) => ship_switch.arp.bind(port, &arp_binding),
+                        Err(e) => log::warn!("| NO ARP BINDING FOR {}: {}", rule.name, e),
+                    }
+                    ship_switch.remove_static(port);
+                    if let Err(e) = ship_switch.add_static_from_str(&rule.mac, port) {
+                        log::warn!("| NO STATIC SWITCH ENTRY FOR {}: {}", rule.name, e);
+                    }
+                    component.name = rule.name.clone();
+                    component.mac = rule.mac;
+                    component.ip = rule.ip;
+                    component.ipv6 = rule.ipv6;
+                    component.sends = rule.sends;
+                    component.receives = rule.receives;
+                    component.groups = config.groups;
+                    updated.push(rule.name);
+                }
+                None => not_attached.push(rule.name),
+            }
+        }
+
+        let missing: Vec<&str> = self
+            .components
+            .iter()
+            .filter(|component| !updated.contains(&component.name))
+            .map(|component| component.name.as_str())
+            .collect();
+
+        // read once when the ship starts
+        let restart_required: Vec<&str> = [
+            ("capture", policy.get_capture().is_some()),
+            ("audit", policy.get_audit().is_some()),
+            ("metrics", policy.get_metrics().is_some()),
+            ("control", policy.get_control().is_some()),
+            ("plots", policy.get_plots().is_some()),
+            ("report", policy.get_report().is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(section, present)| present.then_some(section))
+        .collect();
+
+        log::info!("| POLICY RELOADED FROM {}", policy_path);
+        success(json!({
+            "policy": policy_path,
+            "updated": updated,
+            "not_attached": not_attached,
+            "unchanged": missing,
+            "restart_required": restart_required,
+        }))
+    }
+
+    fn control_enable(&mut self, name: &str, enabled: bool) -> Value {
+        match self
+            .components
+            .iter_mut()
+            .find(|component| component.name == name || component.ifname == name)
+        {
+            Some(component) => {
+                component.enabled = enabled;
+                log::info!(
+                    "| COMPONENT {} {}",
+                    component.name,
+                    if enabled { "ENABLED" } else { "DISABLED" }
+                );
+                success(json!({ "component": component.name, "enabled": enabled }))
+            }
+            None => failure(&format!("unknown component {}", name)),
+        }
+    }
+}
+
+// the reassembler the policy configures, with the fragments the current one holds
+fn reload_reassembler(
+    current: Option<Reassembler>,
+    settings: Option<Reassembler>,
+) -> Option<Reassembler> {
+    let settings = settings?;
+    Some(match current {
+        Some(mut reassembler) => {
+            reassembler.timeout = settings.timeout;
+            reassembler.max_datagrams = settings.max_datagrams;
+            reassembler.max_datagram_size = settings.max_datagram_size;
+            reassembler.max_fragments = settings.max_fragments;
+            reassembler.min_fragment_size = settings.min_fragment_size;
+            reassembler
+        }
+        None => settings,
+    })
+}
+
+// the tcp inspector the policy configures, with the flows the current one follows
+fn reload_tcp(
+    current: Option<TcpInspector>,
+    settings: Option<TcpInspector>,
+) -> Option<TcpInspector> {
+    let settings = settings?;
+    Some(match current {
+        Some(mut inspector) => {
+            inspector.ports = settings.ports;
+            inspector.max_flows = settings.max_flows;
+            inspector.max_line_length = settings.max_line_length;
+            inspector.idle_timeout = settings.idle_timeout;
+            inspector
+        }
+        None => settings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use control_protocol::control_request;
+    use std::thread::JoinHandle;
+
+    fn socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("control-{}-{}.sock", std::process::id(), name))
+    }
+
+    // a ship without components answering on its control socket until the client is done
+    fn round_trip<F>(name: &str, client: F) -> Value
+    where
+        F: FnOnce(&Path) -> io::Result<Value> + Send + 'static,
+    {
+        let path = socket_path(name);
+        let mut ship = Ship::new(Vec::new());
+        ship.control = Some(ControlSocket::bind(&path, "policy.toml", None).unwrap());
+
+        let client_path = path.clone();
+        let client: JoinHandle<io::Result<Value>> = thread::spawn(move || client(&client_path));
+        let mut ship_switch = ShipSwitch::new();
+        while !client.is_finished() {
+            ship.serve_control_requests(&mut ship_switch);
+            thread::sleep(Duration::from_millis(10));
+        }
+        client.join().unwrap().unwrap()
+    }
+
+    // the reply to a line written as it is
+    fn raw_request(path: &Path, line: &[u8]) -> io::Result<Value> {
+        let mut stream = UnixStream::connect(path)?;
+        stream.write_all(line)?;
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply)?;
+        Ok(serde_json::from_str(&reply)?)
+    }
+
+    #[test]
+    fn stats_are_answered_by_the_ship() {
+        let reply = round_trip("stats", |path| {
+            control_request(path, &ControlRequest::Stats)
+        });
+        assert_eq!(reply["ok"], true);
+        assert_eq!(reply["result"]["components"], json!([]));
+    }
+
+    #[test]
+    fn unknown_components_cannot_be_enabled_or_disabled() {
+        for (name, request) in [
+            (
+                "enable",
+                ControlRequest::Enable {
+                    component: String::from("radar"),
+                },
+            ),
+            (
+                "disable",
+                ControlRequest::Disable {
+                    component: String::from("radar"),
+                },
+            ),
+        ] {
+            let reply = round_trip(name, move |path| control_request(path, &request));
+            assert_eq!(reply["ok"], false);
+            assert_eq!(reply["error"], "unknown component radar");
+        }
+    }
+
+    #[test]
+    fn malformed_requests_are_refused() {
+        let reply = round_trip("malformed", |path| {
+            raw_request(path, b"{\"command\": \"reboot\"}\n")
+        });
+        assert_eq!(reply["ok"], false);
+        assert!(reply["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid request"));
+    }
+
+    #[test]
+    fn oversized_requests_are_refused() {
+        let reply = round_trip("oversized", |path| {
+            raw_request(path, &vec![b'x'; MAX_REQUEST_LEN])
+        });
+        assert_eq!(reply["ok"], false);
+        assert_eq!(
+            reply["error"],
+            format!("request longer than {} bytes", MAX_REQUEST_LEN)
+        );
+    }
+}
@@ -1,21 +1,68 @@
-use pnet::packet::ethernet::{EthernetPacket, MutableEthernetPacket};
-use pnet::packet::ipv4::{Ipv4Packet, MutableIpv4Packet};
-use pnet::packet::udp::{MutableUdpPacket, UdpPacket};
-use pnet::packet::{MutablePacket, Packet};
-use pnet::util::MacAddr;
-use shipcomponent::ShipComponent;
+use firewall::{
+    AuditLog, Forwarder, Forwarding, Receiver, SentenceSchemas, ShipSwitch, SourceBinding,
+    TrafficEntry, TransmissionGroups,
+};
+use pcap_handler::Direction;
+use shipcomponent::{DropReason, ShipComponent};
 use std::collections::VecDeque;
-use std::net::Ipv4Addr;
-use std::str::FromStr;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+mod control_socket;
+mod metrics_exporter;
+pub use control_protocol::{control_request, ControlRequest};
+pub use control_socket::ControlSocket;
+pub use metrics_exporter::MetricsExporter;
+
+// with a control socket or metrics the loop wakes up this often even without traffic, in ms
+const CONTROL_POLL_INTERVAL: i32 = 100;
+const DEFAULT_MTU: usize = 1500;
 
 pub struct Ship<'a> {
     pub components: Vec<ShipComponent<'a>>,
+    pub audit_log: Option<AuditLog>,
+    pub metrics: Option<MetricsExporter>,
+    pub control: Option<ControlSocket>,
+    // forwarding database, seeded with the components' addresses from the policy
+    pub ship_switch: ShipSwitch,
+    // how long the network is monitored once the first frame arrives
+    pub duration: Duration,
+    // reassembled datagrams larger than this are fragmented again on their way out
+    pub mtu: usize,
+    // IEC 61162-450 multicast groups, traffic sent to them reaches their subscribers unchanged
+    pub groups: TransmissionGroups,
+    // the sentences recognised besides the builtin ones, shared with the components
+    pub schemas: Arc<SentenceSchemas>,
 }
 
 impl<'a> Ship<'a> {
     pub fn new(components: Vec<ShipComponent<'a>>) -> Self {
-        Ship { components }
+        let mut ship_switch = ShipSwitch::new();
+        components.iter().enumerate().for_each(|(port, component)| {
+            if let Err(e) = ship_switch.add_static_from_str(&component.mac, port) {
+                log::warn!("| NO STATIC SWITCH ENTRY FOR {}: {}", component.name, e);
+            }
+
+            // the arp bindings hold the extra addresses of the component when its source is bound
+            match component.binding.clone().map_or_else(
+                || SourceBinding::new(&component.mac, &component.ip, &[], &[]),
+                Ok,
+            ) {
+                Ok(binding) => ship_switch.arp.bind(port, &binding),
+                Err(e) => log::warn!("| NO ARP BINDING FOR {}: {}", component.name, e),
+            }
+        });
+
+        Ship {
+            components,
+            audit_log: None,
+            metrics: None,
+            control: None,
+            ship_switch,
+            duration: Duration::from_secs(30),
+            mtu: DEFAULT_MTU,
+            groups: TransmissionGroups::new(),
+            schemas: Arc::new(SentenceSchemas::default()),
+        }
     }
 
     pub fn monitor_network(&mut self) {
@@ -24,16 +71,27 @@ impl<'a> Ship<'a> {
             poll_fds.push(component.poll_fd);
         });
 
-        let mut ship_switch = hashbrown::HashMap::new();
+        // the switch is handed to the components next to &mut self while the loop runs
+        let mut ship_switch = std::mem::take(&mut self.ship_switch);
         let mut start_time = Instant::now();
         let mut first_time: bool = true;
+
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.refresh(&self.components, &ship_switch, self.audit_log.as_ref());
+        }
+
+        let poll_timeout = match self.control.is_some() || self.metrics.is_some() {
+            true => CONTROL_POLL_INTERVAL,
+            false => -1,
+        };
+
         loop {
             unsafe {
-                libc::poll(poll_fds.as_mut_ptr(), poll_fds.len() as _, -1);
+                libc::poll(poll_fds.as_mut_ptr(), poll_fds.len() as _, poll_timeout);
             }
 
             // prepare the structure for the network traffic
-            let mut ship_traffic: VecDeque<(usize, Vec<u8>, bool, String)> = VecDeque::new();
+            let mut ship_traffic: VecDeque<TrafficEntry> = VecDeque::new();
 
             for (poll_fd_index, _) in poll_fds
                 .iter()
@@ -54,6 +112,7 @@ impl<'a> Ship<'a> {
                         poll_fds.len(),
                         &mut ship_traffic,
                         &mut ship_switch,
+                        self.audit_log.as_mut(),
                         start_time,
                     );
                 }
@@ -70,7 +129,24 @@ impl<'a> Ship<'a> {
                 component.refill_fill_ring();
             });
 
-            if start_time.elapsed().as_secs() > Duration::from_secs(30).as_secs() {
+            ship_switch.age_out(Instant::now());
+
+            // the rates of the components that went quiet drop to zero
+            if !first_time {
+                let elapsed = start_time.elapsed();
+                self.components.iter_mut().for_each(|component| {
+                    component.stats.roll_to(elapsed);
+                });
+            }
+
+            if let Some(metrics) = self.metrics.as_mut() {
+                metrics.refresh(&self.components, &ship_switch, self.audit_log.as_ref());
+            }
+
+            self.serve_control_requests(&mut ship_switch);
+
+            if !first_time && start_time.elapsed() > self.duration {
+                self.ship_switch = ship_switch;
                 return;
             }
         }
@@ -78,66 +154,103 @@ impl<'a> Ship<'a> {
 
     pub fn send_traffic(
         &mut self,
-        ship_traffic: &VecDeque<(usize, Vec<u8>, bool, String)>,
-        ship_switch: &hashbrown::HashMap<[u8; 6], usize>,
+        ship_traffic: &VecDeque<TrafficEntry>,
+        ship_switch: &ShipSwitch,
         start_time: Instant,
     ) {
+        let receivers: Vec<Receiver> = self
+            .components
+            .iter()
+            .map(|component| Receiver {
+                name: &component.name,
+                mac: &component.mac,
+                ip: &component.ip,
+                ipv6: component.ipv6.as_deref(),
+                receives: &component.receives,
+                groups: &component.groups,
+            })
+            .collect();
+        let forwarder = Forwarder {
+            receivers: &receivers,
+            groups: &self.groups,
+            ship_switch,
+            schemas: &self.schemas,
+        };
+        // decided before anything is sent, the components are borrowed by the forwarder until then
+        let forwardings: Vec<Forwarding> = ship_traffic
+            .iter()
+            .map(|entry| forwarder.forward(entry, self.components[entry.ingress].multi_sentence))
+            .collect();
+
         ship_traffic
             .iter()
-            .for_each(|(destination_poll_fd_index, data, is_nmea, prefix)| {
-                if *is_nmea {
-                    // println!("| FILTERED AND MULTICAST FLOW");
-                    // the nmea sentence should be multicasted to all ship's components that can receive it
-                    self.transmit_multicast(data, ship_switch, prefix, start_time);
-                } else {
-                    // println!("| NORMAL FLOW");
-                    self.transmit(destination_poll_fd_index, data, start_time);
-                }
+            .zip(forwardings)
+            .for_each(|(entry, forwarding)| match forwarding {
+                Forwarding::Deliver(frames) => frames.iter().for_each(|(port, data)| {
+                    self.transmit(port, data, entry, start_time);
+                }),
+                Forwarding::Reset(denied) => self.reset_stream(entry, denied, start_time),
             });
     }
 
-    fn transmit_multicast(
+    // the segment goes no further and both ends of its connection are reset
+    fn reset_stream(&mut self, entry: &TrafficEntry, denied: String, start_time: Instant) {
+        let resets = self.components[entry.ingress].reset_connection(&entry.data);
+
+        let destination = &mut self.components[entry.destination];
+        log::warn!(
+            "| {} DOES NOT RECEIVE {}, TCP CONNECTION RESET",
+            destination.name,
+            denied
+        );
+        Self::record_tx_drop(
+            destination,
+            &entry.data,
+            entry,
+            start_time,
+            DropReason::SentenceNotAllowed(denied),
+        );
+
+        if let Some((to_sender, to_receiver)) = resets {
+            self.transmit(&entry.ingress, &to_sender, entry, start_time);
+            self.transmit(&entry.destination, &to_receiver, entry, start_time);
+        }
+    }
+
+    fn transmit(
         &mut self,
-        data: &Vec<u8>,
-        ship_switch: &hashbrown::HashMap<[u8; 6], usize>,
-        prefix: &String,
+        destination_poll_fd_index: &usize,
+        data: &[u8],
+        entry: &TrafficEntry,
         start_time: Instant,
     ) {
-        for i in 0..self.components.len() {
-            if self.components[i].receives.contains(prefix) {
-                let destination_mac = MacAddr::from_str(self.components[i].mac.as_str());
-                let destination_ip = Ipv4Addr::from_str(self.components[i].ip.as_str());
-
-                let (destination_mac, destination_ip) = match (destination_mac, destination_ip) {
-                    (Ok(mac), Ok(ip)) => (mac, ip),
-                    _ => {
-                        eprintln!("ERROR PARSING MAC OR IP ADDRESS FOR MULTICAST");
-                        return;
-                    }
-                };
-
-                let new_destination_poll_fd_index = match ship_switch.get(&destination_mac.octets())
-                {
-                    Some(index) => index,
-                    None => continue, // Skip if MAC address is not found in the map
-                };
-
-                let new_packet = self.forge_packet(data, destination_mac, destination_ip);
-                let new_packet = match new_packet {
-                    Some(packet) if !packet.is_empty() => packet,
-                    _ => {
-                        eprintln!("ERROR FORGING PACKET FOR MULTICAST");
-                        return;
-                    }
-                };
-
-                self.transmit(new_destination_poll_fd_index, &new_packet, start_time);
-            }
+        match firewall::fragment_ipv4(data, self.mtu) {
+            Some(fragments) => fragments.iter().for_each(|fragment| {
+                self.transmit_frame(destination_poll_fd_index, fragment, entry, start_time)
+            }),
+            None => self.transmit_frame(destination_poll_fd_index, data, entry, start_time),
         }
     }
 
-    fn transmit(&mut self, destination_poll_fd_index: &usize, data: &Vec<u8>, start_time: Instant) {
+    fn transmit_frame(
+        &mut self,
+        destination_poll_fd_index: &usize,
+        data: &[u8],
+        entry: &TrafficEntry,
+        start_time: Instant,
+    ) {
         let current_component = &mut self.components[*destination_poll_fd_index];
+        if !current_component.enabled {
+            Self::record_tx_drop(
+                current_component,
+                data,
+                entry,
+                start_time,
+                DropReason::ComponentDisabled,
+            );
+            return;
+        }
+
         match current_component.umem_allocator.try_allocate() {
             Some(chunk_index) => {
                 // memory for transmission is allocated, needs to be set up
@@ -152,81 +265,69 @@ impl<'a> Ship<'a> {
                     Some(data.len() as _),
                 );
 
-                tx_slice.copy_from_slice(&data);
+                tx_slice.copy_from_slice(data);
                 current_component.sock.tx_ring.advance_producer_index();
                 match current_component.sock.wake_for_transmission() {
                     Ok(()) => {
-                        current_component.stats.total_bytes_received += data.len() as f64;
-                        current_component.stats.performance_receive.push((
-                            start_time.elapsed().as_secs_f64(),
-                            ((current_component.stats.total_bytes_received * 8.0)
-                                / start_time.elapsed().as_secs_f64())
-                                / 1000000.0,
-                        ));
+                        // only frames handed to the socket were forwarded, failed sends take no sample
+                        current_component
+                            .stats
+                            .record_latency(entry.ingress, entry.received_at.elapsed());
+                        current_component
+                            .stats
+                            .record_received(data.len(), start_time.elapsed());
+                        Self::record_tx(current_component, data, None);
                     }
 
-                    Err(_) => println!(
-                        "| TRANSMISSION USING SOCK {} FAILED",
-                        destination_poll_fd_index
-                    ),
+                    Err(_) => {
+                        log::warn!(
+                            "| TRANSMISSION USING SOCK {} FAILED",
+                            destination_poll_fd_index
+                        );
+                        Self::record_tx_drop(
+                            current_component,
+                            data,
+                            entry,
+                            start_time,
+                            DropReason::TxWakeupFailed,
+                        );
+                    }
                 }
             }
 
             None => {
-                println!("| MEMORY ALLOCATION FOR TRANSMISSION FAILED");
+                log::warn!("| MEMORY ALLOCATION FOR TRANSMISSION FAILED");
+                Self::record_tx_drop(
+                    current_component,
+                    data,
+                    entry,
+                    start_time,
+                    DropReason::TxAllocationFailed,
+                );
             }
         }
     }
 
-    fn forge_packet(
-        &self,
+    fn record_tx_drop(
+        component: &mut ShipComponent,
         data: &[u8],
-        destination_mac: MacAddr,
-        destination_ip: Ipv4Addr,
-    ) -> Option<Vec<u8>> {
-        // Parse the Ethernet packet
-        let ethernet_packet = EthernetPacket::new(data)?;
-        let mut new_packet_buffer = vec![0u8; data.len()];
-        let mut new_ethernet_packet = MutableEthernetPacket::new(&mut new_packet_buffer)?;
-
-        new_ethernet_packet.clone_from(&ethernet_packet);
-        new_ethernet_packet.set_destination(destination_mac);
-
-        // Parse the IPv4 payload
-        let ipv4_payload = new_ethernet_packet.payload();
-        let ipv4_packet = Ipv4Packet::new(ipv4_payload)?;
-        let mut ipv4_buffer = vec![0u8; ipv4_payload.len()];
-        let mut new_ipv4_packet = MutableIpv4Packet::new(&mut ipv4_buffer)?;
-
-        new_ipv4_packet.clone_from(&ipv4_packet);
-        new_ipv4_packet.set_destination(destination_ip);
-
-        // Recalculate the IPv4 checksum
-        new_ipv4_packet.set_checksum(0); // Reset checksum before calculation
-        let checksum = pnet::packet::ipv4::checksum(&new_ipv4_packet.to_immutable());
-        new_ipv4_packet.set_checksum(checksum);
-
-        // Parse the UDP payload
-        let udp_payload = new_ipv4_packet.payload();
-        let udp_packet = UdpPacket::new(udp_payload)?;
-        let mut udp_buffer = vec![0u8; udp_payload.len()];
-        let mut new_udp_packet = MutableUdpPacket::new(&mut udp_buffer)?;
-
-        new_udp_packet.clone_from(&udp_packet);
-        new_udp_packet.set_checksum(0);
-
-        // Calculate the UDP checksum
-        let udp_checksum = pnet::packet::udp::ipv4_checksum(
-            &new_udp_packet.to_immutable(),
-            &new_ipv4_packet.get_source(),
-            &new_ipv4_packet.get_destination(),
-        );
-        new_udp_packet.set_checksum(udp_checksum);
-
-        // Assemble the final packet
-        new_ipv4_packet.set_payload(new_udp_packet.packet());
-        new_ethernet_packet.set_payload(new_ipv4_packet.packet());
+        entry: &TrafficEntry,
+        start_time: Instant,
+        drop_reason: DropReason,
+    ) {
+        component
+            .stats
+            .record_drop(&drop_reason, &entry.prefix, start_time.elapsed());
+        Self::record_tx(component, data, Some(&drop_reason));
+    }
 
-        Some(new_packet_buffer)
+    // only frames handed to the destination socket are captured as forwarded
+    fn record_tx(component: &mut ShipComponent, data: &[u8], drop_reason: Option<&DropReason>) {
+        if let Some(recorder) = component.recorder.as_mut() {
+            let comment = drop_reason.map(|reason| reason.to_string());
+            if let Err(e) = recorder.record(data, Direction::Outbound, comment.as_deref()) {
+                log::error!("| CAPTURE ON {} FAILED: {}", component.ifname, e);
+            }
+        }
     }
 }
@@ -0,0 +1,549 @@
+use firewall::{AuditLog, EntryKind, ShipSwitch};
+use shipcomponent::{BucketRing, ShipComponent, ShipComponentStats};
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+// serves the latest rendering of the ship's counters on http://<address>/metrics,
+// the snapshot is refreshed by the monitoring loop so the exporter never touches the sockets
+pub struct MetricsExporter {
+    snapshot: Arc<Mutex<String>>,
+    refresh_interval: Duration,
+    last_refresh: Option<Instant>,
+}
+
+impl MetricsExporter {
+    pub fn start(address: &str, refresh_interval: Duration) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let snapshot = Arc::new(Mutex::new(String::from("# EOF\n")));
+
+        let served_snapshot = snapshot.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = serve(stream, &served_snapshot) {
+                    log::warn!("| METRICS REQUEST FAILED: {}", e);
+                }
+            }
+        });
+
+        Ok(MetricsExporter {
+            snapshot,
+            refresh_interval,
+            last_refresh: None,
+        })
+    }
+
+    pub fn refresh(
+        &mut self,
+        components: &[ShipComponent],
+        ship_switch: &ShipSwitch,
+        audit_log: Option<&AuditLog>,
+    ) {
+        if let Some(last_refresh) = self.last_refresh {
+            if last_refresh.elapsed() < self.refresh_interval {
+                return;
+            }
+        }
+
+        let rendered = render(components, ship_switch, audit_log);
+        if let Ok(mut snapshot) = self.snapshot.lock() {
+            *snapshot = rendered;
+        }
+        self.last_refresh = Some(Instant::now());
+    }
+}
+
+fn serve(mut stream: TcpStream, snapshot: &Arc<Mutex<String>>) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+
+    // only the request line matters, headers and body are ignored
+    let mut request = [0u8; 1024];
+    let read = stream.read(&mut request)?;
+    let request = String::from_utf8_lossy(&request[..read]);
+    let mut request_line = request.lines().next().unwrap_or("").split_whitespace();
+
+    let response = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let body = snapshot
+                .lock()
+                .map(|snapshot| snapshot.clone())
+                .unwrap_or_default();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                CONTENT_TYPE,
+                body.len(),
+                body
+            )
+        }
+        (Some("GET"), Some(_)) => {
+            String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+        }
+        _ => String::from(
+            "HTTP/1.1 405 Method Not Allowed\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ),
+    };
+
+    stream.write_all(response.as_bytes())
+}
+
+fn render(
+    components: &[ShipComponent],
+    ship_switch: &ShipSwitch,
+    audit_log: Option<&AuditLog>,
+) -> String {
+    let mut out = String::new();
+
+    counter_family(
+        &mut out,
+        "l7_component_sent_packets",
+        "Frames sent by the component into the firewall.",
+        components,
+        |component| vec![(String::new(), component.stats.packets_sent)],
+    );
+    counter_family(
+        &mut out,
+        "l7_component_sent_bytes",
+        "Bytes sent by the component into the firewall.",
+        components,
+        |component| vec![(String::new(), component.stats.total_bytes_sent as u64)],
+    );
+    counter_family(
+        &mut out,
+        "l7_component_received_packets",
+        "Frames delivered by the firewall to the component.",
+        components,
+        |component| vec![(String::new(), component.stats.packets_received)],
+    );
+    counter_family(
+        &mut out,
+        "l7_component_received_bytes",
+        "Bytes delivered by the firewall to the component.",
+        components,
+        |component| vec![(String::new(), component.stats.total_bytes_received as u64)],
+    );
+    counter_family(
+        &mut out,
+        "l7_component_sentences",
+        "NMEA sentences sent by the component, by sentence.",
+        components,
+        |component| {
+            component
+                .stats
+                .sentences_sent
+                .iter()
+                .map(|(prefix, count)| (format!(",sentence=\"{}\"", escape(prefix)), *count))
+                .collect()
+        },
+    );
+    counter_family(
+        &mut out,
+        "l7_component_drops",
+        "Frames dropped by the firewall, by reason.",
+        components,
+        |component| {
+            component
+                .stats
+                .drops
+                .iter()
+                .map(|(reason, count)| (format!(",reason=\"{}\"", reason), *count))
+                .collect()
+        },
+    );
+    counter_family(
+        &mut out,
+        "l7_component_reassembled_datagrams",
+        "IPv4 datagrams reassembled from the fragments sent by the component.",
+        components,
+        |component| {
+            vec![(
+                String::new(),
+                component
+                    .reassembler
+                    .as_ref()
+                    .map_or(0, |reassembler| reassembler.reassembled),
+            )]
+        },
+    );
+    counter_family(
+        &mut out,
+        "l7_component_tcp_resets",
+        "TCP connections of the component reset for carrying forbidden sentences.",
+        components,
+        |component| {
+            vec![(
+                String::new(),
+                component.tcp.as_ref().map_or(0, |tcp| tcp.resets),
+            )]
+        },
+    );
+    counter_family(
+        &mut out,
+        "l7_component_sentence_alerts",
+        "Sentences of the component breaking a validation rule whose action is alert.",
+        components,
+        |component| {
+            vec![(
+                String::new(),
+                component
+                    .validator
+                    .as_ref()
+                    .map_or(0, |validator| validator.alerts),
+            )]
+        },
+    );
+    counter_family(
+        &mut out,
+        "l7_component_umem_allocation_failures",
+        "Transmissions aborted because no umem chunk was available.",
+        components,
+        |component| vec![(String::new(), component.stats.umem_allocation_failures)],
+    );
+    counter_family(
+        &mut out,
+        "l7_component_tx_failures",
+        "Transmissions the kernel refused to wake up for.",
+        components,
+        |component| vec![(String::new(), component.stats.tx_failures)],
+    );
+    counter_family(
+        &mut out,
+        "l7_component_rate_limited",
+        "Frames dropped because the component reached a limit of the firewall, like its reassembly table or its tcp flows.",
+        components,
+        |component| vec![(String::new(), component.stats.rate_limited)],
+    );
+
+    gauge_family(
+        &mut out,
+        "l7_component_sent_throughput_mbps",
+        "Throughput sent by the component over the last completed interval.",
+        components,
+        |component| BucketRing::last_rate(&component.stats.buckets.throughput_sent()),
+    );
+    gauge_family(
+        &mut out,
+        "l7_component_received_throughput_mbps",
+        "Throughput delivered to the component over the last completed interval.",
+        components,
+        |component| BucketRing::last_rate(&component.stats.buckets.throughput_received()),
+    );
+    gauge_family(
+        &mut out,
+        "l7_component_sent_packet_rate",
+        "Packets per second sent by the component over the last completed interval.",
+        components,
+        |component| BucketRing::last_rate(&component.stats.buckets.packet_rate_sent()),
+    );
+    gauge_family(
+        &mut out,
+        "l7_component_received_packet_rate",
+        "Packets per second delivered to the component over the last completed interval.",
+        components,
+        |component| BucketRing::last_rate(&component.stats.buckets.packet_rate_received()),
+    );
+
+    let stats: Vec<(&str, &ShipComponentStats)> = components
+        .iter()
+        .map(|component| (component.name.as_str(), &component.stats))
+        .collect();
+    latency_families(&mut out, &stats);
+
+    switch_families(&mut out, ship_switch);
+
+    if let Some(audit_log) = audit_log {
+        let _ = writeln!(out, "# TYPE l7_audit_suppressed_events counter");
+        let _ = writeln!(
+            out,
+            "# HELP l7_audit_suppressed_events Audit events left out to keep the audit log within its rate limit."
+        );
+        let _ = writeln!(
+            out,
+            "l7_audit_suppressed_events_total {}",
+            audit_log.suppressed_total
+        );
+    }
+
+    out.push_str("# EOF\n");
+    out
+}
+
+fn switch_families(out: &mut String, ship_switch: &ShipSwitch) {
+    let _ = writeln!(out, "# TYPE l7_switch_entries gauge");
+    let _ = writeln!(
+        out,
+        "# HELP l7_switch_entries MAC addresses in the forwarding database."
+    );
+    for (kind, label) in [
+        (EntryKind::Static, "static"),
+        (EntryKind::Dynamic, "dynamic"),
+    ] {
+        let _ = writeln!(
+            out,
+            "l7_switch_entries{{kind=\"{}\"}} {}",
+            label,
+            ship_switch
+                .iter()
+                .filter(|(_, entry)| entry.kind == kind)
+                .count()
+        );
+    }
+
+    for (name, help, value) in [
+        (
+            "l7_switch_mac_moves",
+            "Learned MAC addresses that showed up on another port.",
+            ship_switch.moves,
+        ),
+        (
+            "l7_switch_static_conflicts",
+            "Static MAC addresses seen on a port other than their own.",
+            ship_switch.static_conflicts,
+        ),
+        (
+            "l7_switch_port_limit_rejections",
+            "MAC addresses not learned because their port reached its limit.",
+            ship_switch.port_limit_rejections,
+        ),
+        (
+            "l7_switch_aged_out",
+            "Learned MAC addresses forgotten after the aging time.",
+            ship_switch.aged_out,
+        ),
+        (
+            "l7_arp_proxy_replies",
+            "ARP requests answered by the ship on behalf of a component.",
+            ship_switch.arp.proxy_replies,
+        ),
+        (
+            "l7_arp_anomalies",
+            "ARP frames dropped for contradicting the policy.",
+            ship_switch.arp.anomalies,
+        ),
+        (
+            "l7_igmp_joins",
+            "Multicast groups joined by a port, learned from IGMP reports.",
+            ship_switch.igmp.joins,
+        ),
+        (
+            "l7_igmp_leaves",
+            "Multicast groups left by a port, learned from IGMP reports.",
+            ship_switch.igmp.leaves,
+        ),
+        (
+            "l7_igmp_queries",
+            "IGMP membership queries seen from multicast routers.",
+            ship_switch.igmp.queries,
+        ),
+        (
+            "l7_igmp_malformed",
+            "IGMP messages ignored for being truncated or having a wrong checksum.",
+            ship_switch.igmp.malformed,
+        ),
+    ] {
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "{}_total {}", name, value);
+    }
+}
+
+// writes one counter family, the closure returns the extra labels and value of each sample
+fn counter_family<F>(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    components: &[ShipComponent],
+    samples: F,
+) where
+    F: Fn(&ShipComponent) -> Vec<(String, u64)>,
+{
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    components.iter().for_each(|component| {
+        samples(component).iter().for_each(|(labels, value)| {
+            let _ = writeln!(
+                out,
+                "{}_total{{component=\"{}\"{}}} {}",
+                name,
+                escape(&component.name),
+                labels,
+                value
+            );
+        });
+    });
+}
+
+// forwarding latency of every ingress/egress pair, as a summary plus its maximum
+fn latency_families(out: &mut String, components: &[(&str, &ShipComponentStats)]) {
+    let mut pairs = Vec::new();
+    components.iter().for_each(|(egress, stats)| {
+        stats.latency.iter().for_each(|(ingress, histogram)| {
+            let ingress = components
+                .get(*ingress)
+                .map(|(name, _)| *name)
+                .unwrap_or("unknown");
+            pairs.push((
+                format!(
+                    "ingress=\"{}\",egress=\"{}\"",
+                    escape(ingress),
+                    escape(egress)
+                ),
+                histogram,
+            ));
+        });
+    });
+    pairs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let _ = writeln!(out, "# TYPE l7_forwarding_latency_seconds summary");
+    let _ = writeln!(
+        out,
+        "# HELP l7_forwarding_latency_seconds Time from the ingress rx ring to the egress tx ring."
+    );
+    pairs.iter().for_each(|(labels, histogram)| {
+        for quantile in [0.5, 0.99, 0.999] {
+            let _ = writeln!(
+                out,
+                "l7_forwarding_latency_seconds{{{},quantile=\"{}\"}} {}",
+                labels,
+                quantile,
+                histogram.value_at_quantile(quantile) as f64 / 1e9
+            );
+        }
+        let _ = writeln!(
+            out,
+            "l7_forwarding_latency_seconds_sum{{{}}} {}",
+            labels,
+            histogram.mean() * histogram.len() as f64 / 1e9
+        );
+        let _ = writeln!(
+            out,
+            "l7_forwarding_latency_seconds_count{{{}}} {}",
+            labels,
+            histogram.len()
+        );
+    });
+
+    let _ = writeln!(out, "# TYPE l7_forwarding_latency_max_seconds gauge");
+    let _ = writeln!(
+        out,
+        "# HELP l7_forwarding_latency_max_seconds Highest forwarding latency observed."
+    );
+    pairs.iter().for_each(|(labels, histogram)| {
+        let _ = writeln!(
+            out,
+            "l7_forwarding_latency_max_seconds{{{}}} {}",
+            labels,
+            histogram.max() as f64 / 1e9
+        );
+    });
+}
+
+fn gauge_family<F>(out: &mut String, name: &str, help: &str, components: &[ShipComponent], value: F)
+where
+    F: Fn(&ShipComponent) -> f64,
+{
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    components.iter().for_each(|component| {
+        let _ = writeln!(
+            out,
+            "{}{{component=\"{}\"}} {}",
+            name,
+            escape(&component.name),
+            value(component)
+        );
+    });
+}
+
+fn escape(label_value: &str) -> String {
+    label_value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the families of a rendering, by name and type
+    fn families(rendered: &str) -> Vec<(&str, &str)> {
+        rendered
+            .lines()
+            .filter_map(|line| line.strip_prefix("# TYPE "))
+            .filter_map(|family| family.split_once(' '))
+            .collect()
+    }
+
+    #[test]
+    fn the_rendering_is_openmetrics() {
+        let path = std::env::temp_dir().join(format!("metrics-{}-audit.jsonl", std::process::id()));
+        let audit_log =
+            AuditLog::new(&path.to_string_lossy(), firewall::AuditLevel::Drops, 0, 1).unwrap();
+        let rendered = render(&[], &ShipSwitch::new(), Some(&audit_log));
+        std::fs::remove_file(&path).unwrap();
+
+        let families = families(&rendered);
+        for family in [
+            ("l7_component_sent_packets", "counter"),
+            ("l7_component_drops", "counter"),
+            ("l7_component_rate_limited", "counter"),
+            ("l7_component_tx_failures", "counter"),
+            ("l7_component_sent_throughput_mbps", "gauge"),
+            ("l7_forwarding_latency_seconds", "summary"),
+            ("l7_switch_entries", "gauge"),
+            ("l7_switch_port_limit_rejections", "counter"),
+            ("l7_audit_suppressed_events", "counter"),
+        ] {
+            assert!(families.contains(&family), "{:?} missing", family);
+        }
+
+        // every family is described and counter samples carry the _total suffix
+        for (name, kind) in families.iter() {
+            assert!(rendered.contains(&format!("# HELP {} ", name)));
+            let samples: Vec<&str> = rendered
+                .lines()
+                .filter(|line| !line.starts_with('#') && line.starts_with(name))
+                .collect();
+            if *kind == "counter" {
+                assert!(samples
+                    .iter()
+                    .all(|sample| sample.starts_with(&format!("{}_total", name))));
+            }
+        }
+        assert!(rendered.contains("l7_switch_port_limit_rejections_total 0\n"));
+        assert!(rendered.contains("l7_audit_suppressed_events_total 0\n"));
+        assert!(rendered.ends_with("# EOF\n"));
+        assert_eq!(rendered.matches("# EOF").count(), 1);
+    }
+
+    #[test]
+    fn latencies_are_summaries_by_quantile() {
+        let gps = ShipComponentStats::new();
+        let mut radar = ShipComponentStats::new();
+        radar.record_latency(0, Duration::from_micros(20));
+        radar.record_latency(0, Duration::from_micros(40));
+
+        let mut rendered = String::new();
+        latency_families(&mut rendered, &[("gps", &gps), ("radar", &radar)]);
+
+        let labels = "ingress=\"gps\",egress=\"radar\"";
+        for quantile in ["0.5", "0.99", "0.999"] {
+            assert!(rendered.contains(&format!(
+                "l7_forwarding_latency_seconds{{{},quantile=\"{}\"}} ",
+                labels, quantile
+            )));
+        }
+        assert!(rendered.contains(&format!(
+            "l7_forwarding_latency_seconds_count{{{}}} 2\n",
+            labels
+        )));
+        assert!(rendered.contains(&format!("l7_forwarding_latency_seconds_sum{{{}}} ", labels)));
+        assert!(rendered.contains(&format!("l7_forwarding_latency_max_seconds{{{}}} ", labels)));
+        assert!(!rendered.contains("egress=\"gps\""));
+    }
+}
@@ -1,17 +1,24 @@
-use nmea::Nmea;
-use packet_parser::PacketParser;
+use firewall::{
+    AuditLog, FragmentError, Inspection, Inspector, MultiSentence, ProprietaryRules, Reassembler,
+    SentenceSchemas, SentenceValidator, ShipSwitch, SourceBinding, TcpInspector, TrafficEntry,
+};
+use pcap_handler::{Direction, PcapRecorder};
 use std::sync::Arc;
 use std::{collections::VecDeque, os::fd::AsRawFd};
 use xdrippi::{utils::interface_name_to_index, BPFRedirectManager, Umem, UmemAllocator, XDPSocket};
 mod utils;
+pub use firewall::DropReason;
 use std::time::Instant;
-use utils::ship_component_stats::ShipComponentStats;
+pub use utils::bucket_ring::{BucketRing, RateSummary, StatsBucket};
+pub use utils::plots::{plot_dashboard, Chart, ImageFormat, PlotConfig};
+pub use utils::ship_component_stats::{DropEvent, ShipComponentStats};
 
 pub struct ShipComponent<'a> {
     pub name: String,
     pub ifname: String,
     pub mac: String,
     pub ip: String,
+    pub ipv6: Option<String>,
     pub ifindex: libc::c_uint,
     pub bpf_manager: BPFRedirectManager,
     pub sock: XDPSocket<'a>,
@@ -20,6 +27,25 @@ pub struct ShipComponent<'a> {
     pub sends: Vec<String>,
     pub receives: Vec<String>,
     pub stats: ShipComponentStats,
+    pub recorder: Option<PcapRecorder>,
+    // a disabled component neither sends nor receives anything
+    pub enabled: bool,
+    // source addresses the frames coming from the component must carry, none disables the check
+    pub binding: Option<SourceBinding>,
+    // ipv4 fragments are held here until their datagram is whole, none forwards them uninspected
+    pub reassembler: Option<Reassembler>,
+    // follows the nmea carried over tcp, none forwards tcp uninspected
+    pub tcp: Option<TcpInspector>,
+    // what happens to a datagram packing a forbidden sentence with allowed ones
+    pub multi_sentence: MultiSentence,
+    // holds the sentences to the syntax of IEC 61162-1, none only asks the nmea parser
+    pub validator: Option<SentenceValidator>,
+    // proprietary sentences denied ship wide, none leaves them to the sends of the component
+    pub proprietary: Option<ProprietaryRules>,
+    // IEC 61162-450 transmission groups the component is subscribed to
+    pub groups: Vec<String>,
+    // the sentences recognised besides the builtin ones, shared with the ship
+    pub schemas: Arc<SentenceSchemas>,
 }
 
 impl ShipComponent<'_> {
@@ -30,16 +56,19 @@ impl ShipComponent<'_> {
         ip: String,
         sends: Vec<String>,
         receives: Vec<String>,
-    ) -> Self {
+    ) -> Result<Self, String> {
         // Getting interface index
-        let ifindex = interface_name_to_index(ifname.as_str()).unwrap();
+        let ifindex = interface_name_to_index(ifname.as_str())
+            .ok_or_else(|| format!("interface {} not found", ifname))?;
 
         // Setting up umem
-        let umem = Umem::new_4k(760000).unwrap();
+        let umem =
+            Umem::new_4k(760000).map_err(|e| format!("umem setup for {} failed: {}", ifname, e))?;
         let umem = Arc::new(umem);
 
         // Setting up xsk
-        let mut sock = XDPSocket::new(ifindex, 0, umem.clone(), 4096).unwrap();
+        let mut sock = XDPSocket::new(ifindex, 0, umem.clone(), 4096)
+            .map_err(|e| format!("xdp socket setup for {} failed: {}", ifname, e))?;
         let mut bpf_manager = BPFRedirectManager::attach(ifindex);
         bpf_manager.add_redirect(0, sock.as_raw_fd());
 
@@ -65,11 +94,12 @@ impl ShipComponent<'_> {
 
         let stats = ShipComponentStats::new();
 
-        ShipComponent {
+        Ok(ShipComponent {
             name,
             ifname,
             mac,
             ip,
+            ipv6: None,
             ifindex,
             bpf_manager,
             sock,
@@ -78,113 +108,213 @@ impl ShipComponent<'_> {
             sends,
             receives,
             stats,
-        }
+            recorder: None,
+            enabled: true,
+            binding: None,
+            reassembler: Some(Reassembler::new()),
+            tcp: Some(TcpInspector::new()),
+            multi_sentence: MultiSentence::Drop,
+            validator: None,
+            proprietary: None,
+            groups: Vec::new(),
+            schemas: Arc::new(SentenceSchemas::default()),
+        })
     }
 
     pub fn consume_rx_ring(
         &mut self,
         poll_fd_index: usize,
         poll_fds_len: usize,
-        ship_traffic: &mut VecDeque<(usize, Vec<u8>, bool, String)>,
-        ship_switch: &mut hashbrown::HashMap<[u8; 6], usize>,
+        ship_traffic: &mut VecDeque<TrafficEntry>,
+        ship_switch: &mut ShipSwitch,
+        audit_log: Option<&mut AuditLog>,
         start_time: Instant,
     ) {
-        let rx_descriptor = self
+        let received_at = Instant::now();
+        let rx_addr = self
             .sock
             .rx_ring
-            .get_nth_descriptor(self.sock.rx_ring.get_consumer_index() as _);
+            .get_nth_descriptor(self.sock.rx_ring.get_consumer_index() as _)
+            .addr;
 
-        let rx_slice = self
+        // copied off the umem, the stages of the inspection borrow the component mutably
+        let rx_frame = self
             .sock
             .rx_ring
-            .get_nth_slice(self.sock.rx_ring.get_consumer_index() as _, &self.sock.umem);
-
-        //Parse the incoming message
-        let packet_parser = PacketParser::new(rx_slice);
-        let mut message_ok: bool = true;
-        let mut is_nmea: bool = false;
-        let mut prefix: String = String::from("NONMEA");
+            .get_nth_slice(self.sock.rx_ring.get_consumer_index() as _, &self.sock.umem)
+            .to_vec();
+        let rx_slice = rx_frame.as_slice();
 
         //trace stats
-        self.stats.total_bytes_sent += rx_slice.len() as f64;
-        self.stats.performance_send.push((
-            start_time.elapsed().as_secs_f64(),
-            ((self.stats.total_bytes_sent * 8.0) / start_time.elapsed().as_secs_f64()) / 1000000.0,
-        ));
-
-        match packet_parser.parse_traffic() {
-            Ok(message) => (message_ok, is_nmea, prefix) = self.apply_policy(message),
-            Err(_) => {}
+        self.stats.record_sent(rx_slice.len(), start_time.elapsed());
+
+        // datagrams never completed are dropped on behalf of the component that started them
+        if let Some(reassembler) = self.reassembler.as_mut() {
+            for _ in 0..reassembler.expire(received_at) {
+                self.stats.record_drop(
+                    &DropReason::Fragment(FragmentError::Timeout),
+                    "NONMEA",
+                    start_time.elapsed(),
+                );
+            }
         }
 
-        if message_ok {
-            self.handle_network(
-                rx_slice,
-                ship_switch,
-                &poll_fd_index,
-                &poll_fds_len,
-                ship_traffic,
-                is_nmea,
-                prefix,
-            );
-        } else {
-            println!("|-- MESSAGE IS NOT A NMEA SENTENCE OR IS NOT ALLOWED ");
-            println!("|-- REC ALLOWED {:?}", self.receives);
-            println!("|-- SND ALLOWED {:?}", self.sends);
-            println!("----------------------------------------------------")
+        let mut inspector = self.inspector();
+        let inspected = inspector
+            .inspect(rx_slice, poll_fd_index, ship_switch, received_at)
+            .map(|inspection| {
+                let traffic = inspector.traffic(
+                    &inspection,
+                    poll_fd_index,
+                    poll_fds_len,
+                    ship_switch,
+                    received_at,
+                );
+                (inspection, traffic)
+            });
+
+        match inspected {
+            Some((inspection, traffic)) => {
+                self.log_inspection(&inspection, start_time);
+                self.account(&inspection, rx_slice, audit_log, start_time);
+                if inspection.drop_reason.is_some() && traffic.is_empty() {
+                    log::info!("|-- MESSAGE IS NOT A NMEA SENTENCE OR IS NOT ALLOWED ");
+                    log::info!("|-- REC ALLOWED {:?}", self.receives);
+                    log::info!("|-- SND ALLOWED {:?}", self.sends);
+                    log::info!("----------------------------------------------------")
+                }
+                ship_traffic.extend(traffic);
+            }
+            // a fragment held until its datagram is whole
+            None => self.record(rx_slice, None),
         }
 
-        // refill allocator or fill ring
-        if self.sock.fill_ring.can_produce() {
-            self.sock.fill_ring.produce_umem_offset(rx_descriptor.addr);
-        } else {
-            self.umem_allocator.release_offset(rx_descriptor.addr);
+        self.release_rx_descriptor(rx_addr);
+    }
+
+    // the policy and the state the frames of the component are judged with
+    fn inspector(&mut self) -> Inspector<'_> {
+        Inspector {
+            enabled: self.enabled,
+            sends: &self.sends,
+            schemas: &self.schemas,
+            multi_sentence: self.multi_sentence,
+            binding: self.binding.as_ref(),
+            reassembler: self.reassembler.as_mut(),
+            tcp: self.tcp.as_mut(),
+            validator: self.validator.as_mut(),
+            proprietary: self.proprietary.as_mut(),
         }
+    }
 
-        // advance index
-        self.sock.rx_ring.advance_consumer_index();
+    // the spoofed sources and the sentences filtered out of the datagram are told apart
+    fn log_inspection(&mut self, inspection: &Inspection, start_time: Instant) {
+        if let Some(
+            reason @ (DropReason::SpoofedSourceMac(_)
+            | DropReason::SpoofedSourceIp(_)
+            | DropReason::SpoofedSourceId(_)),
+        ) = &inspection.drop_reason
+        {
+            log::warn!(
+                "| {} ON {} ({}), FRAME DROPPED",
+                reason.to_string().to_uppercase(),
+                self.ifname,
+                self.name
+            );
+        }
+
+        inspection.filtered.iter().for_each(|denied| {
+            log::warn!(
+                "| {} NOT ALLOWED ON {} ({}), SENTENCE FILTERED",
+                denied,
+                self.ifname,
+                self.name
+            );
+            self.stats.record_drop(
+                &DropReason::SentenceNotAllowed(denied.clone()),
+                denied,
+                start_time.elapsed(),
+            );
+        });
     }
 
-    fn handle_network(
-        &self,
+    // stats, audit log and capture of an inspected frame
+    fn account(
+        &mut self,
+        inspection: &Inspection,
         rx_slice: &[u8],
-        ship_switch: &mut hashbrown::HashMap<[u8; 6], usize>,
-        poll_fd_index: &usize,
-        poll_fds_len: &usize,
-        ship_traffic: &mut VecDeque<(usize, Vec<u8>, bool, String)>,
-        is_nmea: bool,
-        prefix: String,
+        audit_log: Option<&mut AuditLog>,
+        start_time: Instant,
     ) {
-        // Update the ship switch and add the packets to the ship traffic
-        let eth_dst_addr: &[u8; 6] = &rx_slice[0..6].try_into().unwrap();
-        let eth_src_addr: &[u8; 6] = &rx_slice[6..12].try_into().unwrap();
-
-        // Add mac src address to the ship switch
-        if !ship_switch.contains_key(eth_src_addr) {
-            ship_switch.insert(*eth_src_addr, *poll_fd_index);
-            println!("SWITCH_STATUS:\n{:x?}", ship_switch);
+        let prefix = &inspection.prefix;
+        let mut record_sentence = |sentence: &str| {
+            self.stats.record_sentence(sentence);
+            if inspection.drop_reason.is_none() {
+                self.stats.record_allowed(sentence);
+            }
+        };
+        if inspection.is_nmea {
+            record_sentence(prefix);
+        }
+        inspection
+            .sentences
+            .iter()
+            .for_each(|sentence| record_sentence(sentence));
+        if let Some(reason) = &inspection.drop_reason {
+            self.stats.record_drop(reason, prefix, start_time.elapsed());
         }
 
-        if let Some(destination_poll_fd_index) = ship_switch.get(eth_dst_addr) {
-            ship_traffic.push_back((
-                *destination_poll_fd_index,
-                rx_slice.to_vec(),
-                is_nmea,
-                prefix,
-            ));
-        } else {
-            // nmea sentences should not be flooded since we want that only the correct recipients get what they expect
-            if !is_nmea {
-                for j in 0..*poll_fds_len {
-                    if *poll_fd_index == j {
-                        continue;
-                    }
-                    ship_traffic.push_back((j, rx_slice.to_vec(), is_nmea, prefix.clone()));
-                }
+        if let Some(audit_log) = audit_log {
+            let matched_rule = matched_rule(&self.name, inspection);
+            match &inspection.drop_reason {
+                Some(reason) => audit_log.log_dropped(
+                    &self.name,
+                    &self.ifname,
+                    &inspection.frame,
+                    prefix,
+                    reason,
+                    matched_rule,
+                ),
+                None => audit_log.log_allowed(
+                    &self.name,
+                    &self.ifname,
+                    &inspection.frame,
+                    prefix,
+                    matched_rule,
+                ),
+            }
+        }
+
+        self.record(rx_slice, inspection.drop_reason.as_ref());
+    }
+
+    // the verdict of the policy, whether the frame is forwarded is only known once it is transmitted
+    fn record(&mut self, rx_slice: &[u8], drop_reason: Option<&DropReason>) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            let comment = drop_reason.map(|reason| reason.to_string());
+            if let Err(e) = recorder.record(rx_slice, Direction::Inbound, comment.as_deref()) {
+                log::error!("| CAPTURE ON {} FAILED: {}", self.ifname, e);
             }
         }
     }
 
+    // forgets the connection of a tcp segment going no further, with the resets for both of its ends
+    pub fn reset_connection(&mut self, segment: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+        self.inspector().reset_connection(segment)
+    }
+
+    fn release_rx_descriptor(&mut self, addr: u64) {
+        // refill allocator or fill ring
+        if self.sock.fill_ring.can_produce() {
+            self.sock.fill_ring.produce_umem_offset(addr);
+        } else {
+            self.umem_allocator.release_offset(addr);
+        }
+
+        // advance index
+        self.sock.rx_ring.advance_consumer_index();
+    }
+
     pub fn refill_umem_allocator(&mut self) {
         while self.sock.completion_ring.can_consume() {
             let offset = self
@@ -208,26 +338,31 @@ impl ShipComponent<'_> {
             }
         }
     }
+}
 
-    fn apply_policy(&self, message: String) -> (bool, bool, String) {
-        // here we know that the message is udp and it should be parsed to check for nmea compliance
-        let mut nmea = Nmea::new();
-        let message_ok = nmea.parse(message.clone());
-
-        match message_ok {
-            Ok(()) => {
-                // message is valid nmea
-                // now gotta check if the message can be sent by the component
-                // nmea.show();
-                let prefix = format!("${}{}", nmea.str_talker_id(), nmea.str_sentence_type());
-                let is_allowed = self
-                    .sends
-                    .iter()
-                    .any(|allowed_message| prefix == *allowed_message);
-                (is_allowed, true, prefix)
-            }
-
-            Err(_) => (true, false, String::from("NONMEA")),
-        }
+// the part of the policy that decided the verdict, as it reads in the policy file,
+// none when the frame went no further for reasons of the ship itself
+fn matched_rule(component: &str, inspection: &Inspection) -> Option<String> {
+    match &inspection.drop_reason {
+        None => inspection
+            .is_nmea
+            .then(|| format!("{}.sends {}", component, inspection.prefix)),
+        Some(DropReason::SentenceNotAllowed(_)) => Some(format!("{}.sends", component)),
+        Some(DropReason::SpoofedSourceMac(_)) => Some(format!("{}.allowed_macs", component)),
+        Some(DropReason::SpoofedSourceIp(_)) => Some(format!("{}.allowed_ips", component)),
+        Some(DropReason::SpoofedSourceId(_)) => Some(format!("{}.source_ids", component)),
+        Some(DropReason::ArpSpoofing(_)) => Some(String::from("arp.inspection")),
+        Some(DropReason::Fragment(_)) => Some(String::from("reassembly")),
+        Some(DropReason::TcpStream(_)) => Some(String::from("tcp")),
+        Some(DropReason::InvalidSentence(violation)) => Some(format!(
+            "validation.{}",
+            violation.kind().trim_start_matches("sentence_")
+        )),
+        Some(DropReason::ProprietaryDenied(_)) => Some(String::from("proprietary.deny")),
+        Some(
+            DropReason::ComponentDisabled
+            | DropReason::TxAllocationFailed
+            | DropReason::TxWakeupFailed,
+        ) => None,
     }
 }
@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// traffic seen during one fixed interval of the run
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StatsBucket {
+    // position of the bucket since the start of the run, in intervals
+    pub index: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RateSummary {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+}
+
+impl RateSummary {
+    fn from_rates(mut rates: Vec<f64>) -> Self {
+        if rates.is_empty() {
+            return RateSummary::default();
+        }
+
+        rates.sort_by(|a, b| a.total_cmp(b));
+        let percentile = |p: f64| {
+            // nearest rank
+            let rank = ((p / 100.0) * rates.len() as f64).ceil() as usize;
+            rates[rank.clamp(1, rates.len()) - 1]
+        };
+
+        RateSummary {
+            min: rates[0],
+            max: rates[rates.len() - 1],
+            mean: rates.iter().sum::<f64>() / rates.len() as f64,
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+        }
+    }
+}
+
+// keeps the most recent buckets only, so memory stays the same whatever the packet rate
+pub struct BucketRing {
+    pub interval: Duration,
+    pub capacity: usize,
+    buckets: VecDeque<StatsBucket>,
+}
+
+impl BucketRing {
+    pub fn new(interval: Duration, capacity: usize) -> Self {
+        BucketRing {
+            interval,
+            capacity: capacity.max(1),
+            buckets: VecDeque::with_capacity(capacity.max(1)),
+        }
+    }
+
+    pub fn record_sent(&mut self, bytes: usize, elapsed: Duration) {
+        let bucket = self.bucket_at(elapsed);
+        bucket.bytes_sent += bytes as u64;
+        bucket.packets_sent += 1;
+    }
+
+    pub fn record_received(&mut self, bytes: usize, elapsed: Duration) {
+        let bucket = self.bucket_at(elapsed);
+        bucket.bytes_received += bytes as u64;
+        bucket.packets_received += 1;
+    }
+
+    // empty buckets up to now, so that a link gone quiet reports a rate of zero
+    // and not that of its last traffic
+    pub fn roll_to(&mut self, elapsed: Duration) {
+        if !self.buckets.is_empty() {
+            self.bucket_at(elapsed);
+        }
+    }
+
+    pub fn buckets(&self) -> impl Iterator<Item = &StatsBucket> {
+        self.buckets.iter()
+    }
+
+    // start of the bucket in seconds since the start of the run
+    pub fn bucket_start(&self, bucket: &StatsBucket) -> f64 {
+        bucket.index as f64 * self.interval.as_secs_f64()
+    }
+
+    // (time s, Mbit/s) for every retained bucket
+    pub fn throughput_sent(&self) -> Vec<(f64, f64)> {
+        self.series(|bucket| bucket.bytes_sent as f64 * 8.0 / 1000000.0)
+    }
+
+    pub fn throughput_received(&self) -> Vec<(f64, f64)> {
+        self.series(|bucket| bucket.bytes_received as f64 * 8.0 / 1000000.0)
+    }
+
+    // (time s, packets/s) for every retained bucket
+    pub fn packet_rate_sent(&self) -> Vec<(f64, f64)> {
+        self.series(|bucket| bucket.packets_sent as f64)
+    }
+
+    pub fn packet_rate_received(&self) -> Vec<(f64, f64)> {
+        self.series(|bucket| bucket.packets_received as f64)
+    }
+
+    pub fn summary(series: &[(f64, f64)]) -> RateSummary {
+        RateSummary::from_rates(series.iter().map(|(_, rate)| *rate).collect())
+    }
+
+    // rate of the last completed bucket, the current one is still filling up
+    pub fn last_rate(series: &[(f64, f64)]) -> f64 {
+        match series.len() {
+            0 => 0.0,
+            1 => series[0].1,
+            len => series[len - 2].1,
+        }
+    }
+
+    fn series<F>(&self, amount: F) -> Vec<(f64, f64)>
+    where
+        F: Fn(&StatsBucket) -> f64,
+    {
+        let seconds = self.interval.as_secs_f64();
+        self.buckets
+            .iter()
+            .map(|bucket| (self.bucket_start(bucket), amount(bucket) / seconds))
+            .collect()
+    }
+
+    fn bucket_at(&mut self, elapsed: Duration) -> &mut StatsBucket {
+        let index = (elapsed.as_nanos() / self.interval.as_nanos().max(1)) as u64;
+        let last_index = self.buckets.back().map(|bucket| bucket.index);
+
+        match last_index {
+            Some(last_index) if index <= last_index => {}
+            _ => {
+                // idle intervals get empty buckets so that rates drop to zero instead of being skipped
+                let first_missing = match last_index {
+                    Some(last_index) => {
+                        (last_index + 1).max(index.saturating_sub(self.capacity as u64 - 1))
+                    }
+                    None => index,
+                };
+                for missing in first_missing..=index {
+                    if self.buckets.len() == self.capacity {
+                        self.buckets.pop_front();
+                    }
+                    self.buckets.push_back(StatsBucket {
+                        index: missing,
+                        ..StatsBucket::default()
+                    });
+                }
+            }
+        }
+
+        // late samples from an older interval are accounted to the most recent bucket
+        self.buckets.back_mut().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_intervals_get_empty_buckets() {
+        let mut ring = BucketRing::new(Duration::from_secs(1), 10);
+        ring.record_sent(1000, Duration::from_millis(500));
+        ring.record_sent(1000, Duration::from_millis(3500));
+
+        let rates: Vec<f64> = ring
+            .packet_rate_sent()
+            .iter()
+            .map(|(_, rate)| *rate)
+            .collect();
+        assert_eq!(rates, [1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn a_quiet_link_rolls_to_a_rate_of_zero() {
+        let mut ring = BucketRing::new(Duration::from_secs(1), 10);
+        ring.record_sent(1000, Duration::from_millis(500));
+        ring.record_sent(1000, Duration::from_millis(1500));
+        assert_eq!(BucketRing::last_rate(&ring.packet_rate_sent()), 1.0);
+
+        ring.roll_to(Duration::from_millis(5500));
+        assert_eq!(BucketRing::last_rate(&ring.packet_rate_sent()), 0.0);
+        assert_eq!(ring.buckets().count(), 6);
+    }
+
+    #[test]
+    fn rolling_keeps_the_capacity() {
+        let mut ring = BucketRing::new(Duration::from_secs(1), 4);
+        ring.roll_to(Duration::from_secs(2));
+        assert_eq!(ring.buckets().count(), 0);
+
+        ring.record_received(100, Duration::ZERO);
+        ring.roll_to(Duration::from_secs(100));
+        let indexes: Vec<u64> = ring.buckets().map(|bucket| bucket.index).collect();
+        assert_eq!(indexes, [97, 98, 99, 100]);
+    }
+}
@@ -1 +1,3 @@
+pub mod bucket_ring;
+pub mod plots;
 pub mod ship_component_stats;
@@ -0,0 +1,392 @@
+use super::ship_component_stats::ShipComponentStats;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+const CHART_SIZE: (u32, u32) = (1011, 758);
+const DASHBOARD_CELL_SIZE: (u32, u32) = (640, 400);
+
+// legend label, (time s, value) points and line color
+type Series<'a> = (&'a str, Vec<(f64, f64)>, RGBColor);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Svg,
+}
+
+impl ImageFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.to_lowercase().as_str() {
+            "png" => Some(ImageFormat::Png),
+            "svg" => Some(ImageFormat::Svg),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Chart {
+    Throughput,
+    PacketRate,
+    Drops,
+    Latency,
+    Sentences,
+}
+
+impl Chart {
+    pub const ALL: [Chart; 5] = [
+        Chart::Throughput,
+        Chart::PacketRate,
+        Chart::Drops,
+        Chart::Latency,
+        Chart::Sentences,
+    ];
+
+    pub fn parse(chart: &str) -> Option<Self> {
+        Chart::ALL
+            .into_iter()
+            .find(|candidate| candidate.name() == chart.to_lowercase())
+    }
+
+    // also used as the suffix of the image file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Chart::Throughput => "throughput",
+            Chart::PacketRate => "packet_rate",
+            Chart::Drops => "drops",
+            Chart::Latency => "latency",
+            Chart::Sentences => "sentences",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Chart::Throughput => "throughput",
+            Chart::PacketRate => "packet rate",
+            Chart::Drops => "drops by reason",
+            Chart::Latency => "forwarding latency",
+            Chart::Sentences => "sentences sent",
+        }
+    }
+}
+
+pub struct PlotConfig {
+    pub directory: PathBuf,
+    pub format: ImageFormat,
+    // fit the time series axes to the recorded data instead of the fixed ranges below
+    pub auto_scale: bool,
+    pub time_range: f64,
+    pub throughput_range: f64,
+    pub packet_rate_range: f64,
+    pub charts: Vec<Chart>,
+    // one more image with every chart of every component
+    pub dashboard: bool,
+}
+
+impl Default for PlotConfig {
+    fn default() -> Self {
+        PlotConfig {
+            directory: PathBuf::from("./test/imgs"),
+            format: ImageFormat::Png,
+            auto_scale: true,
+            time_range: 30.0,
+            throughput_range: 4000.0,
+            packet_rate_range: 10000.0,
+            charts: Chart::ALL.to_vec(),
+            dashboard: true,
+        }
+    }
+}
+
+impl PlotConfig {
+    fn image_path(&self, name: &str) -> PathBuf {
+        self.directory
+            .join(format!("{}.{}", name, self.format.extension()))
+    }
+}
+
+impl ShipComponentStats {
+    // writes one image per configured chart, named <component_name>-<chart>
+    pub fn plot_performance(
+        &self,
+        component_name: &str,
+        config: &PlotConfig,
+    ) -> Result<(), Box<dyn Error>> {
+        std::fs::create_dir_all(&config.directory)?;
+
+        for chart in config.charts.iter() {
+            let image_path = config.image_path(&format!("{}-{}", component_name, chart.name()));
+            render(
+                &image_path,
+                config,
+                CHART_SIZE,
+                &[(component_name, self)],
+                &[*chart],
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// one row per component and one column per configured chart, in a single image
+pub fn plot_dashboard(
+    components: &[(&str, &ShipComponentStats)],
+    config: &PlotConfig,
+) -> Result<(), Box<dyn Error>> {
+    if components.is_empty() || config.charts.is_empty() {
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(&config.directory)?;
+
+    let size = (
+        DASHBOARD_CELL_SIZE.0 * config.charts.len() as u32,
+        DASHBOARD_CELL_SIZE.1 * components.len() as u32,
+    );
+    render(
+        &config.image_path("dashboard"),
+        config,
+        size,
+        components,
+        &config.charts,
+    )
+}
+
+fn render(
+    image_path: &Path,
+    config: &PlotConfig,
+    size: (u32, u32),
+    components: &[(&str, &ShipComponentStats)],
+    charts: &[Chart],
+) -> Result<(), Box<dyn Error>> {
+    match config.format {
+        ImageFormat::Png => {
+            let root = BitMapBackend::new(image_path, size).into_drawing_area();
+            draw_grid(&root, config, components, charts)?;
+            root.present()?;
+        }
+        ImageFormat::Svg => {
+            let root = SVGBackend::new(image_path, size).into_drawing_area();
+            draw_grid(&root, config, components, charts)?;
+            root.present()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn draw_grid<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    config: &PlotConfig,
+    components: &[(&str, &ShipComponentStats)],
+    charts: &[Chart],
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+
+    let cells = root.split_evenly((components.len(), charts.len()));
+    for (row, (component_name, stats)) in components.iter().enumerate() {
+        for (column, chart) in charts.iter().enumerate() {
+            let area = &cells[row * charts.len() + column];
+            let title = format!("{} {}", component_name, chart.title());
+            match chart {
+                Chart::Throughput => draw_time_series(
+                    area,
+                    &title,
+                    "Bitrate Mbit/s",
+                    config,
+                    config.throughput_range,
+                    &[
+                        ("Sending MBit/s", stats.buckets.throughput_sent(), RED),
+                        ("Receive MBit/s", stats.buckets.throughput_received(), BLUE),
+                    ],
+                )?,
+                Chart::PacketRate => draw_time_series(
+                    area,
+                    &title,
+                    "Packets/s",
+                    config,
+                    config.packet_rate_range,
+                    &[
+                        ("Sending packets/s", stats.buckets.packet_rate_sent(), RED),
+                        (
+                            "Receive packets/s",
+                            stats.buckets.packet_rate_received(),
+                            BLUE,
+                        ),
+                    ],
+                )?,
+                Chart::Drops => {
+                    let mut drops: Vec<(String, u64)> = stats
+                        .drops
+                        .iter()
+                        .map(|(reason, count)| (reason.to_string(), *count))
+                        .collect();
+                    drops.sort();
+                    draw_bars(area, &title, "Reason", "Frames", &drops, RED)?
+                }
+                Chart::Latency => draw_bars(
+                    area,
+                    &title,
+                    "Latency",
+                    "Packets",
+                    &latency_bins(stats),
+                    GREEN,
+                )?,
+                Chart::Sentences => {
+                    let mut sentences: Vec<(String, u64)> = stats
+                        .sentences_sent
+                        .iter()
+                        .map(|(prefix, count)| (prefix.clone(), *count))
+                        .collect();
+                    sentences.sort();
+                    draw_bars(area, &title, "Sentence", "Sentences", &sentences, BLUE)?
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn draw_time_series<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    title: &str,
+    y_desc: &str,
+    config: &PlotConfig,
+    y_range: f64,
+    series: &[Series],
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let (x_max, y_max) = if config.auto_scale {
+        let points = series.iter().flat_map(|(_, points, _)| points.iter());
+        let (x_max, y_max) = points.fold((0f64, 0f64), |(x_max, y_max), (x, y)| {
+            (x_max.max(*x), y_max.max(*y))
+        });
+        // leave some headroom above the highest sample, and keep empty series drawable
+        (x_max.max(1.0), (y_max * 1.1).max(1.0))
+    } else {
+        (config.time_range, y_range)
+    };
+
+    let mut chart = ChartBuilder::on(area)
+        .caption(title, ("sans-serif", 20))
+        .margin(10)
+        .x_label_area_size(50)
+        .y_label_area_size(60)
+        .build_cartesian_2d(0f64..x_max, 0f64..y_max)?;
+
+    chart
+        .configure_mesh()
+        .x_desc("Time s")
+        .y_desc(y_desc)
+        .draw()?;
+
+    for (label, points, color) in series.iter() {
+        let color = *color;
+        chart
+            .draw_series(LineSeries::new(points.clone(), color))?
+            .label(*label)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    Ok(())
+}
+
+fn draw_bars<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    title: &str,
+    x_desc: &str,
+    y_desc: &str,
+    bars: &[(String, u64)],
+    color: RGBColor,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let y_max = bars.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    let y_max = (y_max as f64 * 1.1).ceil().max(1.0) as u64;
+
+    let mut chart = ChartBuilder::on(area)
+        .caption(title, ("sans-serif", 20))
+        .margin(10)
+        .x_label_area_size(50)
+        .y_label_area_size(60)
+        .build_cartesian_2d(
+            (0..bars.len().saturating_sub(1)).into_segmented(),
+            0u64..y_max,
+        )?;
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(bars.len().max(1))
+        .x_label_formatter(&|value| match value {
+            SegmentValue::CenterOf(index) => bars
+                .get(*index)
+                .map(|(label, _)| label.clone())
+                .unwrap_or_default(),
+            _ => String::new(),
+        })
+        .x_desc(x_desc)
+        .y_desc(y_desc)
+        .draw()?;
+
+    chart.draw_series(
+        Histogram::vertical(&chart)
+            .style(color.filled())
+            .margin(5)
+            .data(
+                bars.iter()
+                    .enumerate()
+                    .map(|(index, (_, count))| (index, *count)),
+            ),
+    )?;
+
+    Ok(())
+}
+
+// packets forwarded to the component from every ingress, in bins doubling from 1 us
+fn latency_bins(stats: &ShipComponentStats) -> Vec<(String, u64)> {
+    let mut histograms = stats.latency.values();
+    let mut merged = match histograms.next() {
+        Some(histogram) => histogram.clone(),
+        None => return Vec::new(),
+    };
+    histograms.for_each(|histogram| {
+        let _ = merged.add(histogram);
+    });
+
+    merged
+        .iter_log(1000, 2.0)
+        .map(|bin| {
+            (
+                format!(
+                    "<={:.0}us",
+                    (bin.value_iterated_to() as f64 / 1000.0).round()
+                ),
+                bin.count_since_last_iteration(),
+            )
+        })
+        .collect()
+}
@@ -1,63 +1,193 @@
-use plotters::prelude::*;
+use super::bucket_ring::BucketRing;
+use firewall::{DropReason, FragmentError, TcpError};
+use hdrhistogram::Histogram;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// 100 ms buckets, ten minutes of history
+const BUCKET_INTERVAL: Duration = Duration::from_millis(100);
+const BUCKET_CAPACITY: usize = 6000;
+
+// latencies are tracked in nanoseconds up to one minute, with three significant digits
+const LATENCY_MAX_NANOS: u64 = 60_000_000_000;
+const LATENCY_SIGNIFICANT_DIGITS: u8 = 3;
+
+// prefixes counted one by one, the sentences of any other prefix are counted together
+const SENTENCE_PREFIXES_CAPACITY: usize = 256;
+const OTHER_PREFIXES: &str = "other";
+
+// drops kept for operators to look at, older ones only survive in the counters
+const RECENT_DROPS_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct DropEvent {
+    // seconds since the start of the run
+    pub at: f64,
+    pub prefix: String,
+    pub reason: DropReason,
+}
 
 pub struct ShipComponentStats {
     pub total_bytes_sent: f64,
     pub total_bytes_received: f64,
-    pub performance_send: Vec<(f64, f64)>,
-    pub performance_receive: Vec<(f64, f64)>,
+    pub buckets: BucketRing,
+    // seconds since the start of the run of the last frame sent or received
+    pub last_activity: f64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    // sentences sent by the component, allowed or not, by prefix up to SENTENCE_PREFIXES_CAPACITY
+    pub sentences_sent: hashbrown::HashMap<String, u64>,
+    // sentences the policy let through, by prefix
+    pub sentences_allowed: hashbrown::HashMap<String, u64>,
+    // sentences the policy did not allow the component to send, by prefix
+    pub sentences_denied: hashbrown::HashMap<String, u64>,
+    pub drops: hashbrown::HashMap<&'static str, u64>,
+    pub recent_drops: VecDeque<DropEvent>,
+    pub umem_allocation_failures: u64,
+    pub tx_failures: u64,
+    // frames dropped because the component reached one of the limits the firewall puts on it
+    pub rate_limited: u64,
+    // time from the rx ring of the ingress component to the tx ring of this one, by ingress index
+    pub latency: hashbrown::HashMap<usize, Histogram<u64>>,
+}
+
+impl Default for ShipComponentStats {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ShipComponentStats {
     pub fn new() -> Self {
-        let mut performance_send: Vec<(f64, f64)> = Vec::new();
-        let mut performance_receive: Vec<(f64, f64)> = Vec::new();
-        performance_send.push((0.0, 0.0));
-        performance_receive.push((0.0, 0.0));
+        Self::with_buckets(BUCKET_INTERVAL, BUCKET_CAPACITY)
+    }
 
+    pub fn with_buckets(interval: Duration, capacity: usize) -> Self {
         ShipComponentStats {
             total_bytes_sent: 0.0,
             total_bytes_received: 0.0,
-            performance_send,
-            performance_receive,
+            buckets: BucketRing::new(interval, capacity),
+            last_activity: 0.0,
+            packets_sent: 0,
+            packets_received: 0,
+            sentences_sent: hashbrown::HashMap::new(),
+            sentences_allowed: hashbrown::HashMap::new(),
+            sentences_denied: hashbrown::HashMap::new(),
+            drops: hashbrown::HashMap::new(),
+            recent_drops: VecDeque::with_capacity(RECENT_DROPS_CAPACITY),
+            umem_allocation_failures: 0,
+            tx_failures: 0,
+            rate_limited: 0,
+            latency: hashbrown::HashMap::new(),
+        }
+    }
+
+    pub fn record_sent(&mut self, bytes: usize, elapsed: Duration) {
+        self.packets_sent += 1;
+        self.total_bytes_sent += bytes as f64;
+        self.buckets.record_sent(bytes, elapsed);
+        self.last_activity = self.last_activity.max(elapsed.as_secs_f64());
+    }
+
+    pub fn record_received(&mut self, bytes: usize, elapsed: Duration) {
+        self.packets_received += 1;
+        self.total_bytes_received += bytes as f64;
+        self.buckets.record_received(bytes, elapsed);
+        self.last_activity = self.last_activity.max(elapsed.as_secs_f64());
+    }
+
+    // empty buckets up to now, for the rates of a component that stopped sending
+    pub fn roll_to(&mut self, elapsed: Duration) {
+        self.buckets.roll_to(elapsed);
+    }
+
+    pub fn record_latency(&mut self, ingress: usize, latency: Duration) {
+        let histogram = self.latency.entry(ingress).or_insert_with(|| {
+            Histogram::new_with_bounds(1, LATENCY_MAX_NANOS, LATENCY_SIGNIFICANT_DIGITS).unwrap()
+        });
+        histogram.saturating_record(latency.as_nanos() as u64);
+    }
+
+    pub fn record_sentence(&mut self, prefix: &str) {
+        count_prefix(&mut self.sentences_sent, prefix);
+    }
+
+    pub fn record_allowed(&mut self, prefix: &str) {
+        count_prefix(&mut self.sentences_allowed, prefix);
+    }
+
+    pub fn record_drop(&mut self, drop_reason: &DropReason, prefix: &str, elapsed: Duration) {
+        *self.drops.entry(drop_reason.kind()).or_insert(0) += 1;
+        if self.recent_drops.len() == RECENT_DROPS_CAPACITY {
+            self.recent_drops.pop_front();
+        }
+        self.recent_drops.push_back(DropEvent {
+            at: elapsed.as_secs_f64(),
+            prefix: prefix.to_string(),
+            reason: drop_reason.clone(),
+        });
+
+        match drop_reason {
+            DropReason::TxAllocationFailed => self.umem_allocation_failures += 1,
+            DropReason::TxWakeupFailed => self.tx_failures += 1,
+            DropReason::Fragment(FragmentError::TableFull | FragmentError::TooManyFragments)
+            | DropReason::TcpStream(TcpError::FlowLimit) => self.rate_limited += 1,
+            DropReason::SentenceNotAllowed(prefix) => {
+                count_prefix(&mut self.sentences_denied, prefix)
+            }
+            _ => {}
         }
     }
+}
+
+// a component sending garbage prefixes must not grow the counters without bound
+fn count_prefix(counters: &mut hashbrown::HashMap<String, u64>, prefix: &str) {
+    let prefix = match counters.contains_key(prefix) || counters.len() < SENTENCE_PREFIXES_CAPACITY
+    {
+        true => prefix,
+        false => OTHER_PREFIXES,
+    };
+    *counters.entry_ref(prefix).or_insert(0) += 1;
+}
 
-    pub fn plot_performance(&self, component_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let image_name = format!("./test/imgs/{}-stats.png", component_name);
-        let root = BitMapBackend::new(&image_name, (1011, 758)).into_drawing_area();
-        root.fill(&WHITE)?;
-
-        let mut chart = ChartBuilder::on(&root)
-            .caption(
-                format!("{} performance", component_name),
-                ("sans-serif", 20),
-            )
-            .margin(1)
-            .x_label_area_size(50)
-            .y_label_area_size(50)
-            .build_cartesian_2d(0f64..30f64, 0f64..4000f64)?;
-
-        chart.configure_mesh().draw()?;
-
-        chart
-            .draw_series(LineSeries::new(self.performance_send.clone(), &RED))?
-            .label("Sending MBit/s/s")
-            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &RED));
-        chart
-            .draw_series(LineSeries::new(self.performance_receive.clone(), &BLUE))?
-            .label("Receive MBit/s/s")
-            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &BLUE));
-        chart
-            .configure_series_labels()
-            .background_style(&WHITE.mix(0.8))
-            .draw()?;
-
-        chart
-            .configure_mesh()
-            .x_desc("Time s") // Label for the x-axis
-            .y_desc("Bitrate Mbit/s") // Label for the y-axis
-            .draw()?; // Draw the grid and labels
-
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sentence_counters_are_capped() {
+        let mut stats = ShipComponentStats::new();
+        for index in 0..SENTENCE_PREFIXES_CAPACITY + 10 {
+            let prefix = format!("$P{:04}", index);
+            stats.record_sentence(&prefix);
+            stats.record_drop(
+                &DropReason::SentenceNotAllowed(prefix.clone()),
+                &prefix,
+                Duration::ZERO,
+            );
+        }
+        stats.record_sentence("$P0000");
+
+        // the prefixes past the capacity are counted together
+        assert_eq!(stats.sentences_sent.len(), SENTENCE_PREFIXES_CAPACITY + 1);
+        assert_eq!(stats.sentences_sent["$P0000"], 2);
+        assert_eq!(stats.sentences_sent[OTHER_PREFIXES], 10);
+        assert_eq!(stats.sentences_denied[OTHER_PREFIXES], 10);
+    }
+
+    #[test]
+    fn only_the_limits_of_the_firewall_count_as_rate_limited() {
+        let mut stats = ShipComponentStats::new();
+        for reason in [
+            DropReason::Fragment(FragmentError::TableFull),
+            DropReason::Fragment(FragmentError::TooManyFragments),
+            DropReason::TcpStream(TcpError::FlowLimit),
+            DropReason::Fragment(FragmentError::Overlap),
+            DropReason::TcpStream(TcpError::OutOfOrder),
+            DropReason::SentenceNotAllowed(String::from("$GPGGA")),
+        ] {
+            stats.record_drop(&reason, "$GPGGA", Duration::ZERO);
+        }
+        assert_eq!(stats.rate_limited, 3);
     }
 }
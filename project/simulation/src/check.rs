@@ -0,0 +1,145 @@
+use firewall::{AuditLevel, MultiSentence, SentenceSchemas, SentenceValidator, TransmissionGroups};
+use policy_handler::{is_proprietary_prefix, PolicyHandler};
+use shipcomponent::{Chart, ImageFormat};
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+
+// everything run would complain about, reported at once instead of one at a time
+pub fn check(policy_path: &str) -> Result<(), String> {
+    let policy = PolicyHandler::new(policy_path.to_string())?;
+    let mut problems = policy.validate();
+
+    if let Some(audit) = policy.get_audit() {
+        if AuditLevel::parse(&audit.level).is_none() {
+            problems.push(format!("audit: unknown level {}", audit.level));
+        }
+    }
+
+    if let Some(plots) = policy.get_plots() {
+        if ImageFormat::parse(&plots.format).is_none() {
+            problems.push(format!("plots: unknown format {}", plots.format));
+        }
+        plots
+            .charts
+            .iter()
+            .filter(|chart| Chart::parse(chart).is_none())
+            .for_each(|chart| problems.push(format!("plots: unknown chart {}", chart)));
+    }
+
+    if let Some(metrics) = policy.get_metrics() {
+        if metrics.address.to_socket_addrs().is_err() {
+            problems.push(format!("metrics: invalid address {}", metrics.address));
+        }
+    }
+
+    if let Some(reassembly) = policy.get_reassembly() {
+        // the smallest ipv4 mtu, anything below cannot carry a header and a fragment
+        if reassembly.mtu < 68 {
+            problems.push(format!("reassembly: mtu {} below 68", reassembly.mtu));
+        }
+        if reassembly.max_datagram_size > 65515 {
+            problems.push(format!(
+                "reassembly: max_datagram_size {} above 65515",
+                reassembly.max_datagram_size
+            ));
+        }
+    }
+
+    let schemas = match SentenceSchemas::load(&policy.get_sentences().schemas) {
+        Ok(schemas) => schemas,
+        Err(e) => {
+            problems.push(format!("sentences: {}", e));
+            SentenceSchemas::default()
+        }
+    };
+    if let Some(validation) = policy.get_validation() {
+        if let Err(e) = SentenceValidator::configure(
+            validation.max_length,
+            &validation.actions(),
+            Arc::new(schemas),
+        ) {
+            problems.push(format!("validation: {}", e));
+        }
+        // the shortest sentence is a delimiter, an address field and the line terminator
+        if validation.max_length < 8 {
+            problems.push(format!(
+                "validation: max_length {} below 8",
+                validation.max_length
+            ));
+        }
+    }
+
+    if let Some(proprietary) = policy.get_proprietary() {
+        proprietary
+            .deny
+            .iter()
+            .filter(|prefix| !is_proprietary_prefix(prefix))
+            .for_each(|prefix| {
+                problems.push(format!(
+                    "proprietary: invalid sentence or manufacturer {}",
+                    prefix
+                ))
+            });
+    }
+
+    let datagram = policy.get_datagram();
+    if MultiSentence::parse(&datagram.multiple_sentences).is_none() {
+        problems.push(format!(
+            "datagram: unknown multiple_sentences mode {}",
+            datagram.multiple_sentences
+        ));
+    }
+
+    let igmp = policy.get_igmp();
+    if igmp.snooping && igmp.membership_interval == 0 {
+        problems.push(String::from("igmp: membership_interval cannot be 0"));
+    }
+
+    if let Some(tcp) = policy.get_tcp() {
+        if tcp.enabled && tcp.ports.is_empty() {
+            problems.push(String::from("tcp: no ports to inspect"));
+        }
+        // a line must at least hold the longest sentence nmea allows
+        if tcp.max_line_length < 82 {
+            problems.push(format!(
+                "tcp: max_line_length {} below 82",
+                tcp.max_line_length
+            ));
+        }
+    }
+
+    match TransmissionGroups::configure(&policy.get_groups()) {
+        Ok(groups) => {
+            let mut components = policy.get_policy();
+            components.sort_by(|a, b| a.name.cmp(&b.name));
+            for component in components {
+                component
+                    .groups
+                    .iter()
+                    .filter(|group| groups.by_name(group).is_none())
+                    .for_each(|group| {
+                        problems.push(format!("{}: unknown group {}", component.name, group))
+                    });
+            }
+        }
+        Err(e) => problems.push(format!("groups: {}", e)),
+    }
+
+    if problems.is_empty() {
+        println!(
+            "| {} OK: {} COMPONENTS",
+            policy_path,
+            policy.get_policy().len()
+        );
+        Ok(())
+    } else {
+        problems
+            .iter()
+            .for_each(|problem| println!("| {}", problem));
+        Err(format!(
+            "{} problems found in {}",
+            problems.len(),
+            policy_path
+        ))
+    }
+}
@@ -1,50 +1,206 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use log::LevelFilter;
 use policy_handler::PolicyHandler;
-use ship::Ship;
-use shipcomponent::ShipComponent;
-fn main() {
-    // Getting policy parameters for components
-    let policy = PolicyHandler::new(String::from("./policies/policy_0.toml"));
-
-    // Setting up ship components accordigly
-    let mut ship_components: Vec<ShipComponent> = Vec::new();
-    let policy = policy.get_policy();
-    policy.iter().for_each(|component| {
-        ship_components.push(ShipComponent::new(
-            component.name.clone(),
-            component.iface.clone(),
-            component.mac.clone(),
-            component.ip.clone(),
-            component.sends.clone(),
-            component.receives.clone(),
-        ));
-    });
-
-    // Setting up ship
-    let mut ship = Ship::new(ship_components);
-    println!("STARTING SIMULATION");
-    ship.monitor_network();
+use replay::{Replay, ReplaySummary};
+use ship::{control_request, ControlRequest};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+mod check;
+mod report;
+mod run;
+mod topology;
 
-    println!("----------------------------------");
-    ship.components.iter().for_each(|component| {
-        println!(
-            "[{}] - [TOTAL SENT: {:.2}Mb] [TOTAL RECEIVED: {:.2}Mb] [BITRATE SEND: {:.2}Mbit/s] [BITRATE RECEIVE: {:.2}Mbit/s] [TOTAL ANALYSIS TIME: {:.2}]",
-            component.name,
-            component.stats.total_bytes_sent / 1000000.0,
-            component.stats.total_bytes_received / 1000000.0,
-            (component.stats.total_bytes_sent * 8.0
-                / component.stats.performance_send[component.stats.performance_send.len() - 1].0)
-                / 1000000.0,
-                            (component.stats.total_bytes_received * 8.0
-                / component.stats.performance_receive[component.stats.performance_receive.len() - 1].0)
-                / 1000000.0,
-                component.stats.performance_send[component.stats.performance_send.len() - 1].0
+#[derive(Parser)]
+#[command(about = "Application layer firewall for the NMEA network of a ship")]
+struct Cli {
+    /// Policy file describing the components and what they may send and receive
+    #[arg(long, global = true, default_value = "./policies/policy_0.toml")]
+    policy: String,
+
+    /// off, error, warn, info, debug or trace
+    #[arg(long, global = true, default_value = "info")]
+    log_level: LevelFilter,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Attach to the components' interfaces and filter their traffic
+    Run(RunArgs),
+    /// Validate the policy file without touching the network
+    Check,
+    /// Print the components of the policy
+    ShowPolicy,
+    /// Run a recorded pcap/pcapng capture through the policy
+    Replay { capture: PathBuf },
+    /// Create or remove the network namespaces of the policy's components
+    Topology {
+        #[command(subcommand)]
+        action: TopologyAction,
+        /// Print the commands instead of running them
+        #[arg(long, global = true)]
+        dry_run: bool,
+    },
+    /// Send a request to the control socket of a running ship
+    Ctl {
+        #[arg(long, default_value = "/tmp/l7-control.sock")]
+        socket: PathBuf,
+        #[command(subcommand)]
+        request: CtlCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum CtlCommand {
+    /// Live counters of every component
+    Stats,
+    /// MAC addresses learned by the ship switch
+    Switch,
+    /// Sends and receives of every component
+    Rules,
+    /// Latest drops of every component with their reason
+    RecentDrops,
+    /// Reload the policy the ship was started with, or another file
+    Reload {
+        policy: Option<String>,
+    },
+    Enable {
+        component: String,
+    },
+    Disable {
+        component: String,
+    },
+    /// off, error, warn, info, debug or trace
+    LogLevel {
+        level: String,
+    },
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    /// Seconds to monitor the network for once the first frame arrives
+    #[arg(long)]
+    pub duration: Option<u64>,
+
+    /// Serve OpenMetrics on this address, overriding the policy's [metrics] section
+    #[arg(long)]
+    pub metrics_addr: Option<String>,
+
+    /// Answer operator requests on this unix socket, overriding the policy's [control] section
+    #[arg(long)]
+    pub control_socket: Option<PathBuf>,
+
+    /// Directory for the plots and the run report, overriding the policy's [plots] and [report] sections
+    #[arg(long)]
+    pub stats_dir: Option<PathBuf>,
+
+    /// Packet I/O backend used to attach to the interfaces
+    #[arg(long, value_enum, default_value_t = Backend::AfXdp)]
+    pub backend: Backend,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Backend {
+    /// AF_XDP sockets fed by an XDP redirect program
+    AfXdp,
+}
 
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::AfXdp => write!(f, "AF_XDP"),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum TopologyAction {
+    Up,
+    Down,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    // the logger lets everything through, the level is the global max level so that
+    // the control socket can change it at runtime
+    env_logger::Builder::new()
+        .filter_level(LevelFilter::Trace)
+        .format_target(false)
+        .init();
+    log::set_max_level(cli.log_level);
+
+    let result = match &cli.command {
+        Command::Run(args) => run::run(&cli.policy, args),
+        Command::Check => check::check(&cli.policy),
+        Command::ShowPolicy => {
+            PolicyHandler::new(cli.policy.clone()).map(|policy| policy.show_policy())
+        }
+        Command::Replay { capture } => replay(&cli.policy, capture),
+        Command::Topology { action, dry_run } => topology::topology(&cli.policy, action, *dry_run),
+        Command::Ctl { socket, request } => ctl(socket, request),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("| ERROR: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn ctl(socket: &Path, request: &CtlCommand) -> Result<(), String> {
+    let request = match request {
+        CtlCommand::Stats => ControlRequest::Stats,
+        CtlCommand::Switch => ControlRequest::Switch,
+        CtlCommand::Rules => ControlRequest::Rules,
+        CtlCommand::RecentDrops => ControlRequest::RecentDrops,
+        CtlCommand::Reload { policy } => ControlRequest::Reload {
+            policy: policy.clone(),
+        },
+        CtlCommand::Enable { component } => ControlRequest::Enable {
+            component: component.clone(),
+        },
+        CtlCommand::Disable { component } => ControlRequest::Disable {
+            component: component.clone(),
+        },
+        CtlCommand::LogLevel { level } => ControlRequest::LogLevel {
+            level: level.clone(),
+        },
+    };
+
+    let reply = control_request(socket, &request)
+        .map_err(|e| format!("control socket {}: {}", socket.display(), e))?;
+
+    if reply["ok"].as_bool() == Some(true) {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&reply["result"]).unwrap_or_default()
         );
-    });
+        Ok(())
+    } else {
+        Err(reply["error"]
+            .as_str()
+            .unwrap_or("unknown error")
+            .to_string())
+    }
+}
+
+fn replay(policy_path: &str, capture: &PathBuf) -> Result<(), String> {
+    let policy = PolicyHandler::new(policy_path.to_string())?;
+    let mut replay = Replay::from_policy(&policy)?;
+
+    let verdicts = replay
+        .replay_file(capture)
+        .map_err(|e| format!("replay of {} failed: {}", capture.display(), e))?;
+
+    verdicts.iter().for_each(|verdict| println!("{}", verdict));
+    println!("----------------------------------");
+    println!("{}", ReplaySummary::new(&verdicts));
 
-    ship.components.iter().for_each(|component| {
-        let _ = component
-            .stats
-            .plot_performance(&format!("{}-{}", component.ifname, component.name));
-    });
+    Ok(())
 }
@@ -0,0 +1,242 @@
+use serde::Serialize;
+use shipcomponent::{BucketRing, ShipComponent};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+// everything a run measured, in a shape that can be diffed between runs
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    pub started_at: String,
+    pub policy: String,
+    pub duration_s: f64,
+    pub components: Vec<ComponentReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComponentReport {
+    pub name: String,
+    pub iface: String,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    // seconds from the start of the run to the last frame sent or received
+    pub active_time_s: f64,
+    pub average_send_mbps: f64,
+    pub average_receive_mbps: f64,
+    pub average_send_pps: f64,
+    pub average_receive_pps: f64,
+    pub peak_send_mbps: f64,
+    pub peak_receive_mbps: f64,
+    pub p99_send_mbps: f64,
+    pub p99_receive_mbps: f64,
+    pub drops: BTreeMap<String, u64>,
+    pub umem_allocation_failures: u64,
+    pub tx_failures: u64,
+    // forwarding latency of the packets delivered to this component, by ingress component
+    pub latency: Vec<LatencyReport>,
+    // policy decisions on the sentences sent by this component, by prefix
+    pub policy_hits: BTreeMap<String, PolicyHits>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencyReport {
+    pub ingress: String,
+    pub samples: u64,
+    pub mean_us: f64,
+    pub p50_us: f64,
+    pub p99_us: f64,
+    pub p999_us: f64,
+    pub max_us: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PolicyHits {
+    pub allowed: u64,
+    pub denied: u64,
+}
+
+impl RunReport {
+    pub fn new(
+        started_at: String,
+        policy: &str,
+        duration: Duration,
+        components: &[ShipComponent],
+    ) -> Self {
+        // the policy is a map, sorting keeps reports of different runs in the same order
+        let mut component_reports: Vec<ComponentReport> = components
+            .iter()
+            .map(|component| ComponentReport::new(component, components))
+            .collect();
+        component_reports.sort_by(|a, b| a.name.cmp(&b.name));
+
+        RunReport {
+            started_at,
+            policy: policy.to_string(),
+            duration_s: duration.as_secs_f64(),
+            components: component_reports,
+        }
+    }
+
+    pub fn write_json(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+
+    // long format, one value per line, so that new metrics never change the columns
+    pub fn write_csv(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "component,metric,key,value")?;
+
+        for component in self.components.iter() {
+            let mut row = |metric: &str, key: &str, value: f64| {
+                writeln!(
+                    writer,
+                    "{},{},{},{}",
+                    csv_field(&component.name),
+                    metric,
+                    csv_field(key),
+                    value
+                )
+            };
+
+            row("packets_sent", "", component.packets_sent as f64)?;
+            row("packets_received", "", component.packets_received as f64)?;
+            row("bytes_sent", "", component.bytes_sent as f64)?;
+            row("bytes_received", "", component.bytes_received as f64)?;
+            row("active_time_s", "", component.active_time_s)?;
+            row("average_send_mbps", "", component.average_send_mbps)?;
+            row("average_receive_mbps", "", component.average_receive_mbps)?;
+            row("average_send_pps", "", component.average_send_pps)?;
+            row("average_receive_pps", "", component.average_receive_pps)?;
+            row("peak_send_mbps", "", component.peak_send_mbps)?;
+            row("peak_receive_mbps", "", component.peak_receive_mbps)?;
+            row("p99_send_mbps", "", component.p99_send_mbps)?;
+            row("p99_receive_mbps", "", component.p99_receive_mbps)?;
+            row(
+                "umem_allocation_failures",
+                "",
+                component.umem_allocation_failures as f64,
+            )?;
+            row("tx_failures", "", component.tx_failures as f64)?;
+
+            for (reason, count) in component.drops.iter() {
+                row("drops", reason, *count as f64)?;
+            }
+
+            for latency in component.latency.iter() {
+                row("latency_samples", &latency.ingress, latency.samples as f64)?;
+                row("latency_mean_us", &latency.ingress, latency.mean_us)?;
+                row("latency_p50_us", &latency.ingress, latency.p50_us)?;
+                row("latency_p99_us", &latency.ingress, latency.p99_us)?;
+                row("latency_p999_us", &latency.ingress, latency.p999_us)?;
+                row("latency_max_us", &latency.ingress, latency.max_us)?;
+            }
+
+            for (prefix, hits) in component.policy_hits.iter() {
+                row("policy_allowed", prefix, hits.allowed as f64)?;
+                row("policy_denied", prefix, hits.denied as f64)?;
+            }
+        }
+
+        writer.flush()
+    }
+}
+
+impl ComponentReport {
+    fn new(component: &ShipComponent, components: &[ShipComponent]) -> Self {
+        let stats = &component.stats;
+        let send_summary = BucketRing::summary(&stats.buckets.throughput_sent());
+        let receive_summary = BucketRing::summary(&stats.buckets.throughput_received());
+
+        let mut latency: Vec<LatencyReport> = stats
+            .latency
+            .iter()
+            .map(|(ingress, histogram)| LatencyReport {
+                ingress: components
+                    .get(*ingress)
+                    .map(|ingress| ingress.name.clone())
+                    .unwrap_or_else(|| String::from("unknown")),
+                samples: histogram.len(),
+                mean_us: histogram.mean() / 1000.0,
+                p50_us: histogram.value_at_quantile(0.5) as f64 / 1000.0,
+                p99_us: histogram.value_at_quantile(0.99) as f64 / 1000.0,
+                p999_us: histogram.value_at_quantile(0.999) as f64 / 1000.0,
+                max_us: histogram.max() as f64 / 1000.0,
+            })
+            .collect();
+        latency.sort_by(|a, b| a.ingress.cmp(&b.ingress));
+
+        // in filter mode the denied sentences are cut out of datagrams that are still forwarded,
+        // the allowed ones are counted as they pass rather than derived from what was sent
+        let policy_hits = stats
+            .sentences_allowed
+            .keys()
+            .chain(stats.sentences_denied.keys())
+            .map(|prefix| {
+                (
+                    prefix.clone(),
+                    PolicyHits {
+                        allowed: stats.sentences_allowed.get(prefix).copied().unwrap_or(0),
+                        denied: stats.sentences_denied.get(prefix).copied().unwrap_or(0),
+                    },
+                )
+            })
+            .collect();
+
+        ComponentReport {
+            name: component.name.clone(),
+            iface: component.ifname.clone(),
+            packets_sent: stats.packets_sent,
+            packets_received: stats.packets_received,
+            bytes_sent: stats.total_bytes_sent as u64,
+            bytes_received: stats.total_bytes_received as u64,
+            active_time_s: stats.last_activity,
+            average_send_mbps: rate(
+                stats.total_bytes_sent * 8.0 / 1000000.0,
+                stats.last_activity,
+            ),
+            average_receive_mbps: rate(
+                stats.total_bytes_received * 8.0 / 1000000.0,
+                stats.last_activity,
+            ),
+            average_send_pps: rate(stats.packets_sent as f64, stats.last_activity),
+            average_receive_pps: rate(stats.packets_received as f64, stats.last_activity),
+            peak_send_mbps: send_summary.max,
+            peak_receive_mbps: receive_summary.max,
+            p99_send_mbps: send_summary.p99,
+            p99_receive_mbps: receive_summary.p99,
+            drops: stats
+                .drops
+                .iter()
+                .map(|(reason, count)| (reason.to_string(), *count))
+                .collect(),
+            umem_allocation_failures: stats.umem_allocation_failures,
+            tx_failures: stats.tx_failures,
+            latency,
+            policy_hits,
+        }
+    }
+}
+
+// a component that saw no traffic has no active time, its rates are zero
+pub fn rate(amount: f64, seconds: f64) -> f64 {
+    if seconds > 0.0 {
+        amount / seconds
+    } else {
+        0.0
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,272 @@
+use crate::report::{rate, RunReport};
+use crate::RunArgs;
+use firewall::{AuditLevel, AuditLog, SentenceSchemas, TransmissionGroups};
+use pcap_handler::PcapRecorder;
+use policy_handler::{ComponentConfig, PolicyHandler};
+use ship::{ControlSocket, MetricsExporter, Ship};
+use shipcomponent::{plot_dashboard, BucketRing, Chart, ImageFormat, PlotConfig, ShipComponent};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub fn run(policy_path: &str, args: &RunArgs) -> Result<(), String> {
+    // Getting policy parameters for components
+    let policy = PolicyHandler::new(policy_path.to_string())?;
+
+    // Setting up ship components accordigly
+    let mut ship_components: Vec<ShipComponent> = Vec::new();
+    let capture = policy.get_capture();
+    let audit = policy.get_audit();
+    let metrics = policy.get_metrics();
+    let plots = policy.get_plots();
+    let report = policy.get_report();
+    let control = policy.get_control();
+    let switch = policy.get_switch();
+    let arp = policy.get_arp();
+    let igmp = policy.get_igmp();
+    let reassembly = policy.get_reassembly();
+    let schemas = Arc::new(
+        SentenceSchemas::load(&policy.get_sentences().schemas)
+            .map_err(|e| format!("sentences: {}", e))?,
+    );
+    let groups = TransmissionGroups::configure(&policy.get_groups())
+        .map_err(|e| format!("groups: {}", e))?;
+    for component in policy.get_policy().iter() {
+        // the inspection settings are the same the reload and the replay of a capture use
+        let config = ComponentConfig::from_policy(&policy, &component.name)?;
+
+        let mut ship_component = ShipComponent::new(
+            component.name.clone(),
+            component.iface.clone(),
+            component.mac.clone(),
+            component.ip.clone(),
+            component.sends.clone(),
+            component.receives.clone(),
+        )?;
+        ship_component.ipv6 = component.ipv6.clone();
+        ship_component.binding = config.binding;
+        ship_component.reassembler = config.reassembler;
+        ship_component.tcp = config.tcp;
+        ship_component.multi_sentence = config.multi_sentence;
+        ship_component.validator = config.validator;
+        ship_component.proprietary = config.proprietary;
+        ship_component.groups = config.groups;
+        ship_component.schemas = config.schemas;
+
+        ship_components.push(ship_component);
+    }
+
+    // Setting up traffic capture on every component's interface
+    if let Some(capture) = capture {
+        ship_components.iter_mut().for_each(|component| {
+            match PcapRecorder::new(
+                PathBuf::from(&capture.directory),
+                component.ifname.clone(),
+                capture.split,
+                capture.max_file_size,
+                Duration::from_secs(capture.max_file_duration),
+            ) {
+                Ok(recorder) => component.recorder = Some(recorder),
+                Err(e) => log::error!("| CAPTURE SETUP FOR {} FAILED: {}", component.ifname, e),
+            }
+        });
+    }
+
+    // Setting up ship
+    let mut ship = Ship::new(ship_components);
+    if let Some(switch) = switch {
+        ship.ship_switch.aging_time = Duration::from_secs(switch.aging_time);
+        ship.ship_switch.max_macs_per_port = switch.max_macs_per_port;
+    }
+    if let Some(reassembly) = &reassembly {
+        ship.mtu = reassembly.mtu;
+    }
+    ship.groups = groups;
+    ship.schemas = schemas;
+    ship.ship_switch.arp.proxy = arp.proxy;
+    ship.ship_switch.arp.inspection = arp.inspection;
+    ship.ship_switch.igmp.enabled = igmp.snooping;
+    ship.ship_switch.igmp.membership_interval = Duration::from_secs(igmp.membership_interval);
+    if let Some(duration) = args.duration {
+        ship.duration = Duration::from_secs(duration);
+    }
+
+    // Setting up the audit log of the firewall decisions
+    if let Some(audit) = audit {
+        let level = AuditLevel::parse(&audit.level).unwrap_or_else(|| {
+            log::warn!("| UNKNOWN AUDIT LEVEL {}, USING DROPS", audit.level);
+            AuditLevel::Drops
+        });
+
+        match AuditLog::new(
+            &audit.path,
+            level,
+            audit.max_events_per_second,
+            audit.sample_allowed,
+        ) {
+            Ok(audit_log) => ship.audit_log = Some(audit_log),
+            Err(e) => log::error!("| AUDIT LOG SETUP FAILED: {}", e),
+        }
+    }
+    // Setting up the metrics endpoint, the address on the command line wins over the policy
+    let metrics = match (&args.metrics_addr, metrics) {
+        (Some(address), metrics) => Some((
+            address.clone(),
+            metrics.map_or(1000, |metrics| metrics.refresh_interval_ms),
+        )),
+        (None, metrics) => metrics.map(|metrics| (metrics.address, metrics.refresh_interval_ms)),
+    };
+    if let Some((address, refresh_interval_ms)) = metrics {
+        let exporter = MetricsExporter::start(&address, Duration::from_millis(refresh_interval_ms))
+            .map_err(|e| format!("metrics exporter on {} failed: {}", address, e))?;
+        ship.metrics = Some(exporter);
+    }
+
+    // Setting up the control socket, the path on the command line wins over the policy
+    let control_group = control.as_ref().and_then(|control| control.group.clone());
+    let control_path = args
+        .control_socket
+        .clone()
+        .or_else(|| control.map(|control| PathBuf::from(control.path)));
+    if let Some(path) = control_path {
+        let control = ControlSocket::bind(&path, policy_path, control_group.as_deref())
+            .map_err(|e| format!("control socket {} failed: {}", path.display(), e))?;
+        ship.control = Some(control);
+    }
+
+    log::info!("STARTING SIMULATION ON {} BACKEND", args.backend);
+    let started_at = chrono::Utc::now().to_rfc3339();
+    let start = Instant::now();
+    ship.monitor_network();
+    let run_duration = start.elapsed();
+
+    println!("----------------------------------");
+    ship.components.iter().for_each(|component| {
+        let send_summary = BucketRing::summary(&component.stats.buckets.throughput_sent());
+        let receive_summary = BucketRing::summary(&component.stats.buckets.throughput_received());
+        println!(
+            "[{}] - [TOTAL SENT: {:.2}Mb] [TOTAL RECEIVED: {:.2}Mb] [BITRATE SEND: {:.2}Mbit/s] [BITRATE RECEIVE: {:.2}Mbit/s] [PEAK SEND: {:.2}Mbit/s] [PEAK RECEIVE: {:.2}Mbit/s] [P99 SEND: {:.2}Mbit/s] [P99 RECEIVE: {:.2}Mbit/s] [TOTAL ANALYSIS TIME: {:.2}]",
+            component.name,
+            component.stats.total_bytes_sent / 1000000.0,
+            component.stats.total_bytes_received / 1000000.0,
+            rate(component.stats.total_bytes_sent * 8.0 / 1000000.0, component.stats.last_activity),
+            rate(component.stats.total_bytes_received * 8.0 / 1000000.0, component.stats.last_activity),
+            send_summary.max,
+            receive_summary.max,
+            send_summary.p99,
+            receive_summary.p99,
+            component.stats.last_activity
+        );
+    });
+
+    ship.components.iter().for_each(|egress| {
+        egress.stats.latency.iter().for_each(|(ingress, histogram)| {
+            println!(
+                "[{} -> {}] - [P50: {:.2}us] [P99: {:.2}us] [P99.9: {:.2}us] [MAX: {:.2}us] [SAMPLES: {}]",
+                ship.components[*ingress].name,
+                egress.name,
+                histogram.value_at_quantile(0.5) as f64 / 1000.0,
+                histogram.value_at_quantile(0.99) as f64 / 1000.0,
+                histogram.value_at_quantile(0.999) as f64 / 1000.0,
+                histogram.max() as f64 / 1000.0,
+                histogram.len()
+            );
+        });
+    });
+
+    // Writing the machine readable report of the run, next to the plots when a stats directory is given
+    let (json_path, csv_path) = match (&args.stats_dir, report) {
+        (Some(stats_dir), _) => (
+            Some(stats_dir.join("report.json")),
+            Some(stats_dir.join("report.csv")),
+        ),
+        (None, Some(report)) => (
+            report.json.map(PathBuf::from),
+            report.csv.map(PathBuf::from),
+        ),
+        (None, None) => (None, None),
+    };
+    if json_path.is_some() || csv_path.is_some() {
+        if let Some(stats_dir) = &args.stats_dir {
+            std::fs::create_dir_all(stats_dir)
+                .map_err(|e| format!("cannot create {}: {}", stats_dir.display(), e))?;
+        }
+
+        let run_report = RunReport::new(started_at, policy_path, run_duration, &ship.components);
+        if let Some(path) = json_path {
+            if let Err(e) = run_report.write_json(&path) {
+                log::error!("| JSON REPORT {} FAILED: {}", path.display(), e);
+            }
+        }
+        if let Some(path) = csv_path {
+            if let Err(e) = run_report.write_csv(&path) {
+                log::error!("| CSV REPORT {} FAILED: {}", path.display(), e);
+            }
+        }
+    }
+
+    if let Some(audit_log) = ship.audit_log.as_mut() {
+        if let Err(e) = audit_log.flush() {
+            log::error!("| AUDIT LOG FLUSH FAILED: {}", e);
+        }
+    }
+
+    ship.components.iter_mut().for_each(|component| {
+        if let Some(recorder) = component.recorder.as_mut() {
+            if let Err(e) = recorder.flush() {
+                log::error!("| CAPTURE FLUSH FOR {} FAILED: {}", component.ifname, e);
+            }
+        }
+    });
+
+    // Setting up the charts drawn at the end of the run
+    let mut plot_config = PlotConfig::default();
+    if let Some(plots) = plots {
+        plot_config.directory = PathBuf::from(&plots.directory);
+        plot_config.format = ImageFormat::parse(&plots.format).unwrap_or_else(|| {
+            log::warn!("| UNKNOWN PLOT FORMAT {}, USING PNG", plots.format);
+            ImageFormat::Png
+        });
+        plot_config.auto_scale = plots.auto_scale;
+        plot_config.time_range = plots.time_range;
+        plot_config.throughput_range = plots.throughput_range;
+        plot_config.packet_rate_range = plots.packet_rate_range;
+        plot_config.charts = plots
+            .charts
+            .iter()
+            .filter_map(|chart| {
+                let parsed = Chart::parse(chart);
+                if parsed.is_none() {
+                    log::warn!("| UNKNOWN CHART {}, SKIPPING", chart);
+                }
+                parsed
+            })
+            .collect();
+        plot_config.dashboard = plots.dashboard;
+    }
+    if let Some(stats_dir) = &args.stats_dir {
+        plot_config.directory = stats_dir.clone();
+    }
+
+    ship.components.iter().for_each(|component| {
+        if let Err(e) = component.stats.plot_performance(
+            &format!("{}-{}", component.ifname, component.name),
+            &plot_config,
+        ) {
+            log::error!("| PLOTTING {} FAILED: {}", component.name, e);
+        }
+    });
+
+    if plot_config.dashboard {
+        let components: Vec<(&str, &shipcomponent::ShipComponentStats)> = ship
+            .components
+            .iter()
+            .map(|component| (component.name.as_str(), &component.stats))
+            .collect();
+        if let Err(e) = plot_dashboard(&components, &plot_config) {
+            log::error!("| PLOTTING DASHBOARD FAILED: {}", e);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use crate::TopologyAction;
+use policy_handler::{Component, PolicyHandler};
+use std::process::Command;
+
+// the namespaces and veths the Makefile builds, one per component, named after its interface.
+// the host side of each veth gets no address or route: the firewall bridges frames
+// between the veths and does not need them, unlike pinging from the host
+pub fn topology(policy_path: &str, action: &TopologyAction, dry_run: bool) -> Result<(), String> {
+    let policy = PolicyHandler::new(policy_path.to_string())?;
+    let mut components = policy.get_policy();
+    components.sort_by(|a, b| a.iface.cmp(&b.iface));
+
+    for component in components.iter() {
+        match action {
+            TopologyAction::Up => {
+                for command in up_commands(component) {
+                    execute(&command, dry_run, true)?;
+                }
+            }
+            TopologyAction::Down => {
+                // whatever is already gone is not an error
+                for command in down_commands(component) {
+                    execute(&command, dry_run, false)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn up_commands(component: &Component) -> Vec<Vec<String>> {
+    let netns = component.iface.as_str();
+    let in_netns = |command: &str| format!("ip netns exec {} {}", netns, command);
+
+    [
+        format!("ip netns add {}", netns),
+        in_netns("ip link set dev lo up"),
+        in_netns(&format!("ip link add eth0 type veth peer name {}", netns)),
+        in_netns(&format!("ip link set dev {} netns 1", netns)),
+        in_netns(&format!("ip link set dev eth0 address {}", component.mac)),
+        in_netns(&format!("ip addr add {}/24 dev eth0", component.ip)),
+        in_netns("ip link set dev eth0 up"),
+        format!("ip link set dev {} up", netns),
+        format!("ethtool -K {} tx off", netns),
+        in_netns("ethtool -K eth0 tx off"),
+    ]
+    .iter()
+    .map(|command| split(command))
+    .collect()
+}
+
+fn down_commands(component: &Component) -> Vec<Vec<String>> {
+    [
+        format!("ip netns del {}", component.iface),
+        format!("ip link del {}", component.iface),
+    ]
+    .iter()
+    .map(|command| split(command))
+    .collect()
+}
+
+fn split(command: &str) -> Vec<String> {
+    command.split_whitespace().map(String::from).collect()
+}
+
+fn execute(command: &[String], dry_run: bool, must_succeed: bool) -> Result<(), String> {
+    let line = command.join(" ");
+    if dry_run {
+        println!("{}", line);
+        return Ok(());
+    }
+
+    log::info!("| {}", line);
+    let output = Command::new(&command[0])
+        .args(&command[1..])
+        .output()
+        .map_err(|e| format!("cannot run {}: {}", command[0], e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if must_succeed {
+            return Err(format!("{} failed: {}", line, stderr.trim()));
+        }
+        log::debug!("| {} failed: {}", line, stderr.trim());
+    }
+
+    Ok(())
+}